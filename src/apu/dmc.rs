@@ -0,0 +1,151 @@
+//! Delta modulation channel, which plays 1-bit delta-encoded samples read from memory by DMA
+//!
+//! See <https://www.nesdev.org/wiki/APU_DMC>.
+
+use super::unit::Timer;
+use crate::memory;
+
+/// Timer periods in CPU cycles, indexed by the low four bits of the flags register
+const RATES: [u16; 16] = [
+  428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+const IRQ_ENABLED: u8 = 0b1000_0000;
+const LOOP: u8 = 0b0100_0000;
+const RATE: u8 = 0b0000_1111;
+const LEVEL: u8 = 0b0111_1111;
+
+const SAMPLE_ADDRESS_START: memory::Address = 0xC000;
+/// Sample addresses wrap around to here after the end of the address space
+const SAMPLE_ADDRESS_WRAP: memory::Address = 0x8000;
+
+#[derive(Debug, Default)]
+pub(super) struct Dmc {
+  irq_enabled: bool,
+  looping: bool,
+  /// Whether the channel is asserting the IRQ line, after a sample finished
+  pub(super) irq: bool,
+  timer: Timer,
+  level: u8,
+  sample_address: memory::Address,
+  sample_length: u16,
+  address: memory::Address,
+  remaining: u16,
+  /// The last byte fetched by DMA, waiting to be played
+  buffer: Option<u8>,
+  /// The byte being played, or `None` while the output is silenced
+  shift: Option<u8>,
+  bits: u8,
+}
+
+impl Dmc {
+  /// Writes to one of the channel's four registers
+  pub(super) fn write(&mut self, register: u16, data: u8) {
+    match register {
+      0 => {
+        self.irq_enabled = data & IRQ_ENABLED != 0;
+        if !self.irq_enabled {
+          self.irq = false;
+        }
+        self.looping = data & LOOP != 0;
+        self.timer.period = RATES[usize::from(data & RATE)] - 1;
+      }
+      1 => self.level = data & LEVEL,
+      2 => self.sample_address = SAMPLE_ADDRESS_START | (u16::from(data) << 6),
+      _ => self.sample_length = (u16::from(data) << 4) | 1,
+    }
+  }
+
+  /// Starts the sample if it is not already playing, or stops it, which also acknowledges the IRQ
+  pub(super) fn set_enabled(&mut self, enabled: bool) {
+    self.irq = false;
+    if !enabled {
+      self.remaining = 0;
+    } else if self.remaining == 0 {
+      self.restart();
+    }
+  }
+
+  fn restart(&mut self) {
+    self.address = self.sample_address;
+    self.remaining = self.sample_length;
+  }
+
+  /// Whether there are bytes of the sample left to fetch
+  pub(super) fn is_active(&self) -> bool {
+    self.remaining > 0
+  }
+
+  /// Address the channel needs to fetch a byte from, if the buffer is empty and the sample is playing
+  pub(super) fn pending_read(&self) -> Option<memory::Address> {
+    (self.buffer.is_none() && self.remaining > 0).then_some(self.address)
+  }
+
+  /// Fills the buffer with a byte fetched by DMA, and moves on through the sample
+  pub(super) fn fill(&mut self, data: u8) {
+    self.buffer = Some(data);
+    self.address = self.address.checked_add(1).unwrap_or(SAMPLE_ADDRESS_WRAP);
+    self.remaining -= 1;
+    if self.remaining == 0 {
+      if self.looping {
+        self.restart();
+      } else if self.irq_enabled {
+        self.irq = true;
+      }
+    }
+  }
+
+  /// Clocked every CPU cycle
+  pub(super) fn clock_timer(&mut self) {
+    if !self.timer.clock() {
+      return;
+    }
+
+    if let Some(shift) = self.shift {
+      if shift & 1 == 1 {
+        if self.level <= 125 {
+          self.level += 2;
+        }
+      } else if self.level >= 2 {
+        self.level -= 2;
+      }
+      self.shift = Some(shift >> 1);
+    }
+
+    self.bits = self.bits.saturating_sub(1);
+    if self.bits == 0 {
+      self.bits = 8;
+      self.shift = self.buffer.take();
+    }
+  }
+
+  /// Current output level, 0-127
+  pub(super) fn output(&self) -> u8 {
+    self.level
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn plays_sample_and_raises_irq() {
+    let mut dmc = Dmc::default();
+    dmc.write(0, IRQ_ENABLED | 0x0F);
+    dmc.write(1, 0x40);
+    dmc.write(2, 0xFF);
+    dmc.write(3, 0x00);
+    dmc.set_enabled(true);
+
+    assert_eq!(Some(0xFFC0), dmc.pending_read());
+    dmc.fill(0xFF);
+    assert_eq!(None, dmc.pending_read());
+    assert!(dmc.irq);
+
+    for _ in 0..9 * RATES[0x0F] {
+      dmc.clock_timer();
+    }
+    assert_eq!(0x40 + 16, dmc.output());
+  }
+}
@@ -0,0 +1,408 @@
+//! Emulation of the 2A03's audio processing unit, mixing its five channels into PCM samples
+//!
+//! See <https://www.nesdev.org/wiki/APU>.
+
+mod dmc;
+mod noise;
+mod pulse;
+mod triangle;
+mod unit;
+
+use std::{fmt, vec};
+
+use crate::{cartridge::Mapper, memory};
+
+/// Frequency of the NTSC CPU, and so of the APU's clock
+pub const CPU_FREQUENCY: u32 = 1_789_773;
+
+// Register numbers, as offsets from $4000
+pub const STATUS: memory::Address = 0x15;
+pub const FRAME_COUNTER: memory::Address = 0x17;
+
+const STATUS_PULSE_1: u8 = 0b0000_0001;
+const STATUS_PULSE_2: u8 = 0b0000_0010;
+const STATUS_TRIANGLE: u8 = 0b0000_0100;
+const STATUS_NOISE: u8 = 0b0000_1000;
+const STATUS_DMC: u8 = 0b0001_0000;
+const STATUS_FRAME_IRQ: u8 = 0b0100_0000;
+const STATUS_DMC_IRQ: u8 = 0b1000_0000;
+
+const FRAME_COUNTER_FIVE_STEP: u8 = 0b1000_0000;
+const FRAME_COUNTER_IRQ_INHIBIT: u8 = 0b0100_0000;
+
+// CPU cycles since the frame counter was reset at which each step is taken
+const FRAME_STEP_1: u32 = 7457;
+const FRAME_STEP_2: u32 = 14913;
+const FRAME_STEP_3: u32 = 22371;
+const FRAME_STEP_4: u32 = 29829;
+const FRAME_STEP_5: u32 = 37281;
+
+/// CPU cycles the CPU is stalled for while the DMC fetches a byte
+///
+/// This varies from 1 to 4 on hardware, depending on what the CPU is doing at the time.
+const DMC_STALL_CYCLES: u16 = 4;
+
+/// The APU's channels, frame counter and mixer
+///
+/// Samples are produced at the configured rate and collect in a buffer until they are drained with
+/// [`Apu::drain_samples`]. The default rate is 0, so hosts which play audio need to set one.
+pub struct Apu {
+  pulses: [pulse::Pulse; 2],
+  triangle: triangle::Triangle,
+  noise: noise::Noise,
+  dmc: dmc::Dmc,
+  five_step: bool,
+  irq_inhibit: bool,
+  frame_irq: bool,
+  /// CPU cycles since the frame counter was reset
+  frame_cycle: u32,
+  /// CPU cycles since power on, used to clock the pulse channels on every other cycle
+  cycles: u64,
+  sample_rate: u32,
+  /// Accumulates the sample rate every cycle, producing a sample each time it passes the CPU frequency
+  sample_clock: u32,
+  samples: Vec<f32>,
+}
+
+impl fmt::Debug for Apu {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Apu")
+      .field("pulses", &self.pulses)
+      .field("triangle", &self.triangle)
+      .field("noise", &self.noise)
+      .field("dmc", &self.dmc)
+      .field("status", &format_args!("{:#010b}", self.status()))
+      .field("frame_cycle", &self.frame_cycle)
+      .field("sample_rate", &self.sample_rate)
+      .field("samples", &self.samples.len())
+      .finish_non_exhaustive()
+  }
+}
+
+impl Default for Apu {
+  fn default() -> Self {
+    Self::new(0)
+  }
+}
+
+impl Apu {
+  /// An APU producing samples at the given rate in hertz
+  #[must_use]
+  pub fn new(sample_rate: u32) -> Self {
+    Self {
+      pulses: [pulse::Pulse::new(true), pulse::Pulse::new(false)],
+      triangle: triangle::Triangle::default(),
+      noise: noise::Noise::default(),
+      dmc: dmc::Dmc::default(),
+      five_step: false,
+      irq_inhibit: false,
+      frame_irq: false,
+      frame_cycle: 0,
+      cycles: 0,
+      sample_rate,
+      sample_clock: 0,
+      samples: Vec::new(),
+    }
+  }
+
+  #[must_use]
+  pub fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  /// Changes the output rate in hertz, where 0 stops samples being produced
+  pub fn set_sample_rate(&mut self, sample_rate: u32) {
+    self.sample_rate = sample_rate;
+    self.sample_clock = 0;
+  }
+
+  /// Removes and returns the samples produced so far, each between 0.0 and 1.0
+  pub fn drain_samples(&mut self) -> vec::Drain<'_, f32> {
+    self.samples.drain(..)
+  }
+
+  /// Whether the frame counter or DMC is asserting the CPU's IRQ line
+  #[must_use]
+  pub fn irq(&self) -> bool {
+    self.frame_irq || self.dmc.irq
+  }
+
+  /// Reads the status register, which acknowledges the frame counter's IRQ
+  pub fn read_status(&mut self) -> u8 {
+    let status = self.status();
+    self.frame_irq = false;
+    status
+  }
+
+  /// Reads the status register without any side effects
+  #[must_use]
+  pub fn peek_status(&self) -> u8 {
+    self.status()
+  }
+
+  fn status(&self) -> u8 {
+    [
+      (self.pulses[0].length.is_active(), STATUS_PULSE_1),
+      (self.pulses[1].length.is_active(), STATUS_PULSE_2),
+      (self.triangle.length.is_active(), STATUS_TRIANGLE),
+      (self.noise.length.is_active(), STATUS_NOISE),
+      (self.dmc.is_active(), STATUS_DMC),
+      (self.frame_irq, STATUS_FRAME_IRQ),
+      (self.dmc.irq, STATUS_DMC_IRQ),
+    ]
+    .into_iter()
+    .filter(|&(set, _)| set)
+    .fold(0, |status, (_, bit)| status | bit)
+  }
+
+  /// Writes to a register, given as an offset from $4000
+  ///
+  /// Offsets which are not APU registers are ignored.
+  pub fn write_register(&mut self, register: memory::Address, data: u8) {
+    match register {
+      0x00..=0x03 => self.pulses[0].write(register % 4, data),
+      0x04..=0x07 => self.pulses[1].write(register % 4, data),
+      0x08..=0x0B => self.triangle.write(register % 4, data),
+      0x0C..=0x0F => self.noise.write(register % 4, data),
+      0x10..=0x13 => self.dmc.write(register % 4, data),
+      STATUS => {
+        self.pulses[0]
+          .length
+          .set_enabled(data & STATUS_PULSE_1 != 0);
+        self.pulses[1]
+          .length
+          .set_enabled(data & STATUS_PULSE_2 != 0);
+        self
+          .triangle
+          .length
+          .set_enabled(data & STATUS_TRIANGLE != 0);
+        self.noise.length.set_enabled(data & STATUS_NOISE != 0);
+        self.dmc.set_enabled(data & STATUS_DMC != 0);
+      }
+      FRAME_COUNTER => {
+        self.five_step = data & FRAME_COUNTER_FIVE_STEP != 0;
+        self.irq_inhibit = data & FRAME_COUNTER_IRQ_INHIBIT != 0;
+        if self.irq_inhibit {
+          self.frame_irq = false;
+        }
+        // Hardware resets the sequence 3 or 4 cycles after the write, which is not emulated
+        self.frame_cycle = 0;
+        if self.five_step {
+          self.clock_quarter_frame();
+          self.clock_half_frame();
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /// Runs for a single CPU cycle, reading DMC samples through the mapper
+  ///
+  /// Returns the number of cycles the CPU is stalled for by the DMC's DMA.
+  pub fn tick(&mut self, mapper: &dyn Mapper) -> u16 {
+    if self.cycles % 2 == 1 {
+      for pulse in &mut self.pulses {
+        pulse.clock_timer();
+      }
+    }
+    self.triangle.clock_timer();
+    self.noise.clock_timer();
+    self.dmc.clock_timer();
+    self.clock_frame_counter();
+
+    let mut stall = 0;
+    if let Some(address) = self.dmc.pending_read() {
+      // Samples are always in $8000-$FFFF, which belongs to the cartridge
+      self.dmc.fill(mapper.read_program(address).unwrap_or(0));
+      stall = DMC_STALL_CYCLES;
+    }
+
+    self.cycles += 1;
+    self.sample();
+    stall
+  }
+
+  fn clock_frame_counter(&mut self) {
+    self.frame_cycle += 1;
+    match self.frame_cycle {
+      FRAME_STEP_1 | FRAME_STEP_3 => self.clock_quarter_frame(),
+      FRAME_STEP_2 | FRAME_STEP_5 => {
+        self.clock_quarter_frame();
+        self.clock_half_frame();
+      }
+      FRAME_STEP_4 if !self.five_step => {
+        self.clock_quarter_frame();
+        self.clock_half_frame();
+        if !self.irq_inhibit {
+          self.frame_irq = true;
+        }
+      }
+      _ => {}
+    }
+
+    let sequence_end = if self.five_step {
+      FRAME_STEP_5
+    } else {
+      FRAME_STEP_4
+    };
+    if self.frame_cycle > sequence_end {
+      self.frame_cycle = 0;
+    }
+  }
+
+  fn clock_quarter_frame(&mut self) {
+    for pulse in &mut self.pulses {
+      pulse.clock_quarter_frame();
+    }
+    self.triangle.clock_quarter_frame();
+    self.noise.clock_quarter_frame();
+  }
+
+  fn clock_half_frame(&mut self) {
+    for pulse in &mut self.pulses {
+      pulse.clock_half_frame();
+    }
+    self.triangle.clock_half_frame();
+    self.noise.clock_half_frame();
+  }
+
+  fn sample(&mut self) {
+    self.sample_clock += self.sample_rate;
+    if self.sample_clock >= CPU_FREQUENCY {
+      self.sample_clock -= CPU_FREQUENCY;
+      self.samples.push(self.mix());
+    }
+  }
+
+  /// Combines the channels' outputs the way the resistors on the 2A03's output pins do
+  ///
+  /// See <https://www.nesdev.org/wiki/APU_Mixer>.
+  #[must_use]
+  pub fn mix(&self) -> f32 {
+    let pulse = self.pulses[0].output() + self.pulses[1].output();
+    let pulse = if pulse == 0 {
+      0.0
+    } else {
+      95.88 / (8128.0 / f32::from(pulse) + 100.0)
+    };
+
+    let (triangle, noise, dmc) = (
+      self.triangle.output(),
+      self.noise.output(),
+      self.dmc.output(),
+    );
+    let tnd = if triangle == 0 && noise == 0 && dmc == 0 {
+      0.0
+    } else {
+      let tnd =
+        f32::from(triangle) / 8227.0 + f32::from(noise) / 12241.0 + f32::from(dmc) / 22638.0;
+      159.79 / (1.0 / tnd + 100.0)
+    };
+
+    pulse + tnd
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cartridge::mapper::{nrom::Nrom, Board};
+
+  fn mapper() -> Nrom {
+    let mut program_rom = vec![0; 0x8000];
+    program_rom[0x4000] = 0xFF;
+    Nrom::new(Board::with_program_rom(program_rom))
+  }
+
+  #[test]
+  fn frame_irq() {
+    let mut apu = Apu::default();
+    let mapper = mapper();
+
+    for _ in 0..FRAME_STEP_4 {
+      apu.tick(&mapper);
+    }
+
+    assert!(apu.irq());
+    assert_eq!(STATUS_FRAME_IRQ, apu.read_status());
+    assert!(!apu.irq());
+  }
+
+  #[test]
+  fn frame_irq_inhibit() {
+    let mut apu = Apu::default();
+    let mapper = mapper();
+    apu.write_register(FRAME_COUNTER, FRAME_COUNTER_IRQ_INHIBIT);
+
+    for _ in 0..FRAME_STEP_4 {
+      apu.tick(&mapper);
+    }
+
+    assert!(!apu.irq());
+  }
+
+  #[test]
+  fn length_counters_in_status() {
+    let mut apu = Apu::default();
+    let mapper = mapper();
+    apu.write_register(STATUS, STATUS_PULSE_2 | STATUS_NOISE);
+    apu.write_register(0x03, 0x08);
+    apu.write_register(0x07, 0x08);
+    apu.write_register(0x0F, 0x08);
+
+    assert_eq!(STATUS_PULSE_2 | STATUS_NOISE, apu.peek_status());
+
+    apu.write_register(STATUS, 0);
+    apu.tick(&mapper);
+    assert_eq!(0, apu.peek_status());
+  }
+
+  #[test]
+  fn dmc_stalls_cpu() {
+    let mut apu = Apu::default();
+    let mapper = mapper();
+    // Sample of one byte at $C000
+    apu.write_register(0x12, 0x00);
+    apu.write_register(0x13, 0x00);
+    apu.write_register(STATUS, STATUS_DMC);
+
+    assert_eq!(DMC_STALL_CYCLES, apu.tick(&mapper));
+    assert_eq!(0, apu.tick(&mapper));
+    assert_eq!(0, apu.peek_status() & STATUS_DMC);
+  }
+
+  #[test]
+  fn mixer() {
+    let mut apu = Apu::default();
+    // The triangle powers on at the top of its waveform
+    assert!((apu.mix() - 0.2464).abs() < 0.001);
+
+    apu.dmc.write(1, 0x7F);
+    assert!((apu.mix() - 0.6813).abs() < 0.001);
+  }
+
+  #[test]
+  fn sample_rate() {
+    let mut apu = Apu::new(48_000);
+    let mapper = mapper();
+
+    for _ in 0..CPU_FREQUENCY {
+      apu.tick(&mapper);
+    }
+
+    assert_eq!(48_000, apu.drain_samples().count());
+    assert_eq!(0, apu.drain_samples().count());
+  }
+
+  #[test]
+  fn no_samples_by_default() {
+    let mut apu = Apu::default();
+    let mapper = mapper();
+
+    for _ in 0..CPU_FREQUENCY {
+      apu.tick(&mapper);
+    }
+
+    assert_eq!(0, apu.drain_samples().count());
+  }
+}
@@ -0,0 +1,105 @@
+//! Pseudo-random noise channel, driven by a linear-feedback shift register
+//!
+//! See <https://www.nesdev.org/wiki/APU_Noise>.
+
+use super::unit::{Envelope, LengthCounter, Timer};
+
+/// Timer periods in CPU cycles, indexed by the low four bits of the period register
+const PERIODS: [u16; 16] = [
+  4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+const LENGTH_HALT: u8 = 0b0010_0000;
+/// Takes feedback from bit 6 rather than bit 1, for a shorter and more metallic sequence
+const MODE: u8 = 0b1000_0000;
+const PERIOD: u8 = 0b0000_1111;
+
+#[derive(Debug)]
+pub(super) struct Noise {
+  envelope: Envelope,
+  pub(super) length: LengthCounter,
+  timer: Timer,
+  mode: bool,
+  /// 15-bit shift register, which mutes the channel while bit 0 is set
+  shift: u16,
+}
+
+impl Default for Noise {
+  fn default() -> Self {
+    Self {
+      envelope: Envelope::default(),
+      length: LengthCounter::default(),
+      timer: Timer::default(),
+      mode: false,
+      shift: 1,
+    }
+  }
+}
+
+impl Noise {
+  /// Writes to one of the channel's four registers, the second of which is unused
+  pub(super) fn write(&mut self, register: u16, data: u8) {
+    match register {
+      0 => {
+        self.length.set_halted(data & LENGTH_HALT != 0);
+        self.envelope.write(data);
+      }
+      1 => {}
+      2 => {
+        self.mode = data & MODE != 0;
+        self.timer.period = PERIODS[usize::from(data & PERIOD)] - 1;
+      }
+      _ => {
+        self.length.load(data);
+        self.envelope.restart();
+      }
+    }
+  }
+
+  /// Clocked every CPU cycle
+  pub(super) fn clock_timer(&mut self) {
+    if self.timer.clock() {
+      let tap = if self.mode { 6 } else { 1 };
+      let feedback = (self.shift ^ (self.shift >> tap)) & 1;
+      self.shift = (self.shift >> 1) | (feedback << 14);
+    }
+  }
+
+  pub(super) fn clock_quarter_frame(&mut self) {
+    self.envelope.clock();
+  }
+
+  pub(super) fn clock_half_frame(&mut self) {
+    self.length.clock();
+  }
+
+  /// Current output level, 0-15
+  pub(super) fn output(&self) -> u8 {
+    if self.shift & 1 == 1 || !self.length.is_active() {
+      0
+    } else {
+      self.envelope.volume()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn shift_register_sequence() {
+    let mut noise = Noise::default();
+    noise.write(2, 0x00);
+
+    let mut shifts = Vec::new();
+    for _ in 0..3 {
+      for _ in 0..PERIODS[0] {
+        noise.clock_timer();
+      }
+      shifts.push(noise.shift);
+    }
+
+    assert_eq!(vec![0x4000, 0x2000, 0x1000], shifts);
+  }
+}
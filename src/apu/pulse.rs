@@ -0,0 +1,191 @@
+//! Square wave channels with a variable duty cycle and a frequency sweep
+//!
+//! See <https://www.nesdev.org/wiki/APU_Pulse> and <https://www.nesdev.org/wiki/APU_Sweep>.
+
+use super::unit::{Envelope, LengthCounter, Timer};
+
+/// Waveforms for each duty cycle, one step per timer clock
+const DUTIES: [[u8; 8]; 4] = [
+  [0, 1, 0, 0, 0, 0, 0, 0],
+  [0, 1, 1, 0, 0, 0, 0, 0],
+  [0, 1, 1, 1, 1, 0, 0, 0],
+  [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+const DUTY_SHIFT: u8 = 6;
+const LENGTH_HALT: u8 = 0b0010_0000;
+
+const SWEEP_ENABLED: u8 = 0b1000_0000;
+const SWEEP_PERIOD: u8 = 0b0111_0000;
+const SWEEP_NEGATE: u8 = 0b0000_1000;
+const SWEEP_SHIFT: u8 = 0b0000_0111;
+
+/// Periods below this are too high-pitched to play, and silence the channel
+const MINIMUM_PERIOD: u16 = 8;
+/// Sweeping to a period above this silences the channel
+const MAXIMUM_PERIOD: u16 = 0x7FF;
+
+#[derive(Debug, Default)]
+struct Sweep {
+  enabled: bool,
+  negate: bool,
+  reload: bool,
+  period: u8,
+  shift: u8,
+  divider: u8,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Pulse {
+  envelope: Envelope,
+  pub(super) length: LengthCounter,
+  timer: Timer,
+  sweep: Sweep,
+  duty: u8,
+  step: u8,
+  /// The first pulse channel negates sweeps with ones' complement, so sweeps down one further than the second
+  ones_complement: bool,
+}
+
+impl Pulse {
+  pub(super) fn new(ones_complement: bool) -> Self {
+    Self {
+      ones_complement,
+      ..Self::default()
+    }
+  }
+
+  /// Writes to one of the channel's four registers
+  pub(super) fn write(&mut self, register: u16, data: u8) {
+    match register {
+      0 => {
+        self.duty = data >> DUTY_SHIFT;
+        self.length.set_halted(data & LENGTH_HALT != 0);
+        self.envelope.write(data);
+      }
+      1 => {
+        self.sweep = Sweep {
+          enabled: data & SWEEP_ENABLED != 0,
+          negate: data & SWEEP_NEGATE != 0,
+          reload: true,
+          period: (data & SWEEP_PERIOD) >> 4,
+          shift: data & SWEEP_SHIFT,
+          divider: self.sweep.divider,
+        };
+      }
+      2 => self.timer.set_period_low(data),
+      _ => {
+        self.timer.set_period_high(data);
+        self.length.load(data);
+        self.envelope.restart();
+        self.step = 0;
+      }
+    }
+  }
+
+  /// Clocked every APU cycle, which is every other CPU cycle
+  pub(super) fn clock_timer(&mut self) {
+    if self.timer.clock() {
+      self.step = (self.step + 1) % 8;
+    }
+  }
+
+  pub(super) fn clock_quarter_frame(&mut self) {
+    self.envelope.clock();
+  }
+
+  pub(super) fn clock_half_frame(&mut self) {
+    self.length.clock();
+
+    if self.sweep.divider == 0 && self.sweep.enabled && self.sweep.shift > 0 && !self.is_muted() {
+      self.timer.period = self.target_period();
+    }
+    if self.sweep.divider == 0 || self.sweep.reload {
+      self.sweep.divider = self.sweep.period;
+      self.sweep.reload = false;
+    } else {
+      self.sweep.divider -= 1;
+    }
+  }
+
+  /// Period the sweep unit is moving towards, which is calculated continuously
+  fn target_period(&self) -> u16 {
+    let change = self.timer.period >> self.sweep.shift;
+    if self.sweep.negate {
+      self
+        .timer
+        .period
+        .saturating_sub(change + u16::from(self.ones_complement))
+    } else {
+      self.timer.period + change
+    }
+  }
+
+  fn is_muted(&self) -> bool {
+    self.timer.period < MINIMUM_PERIOD || self.target_period() > MAXIMUM_PERIOD
+  }
+
+  /// Current output level, 0-15
+  pub(super) fn output(&self) -> u8 {
+    if !self.length.is_active()
+      || self.is_muted()
+      || DUTIES[usize::from(self.duty)][usize::from(self.step)] == 0
+    {
+      0
+    } else {
+      self.envelope.volume()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+
+  fn playing(ones_complement: bool, period: u16) -> Pulse {
+    let mut pulse = Pulse::new(ones_complement);
+    pulse.length.set_enabled(true);
+    let [low, high] = period.to_le_bytes();
+    // 75% duty at constant volume 9
+    pulse.write(0, 0b1101_1001);
+    pulse.write(2, low);
+    pulse.write(3, high | 0b1111_1000);
+    pulse
+  }
+
+  #[test]
+  fn plays_duty_cycle() {
+    let mut pulse = playing(false, 0x100);
+
+    let mut levels = Vec::new();
+    for _ in 0..8 {
+      levels.push(pulse.output());
+      for _ in 0..=0x100 {
+        pulse.clock_timer();
+      }
+    }
+
+    assert_eq!(vec![9, 0, 0, 9, 9, 9, 9, 9], levels);
+  }
+
+  #[test_case(false, 0x1C0 ; "second channel")]
+  #[test_case(true, 0x1BF ; "first channel")]
+  fn sweeps_down(ones_complement: bool, period: u16) {
+    let mut pulse = playing(ones_complement, 0x200);
+    // Enabled, period 0, negated, shift 3
+    pulse.write(1, 0b1000_1011);
+
+    pulse.clock_half_frame();
+
+    assert_eq!(period, pulse.timer.period);
+  }
+
+  #[test]
+  fn sweep_overflow_mutes() {
+    let pulse = playing(false, 0x7F0);
+
+    assert_eq!(0, pulse.output());
+  }
+}
@@ -0,0 +1,97 @@
+//! Triangle wave channel, with a linear counter for finer control of note length
+//!
+//! See <https://www.nesdev.org/wiki/APU_Triangle>.
+
+use super::unit::{LengthCounter, Timer};
+
+/// Output level for each step of the waveform
+const SEQUENCE: [u8; 32] = [
+  15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+  13, 14, 15,
+];
+
+/// Halts the length counter and keeps reloading the linear counter
+const CONTROL: u8 = 0b1000_0000;
+const LINEAR_COUNTER_RELOAD: u8 = 0b0111_1111;
+
+#[derive(Debug, Default)]
+pub(super) struct Triangle {
+  pub(super) length: LengthCounter,
+  timer: Timer,
+  control: bool,
+  linear_counter: u8,
+  linear_counter_reload: u8,
+  reload: bool,
+  step: u8,
+}
+
+impl Triangle {
+  /// Writes to one of the channel's four registers, the second of which is unused
+  pub(super) fn write(&mut self, register: u16, data: u8) {
+    match register {
+      0 => {
+        self.control = data & CONTROL != 0;
+        self.length.set_halted(self.control);
+        self.linear_counter_reload = data & LINEAR_COUNTER_RELOAD;
+      }
+      1 => {}
+      2 => self.timer.set_period_low(data),
+      _ => {
+        self.timer.set_period_high(data);
+        self.length.load(data);
+        self.reload = true;
+      }
+    }
+  }
+
+  /// Clocked every CPU cycle, so the triangle plays an octave lower than a pulse with the same period
+  pub(super) fn clock_timer(&mut self) {
+    if self.timer.clock() && self.length.is_active() && self.linear_counter > 0 {
+      self.step = (self.step + 1) % 32;
+    }
+  }
+
+  pub(super) fn clock_quarter_frame(&mut self) {
+    if self.reload {
+      self.linear_counter = self.linear_counter_reload;
+    } else if self.linear_counter > 0 {
+      self.linear_counter -= 1;
+    }
+    if !self.control {
+      self.reload = false;
+    }
+  }
+
+  pub(super) fn clock_half_frame(&mut self) {
+    self.length.clock();
+  }
+
+  /// Current output level, 0-15, which holds its last value while the channel is silenced
+  pub(super) fn output(&self) -> u8 {
+    SEQUENCE[usize::from(self.step)]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn linear_counter_silences() {
+    let mut triangle = Triangle::default();
+    triangle.length.set_enabled(true);
+    // Linear counter of 1, period of 0
+    triangle.write(0, 0x01);
+    triangle.write(2, 0x00);
+    triangle.write(3, 0xF8);
+    triangle.clock_quarter_frame();
+
+    triangle.clock_timer();
+    triangle.clock_timer();
+    assert_eq!(13, triangle.output());
+
+    triangle.clock_quarter_frame();
+    triangle.clock_timer();
+    assert_eq!(13, triangle.output());
+  }
+}
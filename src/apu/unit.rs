@@ -0,0 +1,170 @@
+//! Building blocks shared between the channels
+//!
+//! See <https://www.nesdev.org/wiki/APU_Envelope> and <https://www.nesdev.org/wiki/APU_Length_Counter>.
+
+/// Lengths loaded into a length counter, indexed by the top five bits of a channel's last register
+const LENGTHS: [u8; 32] = [
+  10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+  192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+const ENVELOPE_LOOP: u8 = 0b0010_0000;
+const ENVELOPE_CONSTANT: u8 = 0b0001_0000;
+const ENVELOPE_VOLUME: u8 = 0b0000_1111;
+
+/// A divider which counts down from its period, clocking whatever it drives each time it reloads
+#[derive(Debug, Default)]
+pub(super) struct Timer {
+  pub(super) period: u16,
+  counter: u16,
+}
+
+impl Timer {
+  /// Returns whether the timer reached zero and reloaded
+  pub(super) fn clock(&mut self) -> bool {
+    if self.counter == 0 {
+      self.counter = self.period;
+      true
+    } else {
+      self.counter -= 1;
+      false
+    }
+  }
+
+  /// Sets the low eight bits of an 11-bit period
+  pub(super) fn set_period_low(&mut self, data: u8) {
+    self.period = (self.period & 0x0700) | u16::from(data);
+  }
+
+  /// Sets the high three bits of an 11-bit period
+  pub(super) fn set_period_high(&mut self, data: u8) {
+    self.period = (self.period & 0x00FF) | (u16::from(data & 0x07) << 8);
+  }
+}
+
+/// Generates a decaying volume, or a constant one
+#[derive(Debug, Default)]
+pub(super) struct Envelope {
+  start: bool,
+  looping: bool,
+  constant: bool,
+  /// Constant volume, which is also the period of the decay
+  volume: u8,
+  divider: u8,
+  decay: u8,
+}
+
+impl Envelope {
+  /// Configures the envelope from a channel's first register
+  pub(super) fn write(&mut self, data: u8) {
+    self.looping = data & ENVELOPE_LOOP != 0;
+    self.constant = data & ENVELOPE_CONSTANT != 0;
+    self.volume = data & ENVELOPE_VOLUME;
+  }
+
+  /// Restarts the decay from full volume on the next quarter frame
+  pub(super) fn restart(&mut self) {
+    self.start = true;
+  }
+
+  /// Clocked by the frame counter every quarter frame
+  pub(super) fn clock(&mut self) {
+    if self.start {
+      self.start = false;
+      self.decay = 15;
+      self.divider = self.volume;
+    } else if self.divider == 0 {
+      self.divider = self.volume;
+      if self.decay > 0 {
+        self.decay -= 1;
+      } else if self.looping {
+        self.decay = 15;
+      }
+    } else {
+      self.divider -= 1;
+    }
+  }
+
+  pub(super) fn volume(&self) -> u8 {
+    if self.constant {
+      self.volume
+    } else {
+      self.decay
+    }
+  }
+}
+
+/// Silences a channel once a loaded length has counted down to zero
+#[derive(Debug, Default)]
+pub(super) struct LengthCounter {
+  enabled: bool,
+  halted: bool,
+  count: u8,
+}
+
+impl LengthCounter {
+  /// Enables or disables the counter from the status register, which also silences the channel when disabled
+  pub(super) fn set_enabled(&mut self, enabled: bool) {
+    self.enabled = enabled;
+    if !enabled {
+      self.count = 0;
+    }
+  }
+
+  pub(super) fn set_halted(&mut self, halted: bool) {
+    self.halted = halted;
+  }
+
+  /// Loads a length from the top five bits of a channel's last register, if the channel is enabled
+  pub(super) fn load(&mut self, data: u8) {
+    if self.enabled {
+      self.count = LENGTHS[usize::from(data >> 3)];
+    }
+  }
+
+  /// Clocked by the frame counter every half frame
+  pub(super) fn clock(&mut self) {
+    if !self.halted && self.count > 0 {
+      self.count -= 1;
+    }
+  }
+
+  pub(super) fn is_active(&self) -> bool {
+    self.count > 0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn envelope_decays_and_loops() {
+    let mut envelope = Envelope::default();
+    envelope.write(ENVELOPE_LOOP);
+    envelope.restart();
+
+    envelope.clock();
+    assert_eq!(15, envelope.volume());
+    for _ in 0..15 {
+      envelope.clock();
+    }
+    assert_eq!(0, envelope.volume());
+    envelope.clock();
+    assert_eq!(15, envelope.volume());
+  }
+
+  #[test]
+  fn length_counter() {
+    let mut length = LengthCounter::default();
+    length.load(0b0001_1000);
+    assert!(!length.is_active(), "loads are ignored while disabled");
+
+    length.set_enabled(true);
+    length.load(0b0001_1000);
+    length.clock();
+    assert!(length.is_active());
+    length.clock();
+    assert!(!length.is_active());
+  }
+}
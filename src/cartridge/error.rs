@@ -0,0 +1,23 @@
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+  #[error("file does not start with the iNES magic bytes \"NES\\x1A\"")]
+  InvalidMagic,
+  #[error("file is truncated: {section} needs {expected} bytes, but only {actual} remain")]
+  Truncated {
+    section: &'static str,
+    expected: usize,
+    actual: usize,
+  },
+  #[error("{section} size exponent {exponent} is too large")]
+  SizeTooLarge { section: &'static str, exponent: u8 },
+  #[error("cartridge has no program ROM")]
+  MissingProgramRom,
+  #[error("mapper {0} is not supported")]
+  UnsupportedMapper(u16),
+  #[error("io error: {0}")]
+  Io(#[from] io::Error),
+}
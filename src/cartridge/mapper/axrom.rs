@@ -0,0 +1,97 @@
+//! AxROM (mapper 7), with a switchable 32 KiB program ROM bank and single-screen mirroring
+//!
+//! See <https://www.nesdev.org/wiki/AxROM>.
+
+use super::{Board, Mapper};
+use crate::{
+  cartridge::{Mirroring, CHARACTER_ROM_BANK_SIZE},
+  cpu,
+  memory::{
+    self,
+    constant::{PROGRAM_RAM_END, PROGRAM_RAM_START, PROGRAM_ROM_SIZE, PROGRAM_ROM_START},
+  },
+};
+
+const BANK_MASK: cpu::Int = 0b0000_0111;
+const NAMETABLE_SELECT: cpu::Int = 0b0001_0000;
+
+/// Writes to ROM select the 32 KiB program ROM bank and which nametable is shown on every screen
+#[derive(Debug)]
+pub struct Axrom {
+  board: Board,
+  bank: usize,
+  mirroring: Mirroring,
+}
+
+impl Axrom {
+  #[must_use]
+  pub fn new(board: Board) -> Self {
+    Self {
+      board,
+      bank: 0,
+      mirroring: Mirroring::SingleScreenLower,
+    }
+  }
+}
+
+impl Mapper for Axrom {
+  fn read_program(&self, address: memory::Address) -> Option<cpu::Int> {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END => self.board.read_program_ram(address),
+      PROGRAM_ROM_START..=memory::Address::MAX => Some(self.board.read_program_rom(
+        self.bank,
+        usize::from(PROGRAM_ROM_SIZE),
+        address,
+      )),
+      _ => None,
+    }
+  }
+
+  fn write_program(&mut self, address: memory::Address, data: cpu::Int) {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END => self.board.write_program_ram(address, data),
+      PROGRAM_ROM_START..=memory::Address::MAX => {
+        self.bank = usize::from(data & BANK_MASK);
+        self.mirroring = if data & NAMETABLE_SELECT == 0 {
+          Mirroring::SingleScreenLower
+        } else {
+          Mirroring::SingleScreenUpper
+        };
+      }
+      _ => {}
+    }
+  }
+
+  fn read_character(&self, address: memory::Address) -> cpu::Int {
+    self
+      .board
+      .read_character(0, CHARACTER_ROM_BANK_SIZE, address)
+  }
+
+  fn write_character(&mut self, address: memory::Address, data: cpu::Int) {
+    self
+      .board
+      .write_character(0, CHARACTER_ROM_BANK_SIZE, address, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cartridge::mapper::numbered_board;
+
+  #[test]
+  fn switches_bank_and_nametable() {
+    let mut axrom = Axrom::new(numbered_board(0x40000, 0x2000));
+
+    axrom.write_program(0x8000, 0b0001_0011);
+
+    assert_eq!(Some(12), axrom.read_program(0x8000));
+    assert_eq!(Some(15), axrom.read_program(0xFFFF));
+    assert_eq!(Mirroring::SingleScreenUpper, axrom.mirroring());
+  }
+}
@@ -0,0 +1,81 @@
+//! CNROM (mapper 3), with a switchable 8 KiB character ROM bank
+//!
+//! See <https://www.nesdev.org/wiki/CNROM>.
+
+use super::{Board, Mapper};
+use crate::{
+  cartridge::{Mirroring, CHARACTER_ROM_BANK_SIZE},
+  cpu,
+  memory::{
+    self,
+    constant::{PROGRAM_RAM_END, PROGRAM_RAM_START, PROGRAM_ROM_SIZE, PROGRAM_ROM_START},
+  },
+};
+
+/// Fixed program ROM as in NROM, with any write to ROM selecting the character ROM bank
+#[derive(Debug)]
+pub struct Cnrom {
+  board: Board,
+  bank: usize,
+}
+
+impl Cnrom {
+  #[must_use]
+  pub fn new(board: Board) -> Self {
+    Self { board, bank: 0 }
+  }
+}
+
+impl Mapper for Cnrom {
+  fn read_program(&self, address: memory::Address) -> Option<cpu::Int> {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END => self.board.read_program_ram(address),
+      PROGRAM_ROM_START..=memory::Address::MAX => Some(self.board.read_program_rom(
+        0,
+        usize::from(PROGRAM_ROM_SIZE),
+        address,
+      )),
+      _ => None,
+    }
+  }
+
+  fn write_program(&mut self, address: memory::Address, data: cpu::Int) {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END => self.board.write_program_ram(address, data),
+      PROGRAM_ROM_START..=memory::Address::MAX => self.bank = usize::from(data),
+      _ => {}
+    }
+  }
+
+  fn read_character(&self, address: memory::Address) -> cpu::Int {
+    self
+      .board
+      .read_character(self.bank, CHARACTER_ROM_BANK_SIZE, address)
+  }
+
+  fn write_character(&mut self, address: memory::Address, data: cpu::Int) {
+    self
+      .board
+      .write_character(self.bank, CHARACTER_ROM_BANK_SIZE, address, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.board.mirroring
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cartridge::mapper::numbered_board;
+
+  #[test]
+  fn switches_character_bank() {
+    let mut cnrom = Cnrom::new(numbered_board(0x8000, 0x8000));
+
+    cnrom.write_program(0x8000, 3);
+
+    assert_eq!(24, cnrom.read_character(0x0000));
+    assert_eq!(31, cnrom.read_character(0x1FFF));
+  }
+}
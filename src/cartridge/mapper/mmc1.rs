@@ -0,0 +1,216 @@
+//! MMC1 (mapper 1), with registers loaded serially through a shift register
+//!
+//! See <https://www.nesdev.org/wiki/MMC1>.
+
+use super::{Board, Mapper};
+use crate::{
+  cartridge::{Mirroring, CHARACTER_ROM_BANK_SIZE, PROGRAM_ROM_BANK_SIZE},
+  cpu,
+  memory::{
+    self,
+    constant::{PROGRAM_RAM_END, PROGRAM_RAM_START, PROGRAM_ROM_SIZE, PROGRAM_ROM_START},
+  },
+};
+
+/// Writing a value with this bit set clears the shift register
+const RESET: cpu::Int = 0b1000_0000;
+/// Number of writes needed to load a register
+const SHIFT_WRITES: u8 = 5;
+/// Control register value at power on, and the bits set by a reset, which fix the last program ROM bank
+const CONTROL_RESET: cpu::Int = 0b0_1100;
+const CHARACTER_4K: cpu::Int = 0b1_0000;
+const PROGRAM_RAM_DISABLE: cpu::Int = 0b1_0000;
+/// Start of the upper program ROM bank
+const UPPER_BANK_START: memory::Address = 0xC000;
+const HALF_CHARACTER_BANK_SIZE: usize = CHARACTER_ROM_BANK_SIZE / 2;
+
+#[derive(Debug)]
+pub struct Mmc1 {
+  board: Board,
+  shift: cpu::Int,
+  writes: u8,
+  control: cpu::Int,
+  character_bank_0: cpu::Int,
+  character_bank_1: cpu::Int,
+  program_bank: cpu::Int,
+}
+
+impl Mmc1 {
+  #[must_use]
+  pub fn new(board: Board) -> Self {
+    Self {
+      board,
+      shift: 0,
+      writes: 0,
+      control: CONTROL_RESET,
+      character_bank_0: 0,
+      character_bank_1: 0,
+      program_bank: 0,
+    }
+  }
+
+  /// Shifts in the lowest bit of the data, loading the register selected by the address on the fifth write
+  fn write_register(&mut self, address: memory::Address, data: cpu::Int) {
+    if data & RESET != 0 {
+      self.shift = 0;
+      self.writes = 0;
+      self.control |= CONTROL_RESET;
+      return;
+    }
+
+    self.shift |= (data & 1) << self.writes;
+    self.writes += 1;
+    if self.writes < SHIFT_WRITES {
+      return;
+    }
+
+    let value = self.shift;
+    self.shift = 0;
+    self.writes = 0;
+    // Address lines 13 and 14 select the register
+    match address & 0x6000 {
+      0x0000 => self.control = value,
+      0x2000 => self.character_bank_0 = value,
+      0x4000 => self.character_bank_1 = value,
+      _ => self.program_bank = value,
+    }
+  }
+
+  fn program_ram_enabled(&self) -> bool {
+    self.program_bank & PROGRAM_RAM_DISABLE == 0
+  }
+
+  /// Program ROM bank and bank size mapped at an address in the ROM region
+  fn program_bank(&self, address: memory::Address) -> (usize, usize) {
+    let bank = usize::from(self.program_bank & 0x0F);
+    let lower = address < UPPER_BANK_START;
+    match (self.control >> 2) & 0b11 {
+      // Switch 32 KiB at once, ignoring the lowest bit of the bank number
+      0 | 1 => (bank >> 1, usize::from(PROGRAM_ROM_SIZE)),
+      // Fix the first bank at $8000 and switch $C000
+      2 => (if lower { 0 } else { bank }, PROGRAM_ROM_BANK_SIZE),
+      // Fix the last bank at $C000 and switch $8000
+      _ => {
+        let last = self.board.program_rom_banks(PROGRAM_ROM_BANK_SIZE) - 1;
+        (if lower { bank } else { last }, PROGRAM_ROM_BANK_SIZE)
+      }
+    }
+  }
+
+  /// Character bank and bank size mapped at an address in the pattern tables
+  fn character_bank(&self, address: memory::Address) -> (usize, usize) {
+    if self.control & CHARACTER_4K == 0 {
+      // Switch 8 KiB at once, ignoring the lowest bit of the bank number
+      (
+        usize::from(self.character_bank_0 >> 1),
+        CHARACTER_ROM_BANK_SIZE,
+      )
+    } else if usize::from(address) < HALF_CHARACTER_BANK_SIZE {
+      (usize::from(self.character_bank_0), HALF_CHARACTER_BANK_SIZE)
+    } else {
+      (usize::from(self.character_bank_1), HALF_CHARACTER_BANK_SIZE)
+    }
+  }
+}
+
+impl Mapper for Mmc1 {
+  fn read_program(&self, address: memory::Address) -> Option<cpu::Int> {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END if self.program_ram_enabled() => {
+        self.board.read_program_ram(address)
+      }
+      PROGRAM_ROM_START..=memory::Address::MAX => {
+        let (bank, bank_size) = self.program_bank(address);
+        Some(self.board.read_program_rom(bank, bank_size, address))
+      }
+      _ => None,
+    }
+  }
+
+  fn write_program(&mut self, address: memory::Address, data: cpu::Int) {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END if self.program_ram_enabled() => {
+        self.board.write_program_ram(address, data);
+      }
+      PROGRAM_ROM_START..=memory::Address::MAX => self.write_register(address, data),
+      _ => {}
+    }
+  }
+
+  fn read_character(&self, address: memory::Address) -> cpu::Int {
+    let (bank, bank_size) = self.character_bank(address);
+    self.board.read_character(bank, bank_size, address)
+  }
+
+  fn write_character(&mut self, address: memory::Address, data: cpu::Int) {
+    let (bank, bank_size) = self.character_bank(address);
+    self.board.write_character(bank, bank_size, address, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    match self.control & 0b11 {
+      0 => Mirroring::SingleScreenLower,
+      1 => Mirroring::SingleScreenUpper,
+      2 => Mirroring::Vertical,
+      _ => Mirroring::Horizontal,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+  use crate::cartridge::mapper::numbered_board;
+
+  fn load(mmc1: &mut Mmc1, address: memory::Address, value: cpu::Int) {
+    for bit in 0..SHIFT_WRITES {
+      mmc1.write_program(address, value >> bit & 1);
+    }
+  }
+
+  #[test_case(0b0_1100, 0x8000 => Some(6))]
+  #[test_case(0b0_1100, 0xC000 => Some(14))]
+  #[test_case(0b0_1000, 0x8000 => Some(0))]
+  #[test_case(0b0_1000, 0xC000 => Some(6))]
+  #[test_case(0b0_0000, 0x8000 => Some(4))]
+  #[test_case(0b0_0000, 0xC000 => Some(6))]
+  fn program_banking(control: cpu::Int, address: memory::Address) -> Option<cpu::Int> {
+    let mut mmc1 = Mmc1::new(numbered_board(0x20000, 0x2000));
+    load(&mut mmc1, 0x8000, control);
+    load(&mut mmc1, 0xE000, 3);
+
+    mmc1.read_program(address)
+  }
+
+  #[test]
+  fn character_banking() {
+    let mut mmc1 = Mmc1::new(numbered_board(0x8000, 0x8000));
+    load(&mut mmc1, 0x8000, CHARACTER_4K);
+    load(&mut mmc1, 0xA000, 3);
+    load(&mut mmc1, 0xC000, 5);
+
+    assert_eq!(12, mmc1.read_character(0x0000));
+    assert_eq!(20, mmc1.read_character(0x1000));
+  }
+
+  #[test]
+  fn reset_clears_shift_register() {
+    let mut mmc1 = Mmc1::new(numbered_board(0x8000, 0x2000));
+    mmc1.write_program(0x8000, 1);
+    mmc1.write_program(0x8000, RESET);
+    load(&mut mmc1, 0x8000, 0b0_0010);
+
+    assert_eq!(Mirroring::Vertical, mmc1.mirroring());
+  }
+
+  #[test]
+  fn program_ram_can_be_disabled() {
+    let mut mmc1 = Mmc1::new(numbered_board(0x8000, 0x2000));
+    mmc1.write_program(0x6000, 0x12);
+    load(&mut mmc1, 0xE000, PROGRAM_RAM_DISABLE);
+
+    assert_eq!(None, mmc1.read_program(0x6000));
+  }
+}
@@ -0,0 +1,296 @@
+//! MMC3 (mapper 4), with fine-grained bank switching and a scanline counter
+//!
+//! See <https://www.nesdev.org/wiki/MMC3>.
+
+use super::{Board, Mapper};
+use crate::{
+  cartridge::Mirroring,
+  cpu,
+  memory::{
+    self,
+    constant::{PROGRAM_RAM_END, PROGRAM_RAM_START, PROGRAM_ROM_START},
+  },
+};
+
+const PROGRAM_BANK_SIZE: usize = 0x2000;
+const CHARACTER_BANK_SIZE: usize = 0x0400;
+/// Bits of the bank select register
+const TARGET_MASK: cpu::Int = 0b0000_0111;
+const PROGRAM_MODE: cpu::Int = 0b0100_0000;
+const CHARACTER_INVERSION: cpu::Int = 0b1000_0000;
+/// Bits of the program RAM protect register
+const PROGRAM_RAM_ENABLE: cpu::Int = 0b1000_0000;
+const PROGRAM_RAM_WRITE_PROTECT: cpu::Int = 0b0100_0000;
+/// PPU address line 12, which is high when fetching from the second pattern table
+const A12: memory::Address = 0x1000;
+/// Consecutive PPU accesses with A12 low after which a rise clocks the scanline counter
+///
+/// The board filters out the brief drops in A12 between sprite pattern fetches, which only last two accesses.
+const A12_LOW_ACCESSES: u8 = 3;
+
+#[derive(Debug)]
+pub struct Mmc3 {
+  board: Board,
+  bank_select: cpu::Int,
+  /// Bank registers R0-R7: two 2 KiB and four 1 KiB character banks, then two 8 KiB program banks
+  banks: [cpu::Int; 8],
+  mirroring: Mirroring,
+  program_ram_protect: cpu::Int,
+  irq_latch: cpu::Int,
+  irq_counter: cpu::Int,
+  irq_reload: bool,
+  irq_enabled: bool,
+  irq_pending: bool,
+  /// Number of consecutive PPU accesses with A12 low, saturating at the filter threshold
+  a12_low: u8,
+}
+
+impl Mmc3 {
+  #[must_use]
+  pub fn new(board: Board) -> Self {
+    let mirroring = board.mirroring;
+    Self {
+      board,
+      bank_select: 0,
+      banks: [0; 8],
+      mirroring,
+      program_ram_protect: PROGRAM_RAM_ENABLE,
+      irq_latch: 0,
+      irq_counter: 0,
+      irq_reload: false,
+      irq_enabled: false,
+      irq_pending: false,
+      a12_low: 0,
+    }
+  }
+
+  /// 8 KiB program ROM bank mapped at an address in the ROM region
+  fn program_bank(&self, address: memory::Address) -> usize {
+    // A ROM with a single bank has it in both fixed slots
+    let second_last = self
+      .board
+      .program_rom_banks(PROGRAM_BANK_SIZE)
+      .saturating_sub(2);
+    let swapped = self.bank_select & PROGRAM_MODE != 0;
+    match (address - PROGRAM_ROM_START) / 0x2000 {
+      0 if swapped => second_last,
+      0 => usize::from(self.banks[6] & 0x3F),
+      1 => usize::from(self.banks[7] & 0x3F),
+      2 if swapped => usize::from(self.banks[6] & 0x3F),
+      2 => second_last,
+      _ => second_last + 1,
+    }
+  }
+
+  /// 1 KiB character bank mapped at an address in the pattern tables
+  fn character_bank(&self, address: memory::Address) -> usize {
+    // Inversion swaps the 2 KiB banks into the second pattern table
+    let address = if self.bank_select & CHARACTER_INVERSION == 0 {
+      address
+    } else {
+      address ^ A12
+    };
+    let slot = usize::from(address & 0x1FFF) / CHARACTER_BANK_SIZE;
+    match slot {
+      0..=3 => usize::from(self.banks[slot / 2] & !1) + slot % 2,
+      _ => usize::from(self.banks[slot - 2]),
+    }
+  }
+
+  fn write_register(&mut self, address: memory::Address, data: cpu::Int) {
+    let even = address & 1 == 0;
+    match (address & 0xE000, even) {
+      (0x8000, true) => self.bank_select = data,
+      (0x8000, false) => self.banks[usize::from(self.bank_select & TARGET_MASK)] = data,
+      (0xA000, true) => {
+        if self.board.mirroring != Mirroring::FourScreen {
+          self.mirroring = if data & 1 == 0 {
+            Mirroring::Vertical
+          } else {
+            Mirroring::Horizontal
+          };
+        }
+      }
+      (0xA000, false) => self.program_ram_protect = data,
+      (0xC000, true) => self.irq_latch = data,
+      (0xC000, false) => {
+        self.irq_counter = 0;
+        self.irq_reload = true;
+      }
+      (_, true) => {
+        self.irq_enabled = false;
+        self.irq_pending = false;
+      }
+      (_, false) => self.irq_enabled = true,
+    }
+  }
+
+  /// Counts down a scanline, raising an IRQ when the counter reaches zero
+  fn clock_scanline_counter(&mut self) {
+    if self.irq_counter == 0 || self.irq_reload {
+      self.irq_counter = self.irq_latch;
+      self.irq_reload = false;
+    } else {
+      self.irq_counter -= 1;
+    }
+    if self.irq_counter == 0 && self.irq_enabled {
+      self.irq_pending = true;
+    }
+  }
+}
+
+impl Mapper for Mmc3 {
+  fn read_program(&self, address: memory::Address) -> Option<cpu::Int> {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END if self.program_ram_protect & PROGRAM_RAM_ENABLE != 0 => {
+        self.board.read_program_ram(address)
+      }
+      PROGRAM_ROM_START..=memory::Address::MAX => Some(self.board.read_program_rom(
+        self.program_bank(address),
+        PROGRAM_BANK_SIZE,
+        address,
+      )),
+      _ => None,
+    }
+  }
+
+  fn write_program(&mut self, address: memory::Address, data: cpu::Int) {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END
+        if self.program_ram_protect & (PROGRAM_RAM_ENABLE | PROGRAM_RAM_WRITE_PROTECT)
+          == PROGRAM_RAM_ENABLE =>
+      {
+        self.board.write_program_ram(address, data);
+      }
+      PROGRAM_ROM_START..=memory::Address::MAX => self.write_register(address, data),
+      _ => {}
+    }
+  }
+
+  fn read_character(&self, address: memory::Address) -> cpu::Int {
+    self
+      .board
+      .read_character(self.character_bank(address), CHARACTER_BANK_SIZE, address)
+  }
+
+  fn write_character(&mut self, address: memory::Address, data: cpu::Int) {
+    let bank = self.character_bank(address);
+    self
+      .board
+      .write_character(bank, CHARACTER_BANK_SIZE, address, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn observe_ppu_address(&mut self, address: memory::Address) {
+    if address & A12 == 0 {
+      self.a12_low = self.a12_low.saturating_add(1).min(A12_LOW_ACCESSES);
+      return;
+    }
+    if self.a12_low >= A12_LOW_ACCESSES {
+      self.clock_scanline_counter();
+    }
+    self.a12_low = 0;
+  }
+
+  fn irq(&self) -> bool {
+    self.irq_pending
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+  use crate::cartridge::mapper::numbered_board;
+
+  fn mmc3() -> Mmc3 {
+    let mut mmc3 = Mmc3::new(numbered_board(0x20000, 0x20000));
+    for (register, bank) in [0x10, 0x12, 0x20, 0x21, 0x22, 0x23, 5, 9]
+      .into_iter()
+      .enumerate()
+    {
+      mmc3.write_program(0x8000, cpu::Int::try_from(register).unwrap());
+      mmc3.write_program(0x8001, bank);
+    }
+    mmc3
+  }
+
+  /// Fetches from each pattern table, as the PPU does over a scanline with sprites in the second table
+  fn scanline(mmc3: &mut Mmc3) {
+    for _ in 0..4 {
+      mmc3.observe_ppu_address(0x2000);
+    }
+    for _ in 0..2 {
+      mmc3.observe_ppu_address(0x1000);
+      mmc3.observe_ppu_address(0x2000);
+      mmc3.observe_ppu_address(0x2000);
+      mmc3.observe_ppu_address(0x1008);
+    }
+  }
+
+  #[test_case(0, 0x8000 => Some(5))]
+  #[test_case(0, 0xA000 => Some(9))]
+  #[test_case(0, 0xC000 => Some(14))]
+  #[test_case(0, 0xE000 => Some(15))]
+  #[test_case(PROGRAM_MODE, 0x8000 => Some(14))]
+  #[test_case(PROGRAM_MODE, 0xC000 => Some(5))]
+  fn program_banking(mode: cpu::Int, address: memory::Address) -> Option<cpu::Int> {
+    let mut mmc3 = mmc3();
+    mmc3.write_program(0x8000, mode);
+
+    mmc3.read_program(address)
+  }
+
+  #[test_case(0x8000)]
+  #[test_case(0xC000)]
+  #[test_case(0xE000)]
+  fn single_program_bank(address: memory::Address) {
+    let mmc3 = Mmc3::new(numbered_board(0x2000, 0x2000));
+
+    assert_eq!(Some(0), mmc3.read_program(address));
+  }
+
+  #[test_case(0, 0x0000 => 0x10)]
+  #[test_case(0, 0x0400 => 0x11)]
+  #[test_case(0, 0x0C00 => 0x13)]
+  #[test_case(0, 0x1C00 => 0x23)]
+  #[test_case(CHARACTER_INVERSION, 0x0000 => 0x20)]
+  #[test_case(CHARACTER_INVERSION, 0x1400 => 0x11)]
+  fn character_banking(mode: cpu::Int, address: memory::Address) -> cpu::Int {
+    let mut mmc3 = mmc3();
+    mmc3.write_program(0x8000, mode);
+
+    mmc3.read_character(address)
+  }
+
+  #[test]
+  fn mirroring_control() {
+    let mut mmc3 = mmc3();
+
+    mmc3.write_program(0xA000, 1);
+
+    assert_eq!(Mirroring::Horizontal, mmc3.mirroring());
+  }
+
+  #[test]
+  fn scanline_irq() {
+    let mut mmc3 = mmc3();
+    mmc3.write_program(0xC000, 2);
+    mmc3.write_program(0xC001, 0);
+    mmc3.write_program(0xE001, 0);
+
+    // The first scanline reloads the counter, then it counts down
+    scanline(&mut mmc3);
+    scanline(&mut mmc3);
+    assert!(!mmc3.irq());
+    scanline(&mut mmc3);
+    assert!(mmc3.irq());
+
+    mmc3.write_program(0xE000, 0);
+    assert!(!mmc3.irq());
+  }
+}
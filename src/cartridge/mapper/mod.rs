@@ -0,0 +1,245 @@
+//! Cartridge boards, which decide how the cartridge's memory appears in the CPU and PPU address spaces
+//!
+//! See <https://www.nesdev.org/wiki/Mapper>.
+
+pub mod axrom;
+pub mod cnrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod nrom;
+pub mod uxrom;
+
+use std::fmt;
+
+use super::{Cartridge, Error, Mirroring, CHARACTER_ROM_BANK_SIZE, PROGRAM_RAM_BANK_SIZE};
+use crate::{
+  cpu,
+  memory::{self, constant::PROGRAM_RAM_START},
+};
+
+pub trait Mapper: fmt::Debug {
+  /// Reads from the cartridge's part of the CPU address space, $4020-$FFFF
+  ///
+  /// Returns `None` if nothing on the board responds at the address.
+  fn read_program(&self, address: memory::Address) -> Option<cpu::Int>;
+
+  /// Writes to the cartridge's part of the CPU address space, $4020-$FFFF
+  ///
+  /// Writes to ROM are usually caught by the board's bank-switching registers.
+  fn write_program(&mut self, address: memory::Address, data: cpu::Int);
+
+  /// Reads from the pattern tables in the PPU address space, $0000-$1FFF
+  fn read_character(&self, address: memory::Address) -> cpu::Int;
+
+  /// Writes to the pattern tables in the PPU address space, $0000-$1FFF, which is ignored for character ROM
+  fn write_character(&mut self, address: memory::Address, data: cpu::Int);
+
+  /// How the nametables are currently arranged, which some boards can switch at runtime
+  fn mirroring(&self) -> Mirroring;
+
+  /// Observes each address the PPU accesses, which some boards watch to count scanlines
+  fn observe_ppu_address(&mut self, _address: memory::Address) {}
+
+  /// Whether the board is asserting the CPU's IRQ line
+  fn irq(&self) -> bool {
+    false
+  }
+}
+
+/// Creates the mapper for the cartridge's board, from its iNES mapper number
+///
+/// # Errors
+/// Returns [`Error::UnsupportedMapper`] if the board is not emulated
+pub fn from_cartridge(cartridge: &Cartridge) -> Result<Box<dyn Mapper>, Error> {
+  let board = Board::new(cartridge);
+  Ok(match cartridge.mapper {
+    0 => Box::new(nrom::Nrom::new(board)),
+    1 => Box::new(mmc1::Mmc1::new(board)),
+    2 => Box::new(uxrom::Uxrom::new(board)),
+    3 => Box::new(cnrom::Cnrom::new(board)),
+    4 => Box::new(mmc3::Mmc3::new(board)),
+    7 => Box::new(axrom::Axrom::new(board)),
+    mapper => return Err(Error::UnsupportedMapper(mapper)),
+  })
+}
+
+/// The memory chips on a cartridge board, which mappers switch banks of into the address spaces
+pub struct Board {
+  pub program_rom: Vec<cpu::Int>,
+  pub program_ram: Vec<cpu::Int>,
+  /// Character ROM, or character RAM if the cartridge has no character ROM
+  pub character: Vec<cpu::Int>,
+  pub character_writable: bool,
+  /// Nametable arrangement wired on the board, for mappers without mirroring control
+  pub mirroring: Mirroring,
+}
+
+impl fmt::Debug for Board {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Board")
+      .field("program_rom", &format_args!("{:X?}", &self.program_rom))
+      .field("program_ram", &format_args!("{:X?}", &self.program_ram))
+      .field("character", &format_args!("{:X?}", &self.character))
+      .field("character_writable", &self.character_writable)
+      .field("mirroring", &self.mirroring)
+      .finish()
+  }
+}
+
+impl Board {
+  /// Copies the memory described by a cartridge, loading any trainer into program RAM
+  #[must_use]
+  pub fn new(cartridge: &Cartridge) -> Self {
+    let mut program_ram = vec![0; cartridge.program_ram_size + cartridge.program_nvram_size];
+    if let Some(trainer) = &cartridge.trainer {
+      let offset = usize::from(memory::constant::TRAINER_START - PROGRAM_RAM_START);
+      if program_ram.len() < offset + trainer.len() {
+        program_ram.resize(offset + trainer.len(), 0);
+      }
+      program_ram[offset..offset + trainer.len()].copy_from_slice(trainer);
+    }
+
+    let character_writable = cartridge.character_rom.is_empty();
+    let character = if character_writable {
+      vec![0; cartridge.character_ram_size + cartridge.character_nvram_size]
+    } else {
+      cartridge.character_rom.clone()
+    };
+
+    Self {
+      program_rom: cartridge.program_rom.clone(),
+      program_ram,
+      character,
+      character_writable,
+      mirroring: cartridge.mirroring,
+    }
+  }
+
+  /// A board with the given program ROM, and a bank each of program RAM and character RAM
+  #[must_use]
+  pub fn with_program_rom(program_rom: Vec<cpu::Int>) -> Self {
+    Self {
+      program_rom,
+      program_ram: vec![0; PROGRAM_RAM_BANK_SIZE],
+      character: vec![0; CHARACTER_ROM_BANK_SIZE],
+      character_writable: true,
+      mirroring: Mirroring::Horizontal,
+    }
+  }
+
+  /// Number of whole program ROM banks of the given size
+  #[must_use]
+  pub fn program_rom_banks(&self, bank_size: usize) -> usize {
+    (self.program_rom.len() / bank_size).max(1)
+  }
+
+  /// Reads from a bank of program ROM, with bank numbers wrapping around the ROM size
+  #[must_use]
+  pub fn read_program_rom(
+    &self,
+    bank: usize,
+    bank_size: usize,
+    address: memory::Address,
+  ) -> cpu::Int {
+    self.program_rom[banked(self.program_rom.len(), bank, bank_size, address)]
+  }
+
+  /// Reads from program RAM at $6000-$7FFF, if the board has any
+  #[must_use]
+  pub fn read_program_ram(&self, address: memory::Address) -> Option<cpu::Int> {
+    let offset = usize::from(address - PROGRAM_RAM_START);
+    (!self.program_ram.is_empty()).then(|| self.program_ram[offset % self.program_ram.len()])
+  }
+
+  /// Writes to program RAM at $6000-$7FFF, if the board has any
+  pub fn write_program_ram(&mut self, address: memory::Address, data: cpu::Int) {
+    let offset = usize::from(address - PROGRAM_RAM_START);
+    let size = self.program_ram.len();
+    if size > 0 {
+      self.program_ram[offset % size] = data;
+    }
+  }
+
+  /// Reads from a bank of character memory, with bank numbers wrapping around the memory size
+  #[must_use]
+  pub fn read_character(
+    &self,
+    bank: usize,
+    bank_size: usize,
+    address: memory::Address,
+  ) -> cpu::Int {
+    if self.character.is_empty() {
+      return 0;
+    }
+    self.character[banked(self.character.len(), bank, bank_size, address)]
+  }
+
+  /// Writes to a bank of character memory, if it is RAM
+  pub fn write_character(
+    &mut self,
+    bank: usize,
+    bank_size: usize,
+    address: memory::Address,
+    data: cpu::Int,
+  ) {
+    if self.character_writable && !self.character.is_empty() {
+      let index = banked(self.character.len(), bank, bank_size, address);
+      self.character[index] = data;
+    }
+  }
+}
+
+/// Index into memory of the given size for an address within a bank
+///
+/// Bank sizes are powers of two, so the address is reduced to its offset within the bank window.
+fn banked(size: usize, bank: usize, bank_size: usize, address: memory::Address) -> usize {
+  (bank * bank_size + usize::from(address) % bank_size) % size
+}
+
+/// A board whose program ROM is numbered in 8 KiB banks and character memory in 1 KiB banks, so tests can
+/// tell which bank is mapped in by reading a byte
+#[cfg(test)]
+pub(crate) fn numbered_board(program_rom_size: usize, character_size: usize) -> Board {
+  let number = |size: usize, bank_size: usize| -> Vec<cpu::Int> {
+    (0..size)
+      .map(|index| cpu::Int::try_from(index / bank_size).unwrap())
+      .collect()
+  };
+  Board {
+    program_rom: number(program_rom_size, 0x2000),
+    program_ram: vec![0; PROGRAM_RAM_BANK_SIZE],
+    character: number(character_size, 0x0400),
+    character_writable: false,
+    mirroring: Mirroring::Vertical,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cartridge::{HEADER_SIZE, PROGRAM_ROM_BANK_SIZE, TRAINER_SIZE};
+
+  #[test]
+  fn unsupported_mapper() {
+    let mut bytes = vec![0; HEADER_SIZE + PROGRAM_ROM_BANK_SIZE];
+    bytes[..8].copy_from_slice(b"NES\x1A\x01\x00\xF0\x00");
+    let cartridge = Cartridge::from_bytes(&bytes).unwrap();
+
+    assert!(matches!(
+      from_cartridge(&cartridge),
+      Err(Error::UnsupportedMapper(0x0F))
+    ));
+  }
+
+  #[test]
+  fn trainer_is_loaded_into_program_ram() {
+    let mut bytes = vec![0; HEADER_SIZE + TRAINER_SIZE + PROGRAM_ROM_BANK_SIZE];
+    bytes[..8].copy_from_slice(b"NES\x1A\x01\x00\x04\x00");
+    bytes[HEADER_SIZE] = 0x12;
+    let cartridge = Cartridge::from_bytes(&bytes).unwrap();
+
+    let mapper = from_cartridge(&cartridge).unwrap();
+
+    assert_eq!(Some(0x12), mapper.read_program(0x7000));
+  }
+}
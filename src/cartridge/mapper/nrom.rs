@@ -0,0 +1,80 @@
+//! NROM (mapper 0), with no bank switching
+//!
+//! See <https://www.nesdev.org/wiki/NROM>.
+
+use super::{Board, Mapper};
+use crate::{
+  cartridge::{Mirroring, CHARACTER_ROM_BANK_SIZE},
+  cpu,
+  memory::{
+    self,
+    constant::{PROGRAM_RAM_END, PROGRAM_RAM_START, PROGRAM_ROM_SIZE, PROGRAM_ROM_START},
+  },
+};
+
+/// 16 KiB or 32 KiB of program ROM, with a 16 KiB ROM mirrored into both halves of the ROM region
+#[derive(Debug)]
+pub struct Nrom {
+  board: Board,
+}
+
+impl Nrom {
+  #[must_use]
+  pub fn new(board: Board) -> Self {
+    Self { board }
+  }
+}
+
+impl Mapper for Nrom {
+  fn read_program(&self, address: memory::Address) -> Option<cpu::Int> {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END => self.board.read_program_ram(address),
+      PROGRAM_ROM_START..=memory::Address::MAX => Some(self.board.read_program_rom(
+        0,
+        usize::from(PROGRAM_ROM_SIZE),
+        address,
+      )),
+      _ => None,
+    }
+  }
+
+  fn write_program(&mut self, address: memory::Address, data: cpu::Int) {
+    if let PROGRAM_RAM_START..PROGRAM_RAM_END = address {
+      self.board.write_program_ram(address, data);
+    }
+  }
+
+  fn read_character(&self, address: memory::Address) -> cpu::Int {
+    self
+      .board
+      .read_character(0, CHARACTER_ROM_BANK_SIZE, address)
+  }
+
+  fn write_character(&mut self, address: memory::Address, data: cpu::Int) {
+    self
+      .board
+      .write_character(0, CHARACTER_ROM_BANK_SIZE, address, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.board.mirroring
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cartridge::mapper::numbered_board;
+
+  #[test]
+  fn mirrors_single_bank() {
+    let mut nrom = Nrom::new(numbered_board(0x4000, 0x2000));
+    nrom.write_program(0x8000, 0xFF);
+
+    assert_eq!(Some(0), nrom.read_program(0x8000));
+    assert_eq!(Some(1), nrom.read_program(0xBFFF));
+    assert_eq!(Some(0), nrom.read_program(0xC000));
+    assert_eq!(Some(1), nrom.read_program(0xFFFF));
+    assert_eq!(None, nrom.read_program(0x5000));
+  }
+}
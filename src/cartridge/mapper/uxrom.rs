@@ -0,0 +1,92 @@
+//! UxROM (mapper 2), with a switchable 16 KiB program ROM bank
+//!
+//! See <https://www.nesdev.org/wiki/UxROM>.
+
+use super::{Board, Mapper};
+use crate::{
+  cartridge::{Mirroring, CHARACTER_ROM_BANK_SIZE, PROGRAM_ROM_BANK_SIZE},
+  cpu,
+  memory::{
+    self,
+    constant::{PROGRAM_RAM_END, PROGRAM_RAM_START, PROGRAM_ROM_START},
+  },
+};
+
+/// Start of the program ROM bank which is fixed to the last bank
+const FIXED_BANK_START: memory::Address = 0xC000;
+
+/// A switchable bank at $8000-$BFFF, with the last bank fixed at $C000-$FFFF
+#[derive(Debug)]
+pub struct Uxrom {
+  board: Board,
+  bank: usize,
+}
+
+impl Uxrom {
+  #[must_use]
+  pub fn new(board: Board) -> Self {
+    Self { board, bank: 0 }
+  }
+}
+
+impl Mapper for Uxrom {
+  fn read_program(&self, address: memory::Address) -> Option<cpu::Int> {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END => self.board.read_program_ram(address),
+      PROGRAM_ROM_START..FIXED_BANK_START => Some(self.board.read_program_rom(
+        self.bank,
+        PROGRAM_ROM_BANK_SIZE,
+        address,
+      )),
+      FIXED_BANK_START..=memory::Address::MAX => {
+        let last = self.board.program_rom_banks(PROGRAM_ROM_BANK_SIZE) - 1;
+        Some(
+          self
+            .board
+            .read_program_rom(last, PROGRAM_ROM_BANK_SIZE, address),
+        )
+      }
+      _ => None,
+    }
+  }
+
+  fn write_program(&mut self, address: memory::Address, data: cpu::Int) {
+    match address {
+      PROGRAM_RAM_START..PROGRAM_RAM_END => self.board.write_program_ram(address, data),
+      PROGRAM_ROM_START..=memory::Address::MAX => self.bank = usize::from(data),
+      _ => {}
+    }
+  }
+
+  fn read_character(&self, address: memory::Address) -> cpu::Int {
+    self
+      .board
+      .read_character(0, CHARACTER_ROM_BANK_SIZE, address)
+  }
+
+  fn write_character(&mut self, address: memory::Address, data: cpu::Int) {
+    self
+      .board
+      .write_character(0, CHARACTER_ROM_BANK_SIZE, address, data);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.board.mirroring
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cartridge::mapper::numbered_board;
+
+  #[test]
+  fn switches_lower_bank() {
+    let mut uxrom = Uxrom::new(numbered_board(0x20000, 0x2000));
+
+    uxrom.write_program(0x8000, 2);
+
+    assert_eq!(Some(4), uxrom.read_program(0x8000));
+    assert_eq!(Some(15), uxrom.read_program(0xFFFF));
+  }
+}
@@ -0,0 +1,437 @@
+//! Parsing of iNES and NES 2.0 ROM files
+//!
+//! See <https://www.nesdev.org/wiki/INES> and <https://www.nesdev.org/wiki/NES_2.0>.
+
+pub mod error;
+pub mod mapper;
+
+use std::io::Read;
+
+pub use self::error::Error;
+pub use self::mapper::Mapper;
+use crate::cpu;
+
+/// Bytes which every iNES file starts with
+pub const MAGIC: [u8; 4] = *b"NES\x1A";
+pub const HEADER_SIZE: usize = 16;
+pub const TRAINER_SIZE: usize = 512;
+pub const PROGRAM_ROM_BANK_SIZE: usize = 0x4000;
+pub const CHARACTER_ROM_BANK_SIZE: usize = 0x2000;
+pub const PROGRAM_RAM_BANK_SIZE: usize = 0x2000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+  /// The original iNES format
+  INes,
+  /// The backwards-compatible NES 2.0 extension of the iNES format
+  Nes2,
+}
+
+/// How the PPU's nametables are mapped onto its two kilobytes of video RAM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirroring {
+  /// Nametables are stacked vertically, for games which scroll vertically
+  Horizontal,
+  /// Nametables are side by side, for games which scroll horizontally
+  Vertical,
+  /// The cartridge provides extra video RAM for four distinct nametables
+  FourScreen,
+  /// Every nametable shows the first kilobyte of video RAM, as selected by some mappers
+  SingleScreenLower,
+  /// Every nametable shows the second kilobyte of video RAM, as selected by some mappers
+  SingleScreenUpper,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cartridge {
+  pub format: Format,
+  /// iNES mapper number, identifying the circuitry on the cartridge board
+  pub mapper: u16,
+  /// NES 2.0 submapper number, distinguishing variants of a mapper
+  pub submapper: u8,
+  pub mirroring: Mirroring,
+  /// Whether the cartridge contains battery-backed memory which should persist
+  pub battery: bool,
+  /// 512 bytes to be loaded at $7000, used by some modified dumps
+  pub trainer: Option<Vec<cpu::Int>>,
+  pub program_rom: Vec<cpu::Int>,
+  pub character_rom: Vec<cpu::Int>,
+  /// Size in bytes of the (volatile) program RAM at $6000-$7FFF
+  pub program_ram_size: usize,
+  /// Size in bytes of the battery-backed program RAM at $6000-$7FFF
+  pub program_nvram_size: usize,
+  /// Size in bytes of the character RAM, used in place of (or alongside) character ROM
+  pub character_ram_size: usize,
+  /// Size in bytes of the battery-backed character RAM
+  pub character_nvram_size: usize,
+}
+
+impl Cartridge {
+  /// Reads and parses a complete iNES or NES 2.0 file
+  ///
+  /// # Errors
+  /// Returns an [`Error`] if the file cannot be read or is not a valid iNES file
+  pub fn read_from(from: &mut dyn Read) -> Result<Self, Error> {
+    let mut bytes = Vec::new();
+    from.read_to_end(&mut bytes)?;
+    Self::from_bytes(&bytes)
+  }
+
+  /// Parses a complete iNES or NES 2.0 file
+  ///
+  /// # Errors
+  /// Returns an [`Error`] if the bytes are not a valid iNES file
+  pub fn from_bytes(bytes: &[cpu::Int]) -> Result<Self, Error> {
+    let mut reader = Reader { bytes };
+    let header = reader.take("header", HEADER_SIZE)?;
+    if header[0..4] != MAGIC {
+      return Err(Error::InvalidMagic);
+    }
+
+    let flags_6 = header[6];
+    let flags_7 = header[7];
+    let format = if flags_7 & 0b0000_1100 == 0b0000_1000 {
+      Format::Nes2
+    } else {
+      Format::INes
+    };
+
+    let mirroring = if flags_6 & 0b0000_1000 != 0 {
+      Mirroring::FourScreen
+    } else if flags_6 & 0b0000_0001 != 0 {
+      Mirroring::Vertical
+    } else {
+      Mirroring::Horizontal
+    };
+    let battery = flags_6 & 0b0000_0010 != 0;
+    let has_trainer = flags_6 & 0b0000_0100 != 0;
+
+    let mapper_low = u16::from(flags_6 >> 4);
+    let (mapper, submapper, sizes) = match format {
+      Format::INes => {
+        // Some old dumps have text such as "DiskDude!" in the unused header bytes, which corrupts the upper nibble
+        let mapper_high = if header[12..16].iter().all(|&byte| byte == 0) {
+          u16::from(flags_7 & 0xF0)
+        } else {
+          0
+        };
+        (mapper_high | mapper_low, 0, Self::ines_sizes(header))
+      }
+      Format::Nes2 => {
+        let mapper = u16::from(header[8] & 0x0F) << 8 | u16::from(flags_7 & 0xF0) | mapper_low;
+        (mapper, header[8] >> 4, Self::nes2_sizes(header)?)
+      }
+    };
+
+    let trainer = if has_trainer {
+      Some(reader.take("trainer", TRAINER_SIZE)?.to_vec())
+    } else {
+      None
+    };
+    let program_rom = reader.take("program ROM", sizes.program_rom)?.to_vec();
+    if program_rom.is_empty() {
+      return Err(Error::MissingProgramRom);
+    }
+    let character_rom = reader.take("character ROM", sizes.character_rom)?.to_vec();
+
+    Ok(Self {
+      format,
+      mapper,
+      submapper,
+      mirroring,
+      battery,
+      trainer,
+      program_rom,
+      character_rom,
+      program_ram_size: sizes.program_ram,
+      program_nvram_size: sizes.program_nvram,
+      character_ram_size: sizes.character_ram,
+      character_nvram_size: sizes.character_nvram,
+    })
+  }
+
+  fn ines_sizes(header: &[cpu::Int]) -> Sizes {
+    let character_rom = usize::from(header[5]) * CHARACTER_ROM_BANK_SIZE;
+    // A size of zero is treated as a single bank, for compatibility with older dumps
+    let program_ram = usize::from(header[8]).max(1) * PROGRAM_RAM_BANK_SIZE;
+    let battery = header[6] & 0b0000_0010 != 0;
+    Sizes {
+      program_rom: usize::from(header[4]) * PROGRAM_ROM_BANK_SIZE,
+      character_rom,
+      program_ram: if battery { 0 } else { program_ram },
+      program_nvram: if battery { program_ram } else { 0 },
+      // Boards without character ROM have character RAM instead
+      character_ram: if character_rom == 0 {
+        CHARACTER_ROM_BANK_SIZE
+      } else {
+        0
+      },
+      character_nvram: 0,
+    }
+  }
+
+  fn nes2_sizes(header: &[cpu::Int]) -> Result<Sizes, Error> {
+    Ok(Sizes {
+      program_rom: nes2_rom_size(
+        "program ROM",
+        header[4],
+        header[9] & 0x0F,
+        PROGRAM_ROM_BANK_SIZE,
+      )?,
+      character_rom: nes2_rom_size(
+        "character ROM",
+        header[5],
+        header[9] >> 4,
+        CHARACTER_ROM_BANK_SIZE,
+      )?,
+      program_ram: nes2_ram_size(header[10] & 0x0F),
+      program_nvram: nes2_ram_size(header[10] >> 4),
+      character_ram: nes2_ram_size(header[11] & 0x0F),
+      character_nvram: nes2_ram_size(header[11] >> 4),
+    })
+  }
+}
+
+/// Sizes in bytes of each memory section described by the header
+struct Sizes {
+  program_rom: usize,
+  character_rom: usize,
+  program_ram: usize,
+  program_nvram: usize,
+  character_ram: usize,
+  character_nvram: usize,
+}
+
+/// Decodes a NES 2.0 ROM size from its least- and most-significant parts
+///
+/// If the most-significant nibble is $F, the least-significant byte instead holds an exponent and multiplier.
+fn nes2_rom_size(
+  section: &'static str,
+  lsb: cpu::Int,
+  msb: cpu::Int,
+  bank_size: usize,
+) -> Result<usize, Error> {
+  if msb == 0x0F {
+    let exponent = lsb >> 2;
+    let multiplier = usize::from(lsb & 0b11) * 2 + 1;
+    1_usize
+      .checked_shl(u32::from(exponent))
+      .and_then(|size| size.checked_mul(multiplier))
+      .ok_or(Error::SizeTooLarge { section, exponent })
+  } else {
+    Ok((usize::from(msb) << 8 | usize::from(lsb)) * bank_size)
+  }
+}
+
+/// Decodes a NES 2.0 RAM size, which is stored as a shift count
+fn nes2_ram_size(shift: cpu::Int) -> usize {
+  if shift == 0 {
+    0
+  } else {
+    64 << shift
+  }
+}
+
+/// Splits sections off the front of a byte slice
+struct Reader<'a> {
+  bytes: &'a [cpu::Int],
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, section: &'static str, size: usize) -> Result<&'a [cpu::Int], Error> {
+    if self.bytes.len() < size {
+      return Err(Error::Truncated {
+        section,
+        expected: size,
+        actual: self.bytes.len(),
+      });
+    }
+    let (taken, rest) = self.bytes.split_at(size);
+    self.bytes = rest;
+    Ok(taken)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rom(header: [u8; HEADER_SIZE], size: usize) -> Vec<u8> {
+    let mut bytes = header.to_vec();
+    bytes.extend((0..=250).cycle().take(size));
+    bytes
+  }
+
+  #[test]
+  fn ines() {
+    let header = [
+      b'N',
+      b'E',
+      b'S',
+      0x1A,
+      2,
+      1,
+      0b0001_0011,
+      0b0000_0000,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+    ];
+    let bytes = rom(header, 2 * PROGRAM_ROM_BANK_SIZE + CHARACTER_ROM_BANK_SIZE);
+
+    let cartridge = Cartridge::from_bytes(&bytes).unwrap();
+
+    assert_eq!(Format::INes, cartridge.format);
+    assert_eq!(1, cartridge.mapper);
+    assert_eq!(Mirroring::Vertical, cartridge.mirroring);
+    assert!(cartridge.battery);
+    assert!(cartridge.trainer.is_none());
+    assert_eq!(0x8000, cartridge.program_rom.len());
+    assert_eq!(
+      bytes[HEADER_SIZE..HEADER_SIZE + 0x8000],
+      cartridge.program_rom
+    );
+    assert_eq!(0x2000, cartridge.character_rom.len());
+    assert_eq!(0, cartridge.character_ram_size);
+    assert_eq!(0x2000, cartridge.program_nvram_size);
+  }
+
+  #[test]
+  fn ines_trainer_and_character_ram() {
+    let header = [
+      b'N',
+      b'E',
+      b'S',
+      0x1A,
+      1,
+      0,
+      0b0000_1100,
+      0b0100_0000,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+    ];
+    let bytes = rom(header, TRAINER_SIZE + PROGRAM_ROM_BANK_SIZE);
+
+    let cartridge = Cartridge::from_bytes(&bytes).unwrap();
+
+    assert_eq!(0x40, cartridge.mapper);
+    assert_eq!(Mirroring::FourScreen, cartridge.mirroring);
+    assert_eq!(
+      Some(TRAINER_SIZE),
+      cartridge.trainer.map(|trainer| trainer.len())
+    );
+    assert_eq!(0x4000, cartridge.program_rom.len());
+    assert!(cartridge.character_rom.is_empty());
+    assert_eq!(0x2000, cartridge.character_ram_size);
+  }
+
+  #[test]
+  fn ines_ignores_corrupt_upper_mapper() {
+    let mut header = [
+      b'N',
+      b'E',
+      b'S',
+      0x1A,
+      1,
+      1,
+      0b0100_0000,
+      b'D',
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+      0,
+    ];
+    header[7..16].copy_from_slice(b"DiskDude!");
+    let bytes = rom(header, PROGRAM_ROM_BANK_SIZE + CHARACTER_ROM_BANK_SIZE);
+
+    let cartridge = Cartridge::from_bytes(&bytes).unwrap();
+
+    assert_eq!(4, cartridge.mapper);
+  }
+
+  #[test]
+  fn nes2() {
+    let header = [
+      b'N',
+      b'E',
+      b'S',
+      0x1A,
+      0x02,
+      0b0000_0101,
+      0b0010_0001,
+      0b0001_1000,
+      0b0011_0001,
+      0xF0,
+      0x07,
+      0x70,
+      0,
+      0,
+      0,
+      0,
+    ];
+    // Character ROM is 2^1 * (1 * 2 + 1) = 6 bytes
+    let bytes = rom(header, 2 * PROGRAM_ROM_BANK_SIZE + 6);
+
+    let cartridge = Cartridge::from_bytes(&bytes).unwrap();
+
+    assert_eq!(Format::Nes2, cartridge.format);
+    assert_eq!(0x112, cartridge.mapper);
+    assert_eq!(3, cartridge.submapper);
+    assert_eq!(0x8000, cartridge.program_rom.len());
+    assert_eq!(6, cartridge.character_rom.len());
+    assert_eq!(0x2000, cartridge.program_ram_size);
+    assert_eq!(0, cartridge.program_nvram_size);
+    assert_eq!(0, cartridge.character_ram_size);
+    assert_eq!(0x2000, cartridge.character_nvram_size);
+  }
+
+  #[test]
+  fn invalid_magic() {
+    let bytes = rom([0; HEADER_SIZE], PROGRAM_ROM_BANK_SIZE);
+
+    assert!(matches!(
+      Cartridge::from_bytes(&bytes),
+      Err(Error::InvalidMagic)
+    ));
+  }
+
+  #[test]
+  fn truncated() {
+    let header = [b'N', b'E', b'S', 0x1A, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let bytes = rom(header, PROGRAM_ROM_BANK_SIZE);
+
+    assert!(matches!(
+      Cartridge::from_bytes(&bytes),
+      Err(Error::Truncated {
+        section: "program ROM",
+        expected: 0x8000,
+        actual: 0x4000,
+      })
+    ));
+  }
+
+  #[test]
+  fn missing_program_rom() {
+    let header = [b'N', b'E', b'S', 0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    assert!(matches!(
+      Cartridge::from_bytes(&header),
+      Err(Error::MissingProgramRom)
+    ));
+  }
+}
@@ -1,9 +1,9 @@
-use std::{convert::TryFrom, path::PathBuf};
+use std::{convert::TryFrom, ops::RangeInclusive, path::PathBuf};
 
 use clap::Parser;
 use thiserror::Error;
 
-use crate::memory;
+use crate::{cpu, emulator::Emulator, input, memory, trace};
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
@@ -14,10 +14,52 @@ pub struct Cli {
   ///
   /// This may be any evalexpr expression which evaluates to a valid memory address integer.
   ///
-  /// The following variables are exposed to be used in the expression: rom, rom_size, ram, ram_size
+  /// The following variables are exposed to be used in the expression: `rom`, `rom_size`, `ram`, `ram_size`
   #[clap(short, long, parse(try_from_str = eval_address_expression))]
   pub start_address: Option<memory::Address>,
+  /// Reject unofficial opcodes as illegal rather than executing them
+  #[clap(long)]
+  pub strict: bool,
+  /// Halt when a BRK instruction is executed, rather than jumping to the interrupt handler
+  #[clap(long)]
+  pub halt_on_break: bool,
+  /// CPU to emulate: `2a03` (NES, no decimal mode) or `6502` (NMOS 6502 with decimal mode)
+  #[clap(long, default_value = "2a03")]
+  pub variant: cpu::Variant,
+  /// Device plugged into the first controller port: `controller`, `zapper`, `four-score`, `power-pad`, `paddle` or `none`
+  #[clap(long, default_value = "controller")]
+  pub port_1: input::Kind,
+  /// Device plugged into the second controller port, as for `--port-1`
+  #[clap(long, default_value = "controller")]
+  pub port_2: input::Kind,
+  /// Stop after running this many frames
+  #[clap(long)]
+  pub frames: Option<u64>,
+  /// Stop once this evalexpr condition holds, which is checked before each instruction
+  ///
+  /// As well as the variables exposed to `--start-address`, the registers are exposed as `pc`, `a`, `x`, `y`,
+  /// `sp` and `p`, along with the CPU's `cycles` and the PPU's `frame`. `peek(address)` reads memory.
+  #[clap(long, parse(try_from_str = evalexpr::build_operator_tree))]
+  pub until: Option<evalexpr::Node>,
+  /// Write the framebuffer to this file once execution stops, as a PNG or PPM image depending on its extension
+  #[clap(long, parse(from_os_str))]
+  pub screenshot: Option<PathBuf>,
+  /// Log each instruction to this file before it runs
+  #[clap(long, parse(from_os_str))]
+  pub trace: Option<PathBuf>,
+  /// Format of the `--trace` log: `nestest`, matching Nintendulator's `nestest.log`, or `compact`, which has
+  /// space-separated fields for PC, instruction bytes, A, X, Y, P, SP, PPU scanline, PPU dot and CPU cycles
+  #[clap(long, default_value = "nestest")]
+  pub trace_format: trace::Format,
+  /// Print a disassembly of this range of addresses, written `start..end` with the end excluded, instead of running
+  ///
+  /// Both ends may be expressions as for `--start-address`, so `rom..rom + rom_size` covers the whole ROM.
+  #[clap(long, parse(try_from_str = eval_address_range))]
+  pub disassemble: Option<RangeInclusive<memory::Address>>,
   /// Program file to load to ROM
+  ///
+  /// This may be an iNES/NES 2.0 file, 6502 assembly source with a `.asm` or `.s` extension, or raw program bytes to
+  /// load at the start of ROM.
   #[clap(name = "FILE", parse(from_os_str))]
   pub file: Option<PathBuf>,
 }
@@ -33,23 +75,184 @@ enum AddressExprError {
     address: evalexpr::IntType,
     source: std::num::TryFromIntError,
   },
+  #[error("range {0:?} should be written as start..end")]
+  RangeSyntax(String),
+  #[error("range {start:#X?}..{end:#X?} is empty")]
+  EmptyRange {
+    start: evalexpr::IntType,
+    end: evalexpr::IntType,
+  },
   #[error(transparent)]
   AddressExpressionError(#[from] evalexpr::EvalexprError),
 }
 
+/// Variables exposed to every expression
+fn address_variables() -> [(&'static str, evalexpr::IntType); 4] {
+  [
+    ("ram", memory::constant::RAM_START.into()),
+    ("ram_size", memory::constant::RAM_SIZE.into()),
+    ("rom", memory::constant::PROGRAM_ROM_START.into()),
+    ("rom_size", memory::constant::PROGRAM_ROM_SIZE.into()),
+  ]
+}
+
+fn eval_int_expression(
+  expression: &str,
+) -> std::result::Result<evalexpr::IntType, AddressExprError> {
+  use evalexpr::ContextWithMutableVariables;
+  let mut context = evalexpr::HashMapContext::new();
+  for (name, value) in address_variables() {
+    context.set_value(name.into(), value.into())?;
+  }
+  Ok(evalexpr::eval_int_with_context(expression, &context)?)
+}
+
+fn to_address(
+  address: evalexpr::IntType,
+) -> std::result::Result<memory::Address, AddressExprError> {
+  memory::Address::try_from(address)
+    .map_err(|source| AddressExprError::AddressOutOfRange { address, source })
+}
+
 fn eval_address_expression(
   expression: &str,
 ) -> std::result::Result<memory::Address, AddressExprError> {
+  to_address(eval_int_expression(expression)?)
+}
+
+/// Parses `start..end`, where the end may be one past the last address
+fn eval_address_range(
+  range: &str,
+) -> std::result::Result<RangeInclusive<memory::Address>, AddressExprError> {
   use AddressExprError::*;
-  let context: evalexpr::HashMapContext = evalexpr::context_map! {
-    "ram" => evalexpr::IntType::from(memory::constant::RAM_START),
-    "ram_size" => evalexpr::IntType::from(memory::constant::RAM_SIZE),
-    "rom" => evalexpr::IntType::from(memory::constant::PROGRAM_ROM_START),
-    "rom_size" => evalexpr::IntType::from(memory::constant::PROGRAM_ROM_SIZE)
-  }?;
-  let result = evalexpr::eval_int_with_context(expression, &context)?;
-  memory::Address::try_from(result).map_err(|source| AddressOutOfRange {
-    address: result,
-    source,
-  })
+  let (start, end) = range
+    .split_once("..")
+    .ok_or_else(|| RangeSyntax(range.to_owned()))?;
+  let start = eval_int_expression(start)?;
+  let end = eval_int_expression(end)?;
+  if end <= start {
+    return Err(EmptyRange { start, end });
+  }
+  Ok(to_address(start)?..=to_address(end - 1)?)
+}
+
+/// Exposes the state of a running emulator to `--until` conditions
+struct EmulatorContext<'a> {
+  emulator: &'a Emulator,
+  variables: Vec<(&'static str, evalexpr::Value)>,
+}
+
+impl<'a> EmulatorContext<'a> {
+  fn new(emulator: &'a Emulator) -> Self {
+    let register = &emulator.cpu.register;
+    let mut variables: Vec<(&'static str, evalexpr::Value)> = vec![
+      ("pc", evalexpr::Value::Int(register.program_counter.into())),
+      ("a", evalexpr::Value::Int(register.accumulator.into())),
+      ("x", evalexpr::Value::Int(register.index_x.into())),
+      ("y", evalexpr::Value::Int(register.index_y.into())),
+      ("sp", evalexpr::Value::Int(register.stack_pointer.into())),
+      ("p", evalexpr::Value::Int(register.status.to_byte().into())),
+      ("cycles", saturating_int(emulator.cpu.cycles)),
+      ("frame", saturating_int(emulator.ppu().frame())),
+    ];
+    variables.extend(
+      address_variables()
+        .into_iter()
+        .map(|(name, value)| (name, value.into())),
+    );
+    Self {
+      emulator,
+      variables,
+    }
+  }
+}
+
+impl evalexpr::Context for EmulatorContext<'_> {
+  fn get_value(&self, identifier: &str) -> Option<&evalexpr::Value> {
+    self
+      .variables
+      .iter()
+      .find(|(name, _)| *name == identifier)
+      .map(|(_, value)| value)
+  }
+
+  fn call_function(
+    &self,
+    identifier: &str,
+    argument: &evalexpr::Value,
+  ) -> evalexpr::EvalexprResult<evalexpr::Value> {
+    match identifier {
+      "peek" => {
+        let address = argument.as_int()?;
+        let address = memory::Address::try_from(address).map_err(|_| {
+          evalexpr::EvalexprError::CustomMessage(format!("address {address:#X} is out of range"))
+        })?;
+        self
+          .emulator
+          .peek(address)
+          .map(|data| evalexpr::Value::Int(data.into()))
+          .map_err(|err| evalexpr::EvalexprError::CustomMessage(err.to_string()))
+      }
+      _ => Err(evalexpr::EvalexprError::FunctionIdentifierNotFound(
+        identifier.to_owned(),
+      )),
+    }
+  }
+}
+
+fn saturating_int(value: u64) -> evalexpr::Value {
+  evalexpr::IntType::try_from(value)
+    .unwrap_or(evalexpr::IntType::MAX)
+    .into()
+}
+
+/// Evaluates a `--until` condition against the emulator's current state
+///
+/// # Errors
+/// Returns an [`evalexpr::EvalexprError`] if the condition cannot be evaluated, or is not a boolean
+pub fn condition_holds(
+  condition: &evalexpr::Node,
+  emulator: &Emulator,
+) -> evalexpr::EvalexprResult<bool> {
+  condition.eval_boolean_with_context(&EmulatorContext::new(emulator))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn start_address_expression() {
+    assert_eq!(0x8010, eval_address_expression("rom + 16").unwrap());
+    assert!(eval_address_expression("rom + rom_size").is_err());
+  }
+
+  #[test]
+  fn address_range() {
+    assert_eq!(
+      0x8000..=0xFFFF,
+      eval_address_range("rom..rom + rom_size").unwrap()
+    );
+    assert_eq!(0x0010..=0x001F, eval_address_range("16..32").unwrap());
+    assert!(eval_address_range("32..16").is_err());
+    assert!(eval_address_range("rom").is_err());
+  }
+
+  #[test]
+  fn until_condition() {
+    let mut emulator = Emulator::default();
+    emulator.cpu.memory.ram[0x10] = 0x2A;
+    emulator.cpu.register.index_x = 3;
+
+    let holds = |condition| {
+      condition_holds(
+        &evalexpr::build_operator_tree(condition).unwrap(),
+        &emulator,
+      )
+    };
+
+    assert!(holds("peek(ram + 16) == 42 && x == 3").unwrap());
+    assert!(!holds("pc == rom").unwrap());
+    assert!(holds("peek(65536)").is_err());
+  }
 }
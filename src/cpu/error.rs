@@ -1,17 +1,24 @@
-use crate::cpu::Operation;
 use std::io;
+
 use thiserror::Error;
 
+use crate::{cpu, memory};
+
 #[derive(Error, Debug)]
 pub enum Error {
-  #[error("unimplemented operation: {0}")]
-  UnimplementedOperation(Operation),
+  #[error("illegal opcode {opcode:#04X} at address {address:#06X}")]
+  IllegalOpcode {
+    address: memory::Address,
+    opcode: cpu::Int,
+  },
+  #[error("no device is attached at address {0:#06X}")]
+  UnmappedAddress(memory::Address),
   #[error("io error: {0}")]
-  IoError(io::Error),
+  Io(io::Error),
 }
 
 impl From<io::Error> for Error {
   fn from(err: io::Error) -> Self {
-    Self::IoError(err)
+    Self::Io(err)
   }
 }
@@ -1,83 +1,500 @@
 use tracing::{debug, instrument};
 
 use super::error::Error;
-use super::operation::addressing_mode::Value;
+use super::interrupt::Interrupt;
+use super::operation::addressing_mode::{same_page, Location, Value};
 use super::operation::Operation;
+use crate::memory::Bus;
+
+impl<B: Bus> super::Nes<B> {
+  /// Sets the zero and negative flags to describe the given result
+  fn set_result_flags(&mut self, result: super::Int) {
+    self.register.status.result_status.zero = result == 0;
+    self.register.status.result_status.negative = result & 0x80 != 0;
+  }
 
-impl super::Cpu {
   fn set_accumulator(&mut self, result: super::Int) {
     self.register.accumulator = result;
-    self.register.status.result_status.zero = result == 0;
+    self.set_result_flags(result);
   }
 
-  /// Executes the given operation
+  fn set_index_x(&mut self, result: super::Int) {
+    self.register.index_x = result;
+    self.set_result_flags(result);
+  }
+
+  fn set_index_y(&mut self, result: super::Int) {
+    self.register.index_y = result;
+    self.set_result_flags(result);
+  }
+
+  /// Executes the given operation, returning the number of cycles it took
   ///
-  /// # Errors
-  /// Forwards errors from executing the operation
+  /// The cycles are also added to the running total in [`Cpu::cycles`](super::Cpu::cycles).
   ///
-  /// Returns [`Error::UnimplementedOperation`] if the provided operation is not implemented
+  /// # Errors
+  /// Forwards errors from executing the operation, such as accessing unmapped memory
   #[instrument]
-  pub fn execute(&mut self, operation: Operation) -> Result<(), Error> {
+  pub fn execute(&mut self, operation: Operation) -> Result<u8, Error> {
     use Operation::*;
     debug!(?operation);
+    let mut cycles = operation.base_cycles();
+    if let Some(location) = operation.page_penalty_location() {
+      cycles += u8::from(location.page_crossed(self)?);
+    }
+
+    let status = &self.register.status.result_status;
     match operation {
-      Adc(value) => self.add_with_carry(value),
-      And(value) => self.and(value),
-      Brk => self.stop = true,
-      _ => return Err(Error::UnimplementedOperation(operation)),
-    };
+      Adc(value) => self.add_with_carry(value)?,
+      And(value) => self.and(value)?,
+      ASLAcc => {
+        let result = self.shift_left(self.register.accumulator);
+        self.set_accumulator(result);
+      }
+      Asl(location) => self.modify(location, Self::shift_left)?,
+      Bit(value) => self.bit(value)?,
+      Bpl(value) => cycles += self.branch(!status.negative, value)?,
+      Bmi(value) => cycles += self.branch(status.negative, value)?,
+      Bvc(value) => cycles += self.branch(!status.overflow, value)?,
+      Bvs(value) => cycles += self.branch(status.overflow, value)?,
+      Bcc(value) => cycles += self.branch(!status.carry, value)?,
+      Bcs(value) => cycles += self.branch(status.carry, value)?,
+      Bne(value) => cycles += self.branch(!status.zero, value)?,
+      Beq(value) => cycles += self.branch(status.zero, value)?,
+      Brk if self.config.halt_on_break => self.halt(),
+      Brk => {
+        // BRK is followed by a padding byte which is skipped on return
+        self.register.program_counter = self.register.program_counter.wrapping_add(1);
+        self.interrupt(Interrupt::Break)?;
+      }
+      Cmp(value) => self.compare(self.register.accumulator, value)?,
+      Cpx(value) => self.compare(self.register.index_x, value)?,
+      Cpy(value) => self.compare(self.register.index_y, value)?,
+      Dec(location) => self.modify(location, Self::decrement)?,
+      Eor(value) => self.exclusive_or(value)?,
+      Sec => self.register.status.result_status.carry = true,
+      Clc => self.register.status.result_status.carry = false,
+      Sei => self.register.status.interrupt_status.disabled = true,
+      Cli => self.register.status.interrupt_status.disabled = false,
+      Clv => self.register.status.result_status.overflow = false,
+      Set => self.register.status.decimal_mode = super::registers::NumberMode::BinaryCodedDecimal,
+      Cld => self.register.status.decimal_mode = super::registers::NumberMode::Binary,
+      Inc(location) => self.modify(location, Self::increment)?,
+      Jmp(location) => self.register.program_counter = location.location(self)?,
+      Jsr(location) => self.jump_to_subroutine(location)?,
+      Lda(value) => {
+        let value = value.value(self)?;
+        self.set_accumulator(value);
+      }
+      Ldx(value) => {
+        let value = value.value(self)?;
+        self.set_index_x(value);
+      }
+      Ldy(value) => {
+        let value = value.value(self)?;
+        self.set_index_y(value);
+      }
+      LsrAcc => {
+        let result = self.shift_right(self.register.accumulator);
+        self.set_accumulator(result);
+      }
+      Lsr(location) => self.modify(location, Self::shift_right)?,
+      Nop => {}
+      Ora(value) => self.or(value)?,
+      Pha => self.push(self.register.accumulator)?,
+      Pla => {
+        let value = self.pull()?;
+        self.set_accumulator(value);
+      }
+      // PHP always pushes with the break flag set
+      Php => self.push(self.register.status.to_stack_byte(true))?,
+      Plp => {
+        let pulled = self.pull()?;
+        self.register.status.set_from_stack_byte(pulled);
+      }
+      Tax => self.set_index_x(self.register.accumulator),
+      Txa => self.set_accumulator(self.register.index_x),
+      Dex => self.set_index_x(self.register.index_x.wrapping_sub(1)),
+      Inx => self.set_index_x(self.register.index_x.wrapping_add(1)),
+      Tay => self.set_index_y(self.register.accumulator),
+      Tya => self.set_accumulator(self.register.index_y),
+      Dey => self.set_index_y(self.register.index_y.wrapping_sub(1)),
+      Iny => self.set_index_y(self.register.index_y.wrapping_add(1)),
+      Tsx => self.set_index_x(self.register.stack_pointer),
+      Txs => self.register.stack_pointer = self.register.index_x,
+      RolAcc => {
+        let result = self.rotate_left(self.register.accumulator);
+        self.set_accumulator(result);
+      }
+      Rol(location) => self.modify(location, Self::rotate_left)?,
+      RorAcc => {
+        let result = self.rotate_right(self.register.accumulator);
+        self.set_accumulator(result);
+      }
+      Ror(location) => self.modify(location, Self::rotate_right)?,
+      Rti => self.return_from_interrupt()?,
+      Rts => self.return_from_subroutine()?,
+      Sbc(value) => self.subtract_with_carry(value)?,
+      Sta(location) => self.store(location, self.register.accumulator)?,
+      Stx(location) => self.store(location, self.register.index_x)?,
+      Sty(location) => self.store(location, self.register.index_y)?,
+      Lax(value) => {
+        let value = value.value(self)?;
+        self.set_accumulator(value);
+        self.set_index_x(value);
+      }
+      Sax(location) => self.store(location, self.register.accumulator & self.register.index_x)?,
+      Dcp(location) => {
+        let value = self.read_modify_write(location, Self::decrement)?;
+        self.compare(self.register.accumulator, value)?;
+      }
+      Isc(location) => {
+        let value = self.read_modify_write(location, Self::increment)?;
+        self.subtract_with_carry(value)?;
+      }
+      Slo(location) => {
+        let value = self.read_modify_write(location, Self::shift_left)?;
+        self.or(value)?;
+      }
+      Rla(location) => {
+        let value = self.read_modify_write(location, Self::rotate_left)?;
+        self.and(value)?;
+      }
+      Sre(location) => {
+        let value = self.read_modify_write(location, Self::shift_right)?;
+        self.exclusive_or(value)?;
+      }
+      Rra(location) => {
+        let value = self.read_modify_write(location, Self::rotate_right)?;
+        self.add_with_carry(value)?;
+      }
+      Anc(value) => {
+        self.and(value)?;
+        self.register.status.result_status.carry = self.register.status.result_status.negative;
+      }
+      Alr(value) => {
+        self.and(value)?;
+        let result = self.shift_right(self.register.accumulator);
+        self.set_accumulator(result);
+      }
+      Arr(value) => self.and_rotate_right(value)?,
+      Axs(value) => {
+        let register = self.register.accumulator & self.register.index_x;
+        self.compare(register, value)?;
+        let value = value.value(self)?;
+        self.set_index_x(register.wrapping_sub(value));
+      }
+      Ign(value) => {
+        value.value(self)?;
+      }
+    }
+
+    self.cycles += u64::from(cycles);
+    Ok(cycles)
+  }
 
+  fn add_with_carry<T: Into<Value>>(&mut self, source: T) -> Result<(), Error> {
+    let source: Value = source.into();
+    let value = source.value(self)?;
+    debug!(accumulator = self.register.accumulator, value, ?source);
+    if self.decimal_enabled() {
+      self.add_decimal(value);
+    } else {
+      self.add_to_accumulator(value);
+    }
     Ok(())
   }
 
-  fn add_with_carry<T: Into<Value>>(&mut self, source: T) {
-    let accumulator = self.register.accumulator;
+  fn subtract_with_carry<T: Into<Value>>(&mut self, source: T) -> Result<(), Error> {
     let source: Value = source.into();
-    let value = source.value(self);
-    debug!(accumulator, value, ?source);
-
-    let (mut result, mut carry) = accumulator.overflowing_add(value);
-    if self.register.status.result_status.overflow {
-      let (carry_result, carry_carry) = result.overflowing_add(1);
-      result = carry_result;
-      carry |= carry_carry;
+    let value = source.value(self)?;
+    debug!(accumulator = self.register.accumulator, value, ?source);
+    let accumulator = self.register.accumulator;
+    let borrow = !self.register.status.result_status.carry;
+    // A - M - (1 - C) == A + !M + C
+    self.add_to_accumulator(!value);
+    // The NMOS 6502 sets all flags from the binary result, even in decimal mode
+    if self.decimal_enabled() {
+      self.register.accumulator = subtract_decimal(accumulator, value, borrow);
     }
+    Ok(())
+  }
+
+  /// Whether arithmetic should treat values as binary-coded decimal
+  fn decimal_enabled(&self) -> bool {
+    self.config.variant.has_decimal_mode()
+      && self.register.status.decimal_mode == super::registers::NumberMode::BinaryCodedDecimal
+  }
+
+  /// Adds to the accumulator as binary-coded decimal, with the flag behaviour of the NMOS 6502
+  ///
+  /// Only the carry flag is valid; zero is set from the binary sum, while negative and overflow are set from
+  /// the sum before the high digit is adjusted.
+  fn add_decimal(&mut self, value: super::Int) {
+    let accumulator = self.register.accumulator;
+    let carry_in = super::Int::from(self.register.status.result_status.carry);
+
+    let mut low = (accumulator & 0x0F) + (value & 0x0F) + carry_in;
+    if low > 0x09 {
+      low += 0x06;
+    }
+    let mut high = u16::from(accumulator >> 4) + u16::from(value >> 4) + u16::from(low > 0x0F);
+    let [unadjusted, _] = (high << 4).to_le_bytes();
+
+    let status = &mut self.register.status.result_status;
+    status.zero = accumulator.wrapping_add(value).wrapping_add(carry_in) == 0;
+    status.negative = unadjusted & 0x80 != 0;
+    status.overflow = !(accumulator ^ value) & (accumulator ^ unadjusted) & 0x80 != 0;
+
+    if high > 0x09 {
+      high += 0x06;
+    }
+    status.carry = high > 0x0F;
+    let [high, _] = high.to_le_bytes();
+    self.register.accumulator = high << 4 | low & 0x0F;
+  }
+
+  fn add_to_accumulator(&mut self, value: super::Int) {
+    let accumulator = self.register.accumulator;
+    let carry_in = super::Int::from(self.register.status.result_status.carry);
+
+    let (partial, carry_partial) = accumulator.overflowing_add(value);
+    let (result, carry_result) = partial.overflowing_add(carry_in);
 
     self.set_accumulator(result);
-    self.register.status.result_status.carry = carry;
+    self.register.status.result_status.carry = carry_partial || carry_result;
     // Overflow if both inputs' sign bits differ from the result sign
     let overflow = (accumulator ^ result) & (value ^ result) & 0x80;
     self.register.status.result_status.overflow = overflow != 0;
   }
 
-  fn and<T: Into<Value>>(&mut self, source: T) {
+  fn and<T: Into<Value>>(&mut self, source: T) -> Result<(), Error> {
     let accumulator = self.register.accumulator;
     let source: Value = source.into();
-    let value = source.value(self);
+    let value = source.value(self)?;
     let result = accumulator & value;
     self.set_accumulator(result);
+    Ok(())
+  }
+
+  fn or<T: Into<Value>>(&mut self, source: T) -> Result<(), Error> {
+    let value = source.into().value(self)?;
+    self.set_accumulator(self.register.accumulator | value);
+    Ok(())
+  }
+
+  fn exclusive_or<T: Into<Value>>(&mut self, source: T) -> Result<(), Error> {
+    let value = source.into().value(self)?;
+    self.set_accumulator(self.register.accumulator ^ value);
+    Ok(())
+  }
+
+  fn bit<T: Into<Value>>(&mut self, source: T) -> Result<(), Error> {
+    let value = source.into().value(self)?;
+    let status = &mut self.register.status.result_status;
+    status.zero = self.register.accumulator & value == 0;
+    status.negative = value & 0b1000_0000 != 0;
+    status.overflow = value & 0b0100_0000 != 0;
+    Ok(())
+  }
+
+  fn compare<T: Into<Value>>(&mut self, register: super::Int, source: T) -> Result<(), Error> {
+    let value = source.into().value(self)?;
+    self.set_result_flags(register.wrapping_sub(value));
+    self.register.status.result_status.carry = register >= value;
+    Ok(())
   }
+
+  /// Branches to the target if the condition holds, returning the number of extra cycles taken
+  ///
+  /// Taking a branch costs one cycle, plus another if the target is on a different page.
+  fn branch(&mut self, condition: bool, target: Value) -> Result<u8, Error> {
+    let Value::Location(location) = target else {
+      return Ok(0);
+    };
+    if !condition {
+      return Ok(0);
+    }
+
+    let address = location.location(self)?;
+    let crossed = !same_page(self.register.program_counter, address);
+    self.register.program_counter = address;
+    Ok(1 + u8::from(crossed))
+  }
+
+  fn store(&mut self, location: Location, value: super::Int) -> Result<(), Error> {
+    let address = location.location(self)?;
+    self.memory.write(address, value)
+  }
+
+  /// Applies a read-modify-write operation to the value at the given location
+  fn modify(
+    &mut self,
+    location: Location,
+    operation: fn(&mut Self, super::Int) -> super::Int,
+  ) -> Result<(), Error> {
+    self.read_modify_write(location, operation)?;
+    Ok(())
+  }
+
+  /// Applies a read-modify-write operation to the value at the given location, returning the value written
+  fn read_modify_write(
+    &mut self,
+    location: Location,
+    operation: fn(&mut Self, super::Int) -> super::Int,
+  ) -> Result<super::Int, Error> {
+    let address = location.location(self)?;
+    let value = self.memory.read(address)?;
+    let result = operation(self, value);
+    self.memory.write(address, result)?;
+    Ok(result)
+  }
+
+  fn and_rotate_right(&mut self, source: Value) -> Result<(), Error> {
+    self.and(source)?;
+    let carry_in = super::Int::from(self.register.status.result_status.carry);
+    let result = self.register.accumulator >> 1 | carry_in << 7;
+    self.set_accumulator(result);
+    let status = &mut self.register.status.result_status;
+    status.carry = result & 0b0100_0000 != 0;
+    status.overflow = (result >> 6 ^ result >> 5) & 1 != 0;
+    Ok(())
+  }
+
+  fn increment(&mut self, value: super::Int) -> super::Int {
+    let result = value.wrapping_add(1);
+    self.set_result_flags(result);
+    result
+  }
+
+  fn decrement(&mut self, value: super::Int) -> super::Int {
+    let result = value.wrapping_sub(1);
+    self.set_result_flags(result);
+    result
+  }
+
+  fn shift_left(&mut self, value: super::Int) -> super::Int {
+    let result = value << 1;
+    self.register.status.result_status.carry = value & 0b1000_0000 != 0;
+    self.set_result_flags(result);
+    result
+  }
+
+  fn shift_right(&mut self, value: super::Int) -> super::Int {
+    let result = value >> 1;
+    self.register.status.result_status.carry = value & 0b0000_0001 != 0;
+    self.set_result_flags(result);
+    result
+  }
+
+  fn rotate_left(&mut self, value: super::Int) -> super::Int {
+    let carry_in = super::Int::from(self.register.status.result_status.carry);
+    let result = value << 1 | carry_in;
+    self.register.status.result_status.carry = value & 0b1000_0000 != 0;
+    self.set_result_flags(result);
+    result
+  }
+
+  fn rotate_right(&mut self, value: super::Int) -> super::Int {
+    let carry_in = super::Int::from(self.register.status.result_status.carry);
+    let result = value >> 1 | carry_in << 7;
+    self.register.status.result_status.carry = value & 0b0000_0001 != 0;
+    self.set_result_flags(result);
+    result
+  }
+
+  fn jump_to_subroutine(&mut self, location: Location) -> Result<(), Error> {
+    // The return address pushed is the last byte of the JSR instruction
+    self.push_u16(self.register.program_counter.wrapping_sub(1))?;
+    self.register.program_counter = location.location(self)?;
+    Ok(())
+  }
+
+  fn return_from_subroutine(&mut self) -> Result<(), Error> {
+    self.register.program_counter = self.pull_u16()?.wrapping_add(1);
+    Ok(())
+  }
+
+  fn return_from_interrupt(&mut self) -> Result<(), Error> {
+    let status = self.pull()?;
+    self.register.status.set_from_stack_byte(status);
+    self.register.program_counter = self.pull_u16()?;
+    Ok(())
+  }
+}
+
+/// Subtracts as binary-coded decimal, as the NMOS 6502 does
+fn subtract_decimal(accumulator: super::Int, value: super::Int, borrow: bool) -> super::Int {
+  let mut low = i16::from(accumulator & 0x0F) - i16::from(value & 0x0F) - i16::from(borrow);
+  let mut high = i16::from(accumulator >> 4) - i16::from(value >> 4);
+  if low < 0 {
+    low -= 0x06;
+    high -= 1;
+  }
+  if high < 0 {
+    high -= 0x06;
+  }
+  let [result, _] = ((high << 4) | (low & 0x0F)).to_le_bytes();
+  result
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::cpu::{self, operation::addressing_mode::Value};
   use test_case::test_case;
 
-  #[test_case(80, 16, true => (96, false, false))]
-  #[test_case(80, 80, true => (160, false, true))]
-  #[test_case(80, 144, true => (224, false, false))]
-  #[test_case(80, 208, true => (32, true, false))]
-  #[test_case(208, 16, true => (224, false, false))]
-  #[test_case(208, 80, true => (32, true, false))]
-  #[test_case(208, 144, true => (96, true, true))]
-  #[test_case(208, 208, true => (160, true, false))]
+  use crate::{
+    cpu::{
+      self,
+      operation::{
+        addressing_mode::{Location, Value},
+        assemble::assemble,
+        Operation,
+      },
+      registers::NumberMode,
+    },
+    memory::Bus,
+  };
+
+  #[test_case(80, 16, false => (96, false, false))]
+  #[test_case(80, 80, false => (160, false, true))]
+  #[test_case(80, 144, false => (224, false, false))]
+  #[test_case(80, 208, false => (32, true, false))]
+  #[test_case(208, 16, false => (224, false, false))]
+  #[test_case(208, 80, false => (32, true, false))]
+  #[test_case(208, 144, false => (96, true, true))]
+  #[test_case(208, 208, false => (160, true, false))]
+  #[test_case(80, 16, true => (97, false, false))]
+  #[test_case(127, 0, true => (128, false, true))]
+  #[test_case(255, 0, true => (0, true, false))]
   fn add_with_carry(accumulator: u8, value: u8, carry: bool) -> (u8, bool, bool) {
     let mut cpu = cpu::Cpu::default();
     cpu.register.accumulator = accumulator;
     cpu.register.status.result_status.carry = carry;
 
-    cpu.add_with_carry(Value::Immediate(value));
+    cpu.add_with_carry(Value::Immediate(value)).unwrap();
+
+    (
+      cpu.register.accumulator,
+      cpu.register.status.result_status.carry,
+      cpu.register.status.result_status.overflow,
+    )
+  }
+
+  #[test_case(80, 240, true => (96, false, false))]
+  #[test_case(80, 176, true => (160, false, true))]
+  #[test_case(80, 112, true => (224, false, false))]
+  #[test_case(80, 48, true => (32, true, false))]
+  #[test_case(208, 240, true => (224, false, false))]
+  #[test_case(208, 176, true => (32, true, false))]
+  #[test_case(208, 112, true => (96, true, true))]
+  #[test_case(208, 48, true => (160, true, false))]
+  #[test_case(5, 3, false => (1, true, false))]
+  fn subtract_with_carry(accumulator: u8, value: u8, carry: bool) -> (u8, bool, bool) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.accumulator = accumulator;
+    cpu.register.status.result_status.carry = carry;
+
+    cpu.subtract_with_carry(Value::Immediate(value)).unwrap();
 
     (
       cpu.register.accumulator,
@@ -85,4 +502,243 @@ mod tests {
       cpu.register.status.result_status.overflow,
     )
   }
+
+  #[test_case(cpu::Variant::Nmos6502, 0x12, 0x34, false => (0x46, false))]
+  #[test_case(cpu::Variant::Nmos6502, 0x58, 0x46, true => (0x05, true))]
+  #[test_case(cpu::Variant::Nmos6502, 0x99, 0x01, false => (0x00, true))]
+  #[test_case(cpu::Variant::Ricoh2A03, 0x09, 0x01, false => (0x0A, false))]
+  fn add_with_carry_decimal(
+    variant: cpu::Variant,
+    accumulator: u8,
+    value: u8,
+    carry: bool,
+  ) -> (u8, bool) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.config.variant = variant;
+    cpu.register.status.decimal_mode = NumberMode::BinaryCodedDecimal;
+    cpu.register.accumulator = accumulator;
+    cpu.register.status.result_status.carry = carry;
+
+    cpu.add_with_carry(Value::Immediate(value)).unwrap();
+
+    (
+      cpu.register.accumulator,
+      cpu.register.status.result_status.carry,
+    )
+  }
+
+  #[test_case(cpu::Variant::Nmos6502, 0x46, 0x12, true => (0x34, true))]
+  #[test_case(cpu::Variant::Nmos6502, 0x40, 0x13, true => (0x27, true))]
+  #[test_case(cpu::Variant::Nmos6502, 0x32, 0x02, false => (0x29, true))]
+  #[test_case(cpu::Variant::Nmos6502, 0x00, 0x01, true => (0x99, false))]
+  #[test_case(cpu::Variant::Ricoh2A03, 0x10, 0x01, true => (0x0F, true))]
+  fn subtract_with_carry_decimal(
+    variant: cpu::Variant,
+    accumulator: u8,
+    value: u8,
+    carry: bool,
+  ) -> (u8, bool) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.config.variant = variant;
+    cpu.register.status.decimal_mode = NumberMode::BinaryCodedDecimal;
+    cpu.register.accumulator = accumulator;
+    cpu.register.status.result_status.carry = carry;
+
+    cpu.subtract_with_carry(Value::Immediate(value)).unwrap();
+
+    (
+      cpu.register.accumulator,
+      cpu.register.status.result_status.carry,
+    )
+  }
+
+  #[test_case(0x10, 0x10 => (true, false, true))]
+  #[test_case(0x10, 0x20 => (false, true, false))]
+  #[test_case(0x20, 0x10 => (false, false, true))]
+  #[test_case(0x00, 0x01 => (false, true, false))]
+  fn compare(register: u8, value: u8) -> (bool, bool, bool) {
+    let mut cpu = cpu::Cpu::default();
+
+    cpu.compare(register, Value::Immediate(value)).unwrap();
+
+    let status = &cpu.register.status.result_status;
+    (status.zero, status.negative, status.carry)
+  }
+
+  #[test_case(0b1000_0001, false => (0b0000_0010, true))]
+  #[test_case(0b0100_0000, true => (0b1000_0001, false))]
+  fn rotate_left(value: u8, carry: bool) -> (u8, bool) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.status.result_status.carry = carry;
+
+    let result = cpu.rotate_left(value);
+
+    (result, cpu.register.status.result_status.carry)
+  }
+
+  #[test_case(0b1000_0001, false => (0b0100_0000, true))]
+  #[test_case(0b0000_0010, true => (0b1000_0001, false))]
+  fn rotate_right(value: u8, carry: bool) -> (u8, bool) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.status.result_status.carry = carry;
+
+    let result = cpu.rotate_right(value);
+
+    (result, cpu.register.status.result_status.carry)
+  }
+
+  #[test_case(0x8010, 0x05, false => (0x8010, 0))]
+  #[test_case(0x8010, 0x05, true => (0x8015, 1))]
+  #[test_case(0x8010, 0xFB, true => (0x800B, 1))]
+  #[test_case(0x80FD, 0x05, true => (0x8102, 2))]
+  #[test_case(0x8000, 0xFE, true => (0x7FFE, 2))]
+  fn branch(program_counter: u16, offset: u8, condition: bool) -> (u16, u8) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.program_counter = program_counter;
+
+    let cycles = cpu
+      .branch(condition, Value::from(Location::Relative(offset)))
+      .unwrap();
+
+    (cpu.register.program_counter, cycles)
+  }
+
+  #[test_case(Location::XIndexedAbsolute(0x0200), 0x10 => 4)]
+  #[test_case(Location::XIndexedAbsolute(0x02F8), 0x10 => 5)]
+  #[test_case(Location::IndirectYIndexed(0x20), 0x01 => 5)]
+  #[test_case(Location::IndirectYIndexed(0x20), 0x10 => 6)]
+  fn page_crossing_penalty(location: Location, index: u8) -> u8 {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.index_x = index;
+    cpu.register.index_y = index;
+    cpu.memory.write_u16(0x20, 0x02F0).unwrap();
+
+    let cycles = cpu.execute(Operation::Lda(Value::from(location))).unwrap();
+
+    assert_eq!(u64::from(cycles), cpu.cycles);
+    cycles
+  }
+
+  #[test]
+  fn subroutine() {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.stack_pointer = 0xFD;
+    cpu.register.program_counter = 0x8003;
+
+    cpu
+      .execute(Operation::Jsr(Location::Absolute(0x9000)))
+      .unwrap();
+    assert_eq!(0x9000, cpu.register.program_counter);
+    assert_eq!(0xFB, cpu.register.stack_pointer);
+
+    cpu.execute(Operation::Rts).unwrap();
+    assert_eq!(0x8003, cpu.register.program_counter);
+    assert_eq!(0xFD, cpu.register.stack_pointer);
+  }
+
+  #[test]
+  fn stack() {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.stack_pointer = 0xFD;
+    cpu.register.accumulator = 0x42;
+    cpu.register.status.result_status.carry = true;
+
+    cpu.execute(Operation::Pha).unwrap();
+    cpu.execute(Operation::Php).unwrap();
+    assert_eq!(0x42, cpu.memory.read(0x01FD).unwrap());
+    assert_eq!(0b0011_0001, cpu.memory.read(0x01FC).unwrap());
+
+    cpu.execute(Operation::Lda(Value::Immediate(0))).unwrap();
+    cpu.execute(Operation::Plp).unwrap();
+    cpu.execute(Operation::Pla).unwrap();
+    assert_eq!(0x42, cpu.register.accumulator);
+    assert!(cpu.register.status.result_status.carry);
+    assert!(!cpu.register.status.result_status.zero);
+    assert_eq!(0xFD, cpu.register.stack_pointer);
+  }
+
+  #[test]
+  fn assembled_program() {
+    // Multiplies 6 by 7 with repeated addition
+    let program = assemble(
+      "
+              LDA #0
+              LDX #7
+        loop: CLC
+              ADC #6
+              DEX
+              BNE loop
+              STA $10
+              BRK
+      ",
+    )
+    .unwrap();
+    let mut cpu = cpu::Cpu::default();
+    cpu.config.halt_on_break = true;
+    cpu.load(&program.bytes);
+
+    cpu.start().unwrap();
+
+    assert_eq!(42, cpu.memory.read(0x0010).unwrap());
+    assert!(cpu.register.status.result_status.zero);
+  }
+
+  #[test_case(0x10, 0x11 => (0x0F, false, true))]
+  #[test_case(0x01, 0x00 => (0x00, true, true))]
+  fn decrement_compare(value: u8, accumulator: u8) -> (u8, bool, bool) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.memory.write(0x10, value).unwrap();
+    cpu.register.accumulator = accumulator;
+
+    cpu
+      .execute(Operation::Dcp(Location::ZeroPage(0x10)))
+      .unwrap();
+
+    let status = &cpu.register.status.result_status;
+    (cpu.memory.read(0x10).unwrap(), status.zero, status.carry)
+  }
+
+  #[test]
+  fn load_accumulator_and_x() {
+    let mut cpu = cpu::Cpu::default();
+    cpu.memory.write(0x10, 0x80).unwrap();
+
+    cpu
+      .execute(Operation::Lax(Value::from(Location::ZeroPage(0x10))))
+      .unwrap();
+
+    assert_eq!(0x80, cpu.register.accumulator);
+    assert_eq!(0x80, cpu.register.index_x);
+    assert!(cpu.register.status.result_status.negative);
+  }
+
+  #[test_case(0xFF, 0x0F, 0x05 => (0x0A, true))]
+  #[test_case(0x0F, 0xFF, 0x10 => (0xFF, false))]
+  fn and_x_subtract(accumulator: u8, index_x: u8, value: u8) -> (u8, bool) {
+    let mut cpu = cpu::Cpu::default();
+    cpu.register.accumulator = accumulator;
+    cpu.register.index_x = index_x;
+
+    cpu
+      .execute(Operation::Axs(Value::Immediate(value)))
+      .unwrap();
+
+    (
+      cpu.register.index_x,
+      cpu.register.status.result_status.carry,
+    )
+  }
+
+  #[test]
+  fn increment_wraps() {
+    let mut cpu = cpu::Cpu::default();
+    cpu.memory.write(0x10, 0xFF).unwrap();
+
+    cpu
+      .execute(Operation::Inc(Location::ZeroPage(0x10)))
+      .unwrap();
+
+    assert_eq!(0, cpu.memory.read(0x10).unwrap());
+    assert!(cpu.register.status.result_status.zero);
+  }
 }
@@ -0,0 +1,170 @@
+use super::error::Error;
+use crate::memory::{self, constant, Bus};
+
+/// Cycles taken to push state and jump to an interrupt handler
+pub const INTERRUPT_CYCLES: u8 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+  /// Non-maskable interrupt (NMI), raised on an edge of the NMI line
+  NonMaskable,
+  /// Maskable interrupt (IRQ), raised while the IRQ line is asserted and interrupts are not disabled
+  Maskable,
+  /// Software interrupt raised by the BRK instruction, which shares the IRQ handler
+  Break,
+}
+
+impl Interrupt {
+  /// Address containing the address of the interrupt handler
+  #[must_use]
+  pub fn vector(self) -> memory::Address {
+    match self {
+      Self::NonMaskable => constant::NON_MASKABLE_INTERRUPT,
+      Self::Maskable | Self::Break => constant::INTERRUPT,
+    }
+  }
+}
+
+/// State of the interrupt request lines into the CPU
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default)]
+pub struct Lines {
+  nmi: bool,
+  /// Level of the NMI line driven by devices on the bus, as of the last poll
+  bus_nmi: bool,
+  nmi_pending: bool,
+  irq: bool,
+}
+
+impl<B: Bus> super::Nes<B> {
+  /// Sets the level of the NMI line
+  ///
+  /// An NMI is serviced once for each change from unasserted to asserted, however long the line is held.
+  pub fn set_nmi(&mut self, asserted: bool) {
+    if asserted && !self.interrupts.nmi {
+      self.interrupts.nmi_pending = true;
+    }
+    self.interrupts.nmi = asserted;
+  }
+
+  /// Sets the level of the IRQ line
+  ///
+  /// An IRQ is serviced before each instruction for as long as the line is asserted, unless interrupts are disabled.
+  /// Devices on the bus can also assert the line, through [`Bus::irq`].
+  pub fn set_irq(&mut self, asserted: bool) {
+    self.interrupts.irq = asserted;
+  }
+
+  /// Services any pending interrupt, returning the number of cycles taken
+  ///
+  /// # Errors
+  /// Forwards any error from accessing the stack or interrupt vector
+  pub fn poll_interrupts(&mut self) -> Result<u8, Error> {
+    let bus_nmi = self.memory.nmi();
+    if bus_nmi && !self.interrupts.bus_nmi {
+      self.interrupts.nmi_pending = true;
+    }
+    self.interrupts.bus_nmi = bus_nmi;
+
+    let interrupt = if self.interrupts.nmi_pending {
+      self.interrupts.nmi_pending = false;
+      Interrupt::NonMaskable
+    } else if (self.interrupts.irq || self.memory.irq())
+      && !self.register.status.interrupt_status.disabled
+    {
+      Interrupt::Maskable
+    } else {
+      return Ok(0);
+    };
+
+    self.interrupt(interrupt)?;
+    self.cycles += u64::from(INTERRUPT_CYCLES);
+    Ok(INTERRUPT_CYCLES)
+  }
+
+  /// Pushes the program counter and processor status, then jumps to the interrupt handler
+  ///
+  /// # Errors
+  /// Forwards any error from accessing the stack or interrupt vector
+  pub fn interrupt(&mut self, interrupt: Interrupt) -> Result<(), Error> {
+    let status = self
+      .register
+      .status
+      .to_stack_byte(interrupt == Interrupt::Break);
+
+    self.push_u16(self.register.program_counter)?;
+    self.push(status)?;
+    self.register.status.interrupt_status.disabled = true;
+    self.register.program_counter = self.memory.read_u16(interrupt.vector())?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    cpu::{stack::STACK_POINTER_RESET, Cpu},
+    memory::{constant, Bus},
+  };
+
+  fn cpu() -> Cpu {
+    // ROM is read-only, so the vectors are set in the program image
+    let mut program = vec![0; constant::PROGRAM_ROM_SIZE as usize];
+    let vector = |address| usize::from(address - constant::PROGRAM_ROM_START);
+    program[vector(constant::NON_MASKABLE_INTERRUPT)..][..2].copy_from_slice(&[0x00, 0x90]);
+    program[vector(constant::INTERRUPT)..][..2].copy_from_slice(&[0x00, 0xA0]);
+    let mut cpu = Cpu::default();
+    cpu.load(&program);
+    cpu.reset().unwrap();
+    cpu.register.program_counter = 0x8123;
+    cpu
+  }
+
+  #[test]
+  fn non_maskable_is_edge_triggered() {
+    let mut cpu = cpu();
+    cpu.set_nmi(true);
+
+    assert_eq!(7, cpu.poll_interrupts().unwrap());
+    assert_eq!(0x9000, cpu.register.program_counter);
+    assert_eq!(0x8123, cpu.memory.read_u16(0x01FC).unwrap());
+    assert_eq!(0b0010_0100, cpu.memory.read(0x01FB).unwrap());
+
+    cpu.set_nmi(true);
+    assert_eq!(0, cpu.poll_interrupts().unwrap());
+  }
+
+  #[test]
+  fn maskable_is_level_triggered() {
+    let mut cpu = cpu();
+    cpu.set_irq(true);
+
+    assert_eq!(0, cpu.poll_interrupts().unwrap());
+
+    cpu.register.status.interrupt_status.disabled = false;
+    assert_eq!(7, cpu.poll_interrupts().unwrap());
+    assert_eq!(0xA000, cpu.register.program_counter);
+    assert!(cpu.register.status.interrupt_status.disabled);
+
+    cpu.register.status.interrupt_status.disabled = false;
+    assert_eq!(7, cpu.poll_interrupts().unwrap());
+
+    cpu.set_irq(false);
+    cpu.register.status.interrupt_status.disabled = false;
+    assert_eq!(0, cpu.poll_interrupts().unwrap());
+    assert_eq!(STACK_POINTER_RESET - 6, cpu.register.stack_pointer);
+  }
+
+  #[test]
+  fn break_sets_break_flag() {
+    let mut cpu = cpu();
+    cpu.memory.write(0x0200, 0x00).unwrap();
+    cpu.register.program_counter = 0x0200;
+
+    assert_eq!(7, cpu.step().unwrap());
+    assert_eq!(0xA000, cpu.register.program_counter);
+    // BRK skips a padding byte, so returns to two bytes after the opcode
+    assert_eq!(0x0202, cpu.memory.read_u16(0x01FC).unwrap());
+    assert_eq!(0b0011_0100, cpu.memory.read(0x01FB).unwrap());
+  }
+}
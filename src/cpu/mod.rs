@@ -2,24 +2,71 @@
 
 pub mod error;
 pub mod exec;
+pub mod interrupt;
 pub mod operation;
 pub mod registers;
+pub mod stack;
 
 use std::io::Read;
 
+use strum::EnumString;
 use tracing::info;
 
-use crate::memory;
+use crate::{
+  cartridge::{
+    self,
+    mapper::{nrom::Nrom, Board},
+    Cartridge,
+  },
+  input,
+  memory::{self, Bus},
+};
 use operation::Operation;
 
 pub type Cpu = Nes;
 pub type Int = u8;
 
+/// A 6502 core connected to a memory bus, which defaults to the NES address space
 #[derive(Debug, Default)]
-pub struct Nes {
+pub struct Nes<B: Bus = memory::Nes> {
   pub register: registers::Nes,
-  pub memory: memory::Nes,
-  stop: bool,
+  pub memory: B,
+  pub config: Config,
+  /// Total number of cycles executed
+  pub cycles: u64,
+  interrupts: interrupt::Lines,
+  halted: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+  /// Whether unofficial opcodes should be rejected as illegal rather than executed
+  pub strict: bool,
+  /// Whether BRK should halt the CPU rather than raise an interrupt
+  ///
+  /// This is useful for running small programs which do not set up an interrupt handler.
+  pub halt_on_break: bool,
+  pub variant: Variant,
+}
+
+/// Which 6502 implementation to emulate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString)]
+pub enum Variant {
+  /// The Ricoh 2A03 used in the NES, which lacks decimal mode
+  #[default]
+  #[strum(serialize = "2a03")]
+  Ricoh2A03,
+  /// The original NMOS 6502, with binary-coded decimal arithmetic
+  #[strum(serialize = "6502")]
+  Nmos6502,
+}
+
+impl Variant {
+  /// Whether ADC and SBC honour the decimal mode flag
+  #[must_use]
+  pub fn has_decimal_mode(self) -> bool {
+    self == Self::Nmos6502
+  }
 }
 
 impl Nes {
@@ -28,70 +75,176 @@ impl Nes {
   /// # Errors
   /// Forwards any errors encountered while reading the file
   pub fn load_from(&mut self, from: &mut dyn Read) -> anyhow::Result<usize> {
-    let result = from.read(&mut self.memory.program_rom)?;
-    self
-      .memory
-      .write_u16(memory::constant::PROGRAM_COUNTER_RESET, 0x8000);
+    let mut program_rom = vec![0; memory::constant::PROGRAM_ROM_SIZE as usize];
+    let result = from.read(&mut program_rom)?;
+    self.load_program_rom(program_rom);
 
     Ok(result)
   }
 
   pub fn load(&mut self, program: &[Int]) {
-    self.memory.program_rom[..program.len()].copy_from_slice(program);
-    self
-      .memory
-      .write_u16(memory::constant::PROGRAM_COUNTER_RESET, 0x8000);
+    let mut program_rom = vec![0; memory::constant::PROGRAM_ROM_SIZE as usize];
+    program_rom[..program.len()].copy_from_slice(program);
+    self.load_program_rom(program_rom);
   }
 
-  pub fn reset(&mut self) {
+  /// Maps a cartridge's memory into the address space through the mapper for its board
+  ///
+  /// Any trainer is loaded into program RAM, and the reset vector is taken from the ROM itself.
+  ///
+  /// # Errors
+  /// Returns [`cartridge::Error::UnsupportedMapper`] if the cartridge's board is not emulated
+  pub fn load_cartridge(&mut self, cartridge: &Cartridge) -> Result<(), cartridge::Error> {
+    self.memory.mapper = cartridge::mapper::from_cartridge(cartridge)?;
+    Ok(())
+  }
+
+  /// Plugs a device into a controller port, 0 or 1, or empties the port
+  ///
+  /// # Panics
+  /// Panics if the port is not 0 or 1
+  pub fn connect(&mut self, port: usize, device: Option<Box<dyn input::Device>>) {
+    self.memory.ports[port] = device;
+  }
+
+  /// Updates the device in a controller port, 0 or 1, which holds its state until it is next set
+  ///
+  /// # Panics
+  /// Panics if the port is not 0 or 1
+  pub fn set_input(&mut self, port: usize, input: input::Input) {
+    if let Some(device) = &mut self.memory.ports[port] {
+      device.set_input(input);
+    }
+  }
+
+  /// Sets the buttons held down on a player's controller, which stay held until they are next set
+  ///
+  /// Players 0 and 1 are plugged into the first and second ports, and players 2 and 3 into the second socket
+  /// of a Four Score on each port.
+  ///
+  /// # Panics
+  /// Panics if the player is not 0-3
+  pub fn set_buttons(&mut self, player: usize, buttons: input::Buttons) {
+    assert!(player < 4, "there are at most four players");
+    self.set_input(
+      player % 2,
+      input::Input::Buttons {
+        socket: player / 2,
+        buttons,
+      },
+    );
+  }
+
+  /// Maps a raw program image into ROM on an NROM board, with the reset vector pointing to the start of ROM
+  fn load_program_rom(&mut self, mut program_rom: Vec<Int>) {
+    use memory::constant::{PROGRAM_COUNTER_RESET, PROGRAM_ROM_START};
+    let offset = usize::from(PROGRAM_COUNTER_RESET - PROGRAM_ROM_START);
+    program_rom[offset..offset + 2].copy_from_slice(&PROGRAM_ROM_START.to_le_bytes());
+    self.memory.mapper = Box::new(Nrom::new(Board::with_program_rom(program_rom)));
+  }
+}
+
+impl<B: Bus> Nes<B> {
+  /// Resets the registers and jumps to the address in the reset vector, with interrupts disabled
+  ///
+  /// # Errors
+  /// Returns an [`error::Error`] if the reset vector cannot be read
+  pub fn reset(&mut self) -> Result<(), error::Error> {
     self.register = registers::Nes::default();
+    self.register.stack_pointer = stack::STACK_POINTER_RESET;
+    self.register.status.interrupt_status.disabled = true;
     self.register.program_counter = self
       .memory
-      .read_u16(memory::constant::PROGRAM_COUNTER_RESET);
+      .read_u16(memory::constant::PROGRAM_COUNTER_RESET)?;
+    self.interrupts = interrupt::Lines::default();
+    self.halted = false;
+    self.cycles += u64::from(interrupt::INTERRUPT_CYCLES);
+    self.memory.tick(interrupt::INTERRUPT_CYCLES);
+    Ok(())
+  }
+
+  /// Stops execution after the current instruction
+  pub fn halt(&mut self) {
+    self.halted = true;
+  }
+
+  #[must_use]
+  pub fn is_halted(&self) -> bool {
+    self.halted
   }
 
   /// # Errors
   /// See [`Cpu::resume`]
-  pub fn start(&mut self) -> anyhow::Result<()> {
-    self.reset();
+  pub fn start(&mut self) -> Result<(), error::Error> {
+    self.reset()?;
     self.resume()
   }
 
   /// # Errors
   /// See [`Cpu::resume`]
-  pub fn start_from(&mut self, instructions: memory::Address) -> anyhow::Result<()> {
-    self.reset();
+  pub fn start_from(&mut self, instructions: memory::Address) -> Result<(), error::Error> {
+    self.reset()?;
     self.register.program_counter = instructions;
     self.resume()
   }
 
+  /// Runs until the CPU is halted
+  ///
   /// # Errors
-  /// Returns any [`error::Error`] that occurs during execution
-  pub fn resume(&mut self) -> anyhow::Result<()> {
+  /// Returns any [`error::Error`] that occurs during decoding or execution
+  pub fn resume(&mut self) -> Result<(), error::Error> {
     info!("starting from address {:#X}", self.register.program_counter);
-    loop {
-      let operation = Operation::next(self);
-      self.execute(operation)?;
-      self.execute(operation)?;
-      if self.stop {
-        break;
-      }
+    self.halted = false;
+    while !self.halted {
+      self.step()?;
     }
     info!("execution stopped");
 
     Ok(())
   }
 
-  fn next_int(&mut self) -> Int {
-    let result = self.memory.read(self.register.program_counter);
-    self.register.program_counter += 1;
-    result
+  /// Services a pending interrupt, or otherwise decodes and executes the next instruction,
+  /// returning the number of cycles taken
+  ///
+  /// The rest of the bus is then run for the same number of cycles, plus any cycles the CPU was stalled for.
+  ///
+  /// # Errors
+  /// Returns any [`error::Error`] that occurs during decoding or execution
+  pub fn step(&mut self) -> Result<u16, error::Error> {
+    self.step_with(|_| Ok(()))
+  }
+
+  /// Like [`Cpu::step`], but calls `before` just before an instruction is decoded, which is skipped when an
+  /// interrupt is serviced instead
+  ///
+  /// # Errors
+  /// Returns any [`error::Error`] that occurs during decoding or execution, or from `before`
+  pub fn step_with(
+    &mut self,
+    before: impl FnOnce(&Self) -> Result<(), error::Error>,
+  ) -> Result<u16, error::Error> {
+    let mut cycles = self.poll_interrupts()?;
+    if cycles == 0 {
+      before(self)?;
+      let operation = Operation::next(self)?;
+      cycles = self.execute(operation)?;
+    }
+
+    let stall = self.memory.tick(cycles);
+    self.cycles += u64::from(stall);
+    Ok(u16::from(cycles) + stall)
   }
 
-  fn next_address(&mut self) -> memory::Address {
-    let result = self.memory.read_u16(self.register.program_counter);
-    self.register.program_counter += 2;
-    result
+  fn next_int(&mut self) -> Result<Int, error::Error> {
+    let result = self.memory.read(self.register.program_counter)?;
+    self.register.program_counter = self.register.program_counter.wrapping_add(1);
+    Ok(result)
+  }
+
+  fn next_address(&mut self) -> Result<memory::Address, error::Error> {
+    let result = self.memory.read_u16(self.register.program_counter)?;
+    self.register.program_counter = self.register.program_counter.wrapping_add(2);
+    Ok(result)
   }
 }
 
@@ -128,4 +281,83 @@ mod tests {
 
     assert_eq!(rom_size, cpu.load_from(&mut values.as_slice()).unwrap());
   }
+
+  #[test]
+  fn load_cartridge_mirrors_bank() {
+    let mut bytes = vec![0; cartridge::HEADER_SIZE + cartridge::PROGRAM_ROM_BANK_SIZE];
+    bytes[..8].copy_from_slice(b"NES\x1A\x01\x00\x00\x00");
+    // Reset vector at the end of the bank
+    bytes[cartridge::HEADER_SIZE + 0x3FFC] = 0x34;
+    bytes[cartridge::HEADER_SIZE + 0x3FFD] = 0xC0;
+    let cartridge = Cartridge::from_bytes(&bytes).unwrap();
+    let mut cpu = Cpu::default();
+
+    cpu.load_cartridge(&cartridge).unwrap();
+    cpu.reset().unwrap();
+
+    assert_eq!(0xC034, cpu.register.program_counter);
+    assert_eq!(0x34, cpu.memory.read(0xBFFC).unwrap());
+  }
+
+  #[test]
+  fn reset() {
+    let mut cpu = Cpu::default();
+    cpu.load(&[]);
+
+    cpu.reset().unwrap();
+
+    assert_eq!(
+      memory::constant::PROGRAM_ROM_START,
+      cpu.register.program_counter
+    );
+    assert_eq!(stack::STACK_POINTER_RESET, cpu.register.stack_pointer);
+  }
+
+  #[test]
+  fn set_buttons_for_third_player() {
+    let mut cpu = Cpu::default();
+    cpu.connect(0, input::Kind::FourScore.device(0));
+
+    cpu.set_buttons(2, input::Buttons::from(input::Button::A));
+    cpu.memory.write(0x4016, 0x01).unwrap();
+    cpu.memory.write(0x4016, 0x00).unwrap();
+
+    let reads: Vec<Int> = (0..9).map(|_| cpu.memory.read(0x4016).unwrap()).collect();
+    assert_eq!(
+      vec![0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x41],
+      reads
+    );
+  }
+
+  #[test]
+  fn flat_bus() {
+    let mut cpu = Nes::<memory::bus::Flat>::default();
+    // LDA #$42; STA $2000; BRK
+    cpu.memory.memory[0x0200..0x0206].copy_from_slice(&[0xA9, 0x42, 0x8D, 0x00, 0x20, 0x00]);
+    cpu
+      .memory
+      .write_u16(memory::constant::PROGRAM_COUNTER_RESET, 0x0200)
+      .unwrap();
+    cpu.config.halt_on_break = true;
+
+    cpu.start().unwrap();
+
+    assert_eq!(0x42, cpu.memory.peek(0x2000).unwrap());
+  }
+
+  #[test]
+  fn resume_illegal_opcode() {
+    let mut cpu = Cpu::default();
+    cpu.load(&[0xEA, 0x02]);
+
+    let result = cpu.start();
+
+    assert!(matches!(
+      result,
+      Err(error::Error::IllegalOpcode {
+        address: 0x8001,
+        opcode: 0x02,
+      })
+    ));
+  }
 }
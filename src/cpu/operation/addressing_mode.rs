@@ -1,4 +1,7 @@
-use crate::{cpu, memory};
+use crate::{
+  cpu::{self, error::Error},
+  memory::{self, Bus},
+};
 
 #[derive(Clone, Copy, Debug)]
 pub enum Value {
@@ -7,19 +10,42 @@ pub enum Value {
   Location(Location),
 }
 impl Value {
-  #[must_use]
-  pub fn value(self, cpu: &cpu::Cpu) -> cpu::Int {
+  /// # Errors
+  /// Forwards any error from reading memory
+  pub fn value<B: Bus>(self, cpu: &mut cpu::Nes<B>) -> Result<cpu::Int, Error> {
     use Value::*;
     match self {
-      Immediate(value) => value,
-      Location(at) => cpu.memory.read(at.location(cpu)),
+      Immediate(value) => Ok(value),
+      Location(at) => {
+        let address = at.location(cpu)?;
+        cpu.memory.read(address)
+      }
+    }
+  }
+}
+
+impl Value {
+  /// The operand which follows the opcode
+  #[must_use]
+  pub fn operand(self) -> u16 {
+    match self {
+      Value::Immediate(value) => value.into(),
+      Value::Location(location) => location.operand(),
+    }
+  }
+
+  #[must_use]
+  pub fn mode(self) -> Mode {
+    match self {
+      Value::Immediate(_) => Mode::Immediate,
+      Value::Location(location) => location.mode(),
     }
   }
 }
 
 impl From<cpu::Int> for Value {
   fn from(value: cpu::Int) -> Self {
-      Value::Immediate(value)
+    Value::Immediate(value)
   }
 }
 
@@ -44,28 +70,166 @@ pub enum Location {
 }
 
 impl Location {
+  /// Builds a location from its addressing mode and the operand which follows the opcode
+  ///
+  /// Single byte operands are taken from the low byte. Returns `None` for modes which do not address memory.
+  #[must_use]
+  pub fn from_operand(mode: Mode, operand: u16) -> Option<Self> {
+    use Location::*;
+    let [low, _] = operand.to_le_bytes();
+    Some(match mode {
+      Mode::Implied | Mode::Accumulator | Mode::Immediate => return None,
+      Mode::ZeroPage => ZeroPage(low),
+      Mode::Absolute => Absolute(operand),
+      Mode::XIndexedZeroPage => XIndexedZeroPage(low),
+      Mode::YIndexedZeroPage => YIndexedZeroPage(low),
+      Mode::XIndexedAbsolute => XIndexedAbsolute(operand),
+      Mode::YIndexedAbsolute => YIndexedAbsolute(operand),
+      Mode::Relative => Relative(low),
+      Mode::Indirect => Indirect(operand),
+      Mode::XIndexedIndirect => XIndexedIndirect(low),
+      Mode::IndirectYIndexed => IndirectYIndexed(low),
+    })
+  }
+
+  /// The operand which follows the opcode
   #[must_use]
-  pub fn location(self, cpu: &cpu::Cpu) -> memory::Address {
+  pub fn operand(self) -> u16 {
+    use Location::*;
+    match self {
+      ZeroPage(addr)
+      | XIndexedZeroPage(addr)
+      | YIndexedZeroPage(addr)
+      | Relative(addr)
+      | XIndexedIndirect(addr)
+      | IndirectYIndexed(addr) => addr.into(),
+      Absolute(addr) | XIndexedAbsolute(addr) | YIndexedAbsolute(addr) | Indirect(addr) => addr,
+    }
+  }
+
+  /// # Errors
+  /// Forwards any error from reading a pointer from memory
+  pub fn location<B: Bus>(self, cpu: &mut cpu::Nes<B>) -> Result<memory::Address, Error> {
     use cpu::Int;
     use memory::Address;
     use Location::*;
-    match self {
+    Ok(match self {
       ZeroPage(addr) => Address::from(addr),
       Absolute(addr) => addr,
       XIndexedZeroPage(addr) => Address::from(Int::wrapping_add(addr, cpu.register.index_x)),
       YIndexedZeroPage(addr) => Address::from(Int::wrapping_add(addr, cpu.register.index_y)),
-      XIndexedAbsolute(addr) => addr + Address::from(cpu.register.index_x),
-      YIndexedAbsolute(addr) => addr + Address::from(cpu.register.index_y),
-      Relative(addr) => Address::wrapping_add(Address::from(addr), cpu.register.program_counter),
-      Indirect(addr) => cpu.memory.read_u16(addr),
+      XIndexedAbsolute(addr) => Address::wrapping_add(addr, Address::from(cpu.register.index_x)),
+      YIndexedAbsolute(addr) => Address::wrapping_add(addr, Address::from(cpu.register.index_y)),
+      // Offset is a signed byte relative to the address of the next instruction
+      Relative(offset) => Address::wrapping_add_signed(
+        cpu.register.program_counter,
+        i16::from(Int::cast_signed(offset)),
+      ),
+      // The 6502 does not carry into the high byte when fetching the pointer, so a pointer at $xxFF wraps within its page
+      Indirect(addr) => {
+        let [low, high] = addr.to_le_bytes();
+        let high_addr = Address::from_le_bytes([low.wrapping_add(1), high]);
+        Address::from_le_bytes([cpu.memory.read(addr)?, cpu.memory.read(high_addr)?])
+      }
       XIndexedIndirect(addr) => {
-        let addr = Int::wrapping_add(addr, cpu.register.index_x);
-        cpu.memory.read_u16(Address::from(addr))
+        let pointer = Int::wrapping_add(addr, cpu.register.index_x);
+        read_zero_page_u16(&mut cpu.memory, pointer)?
       }
       IndirectYIndexed(addr) => {
-        let addr = cpu.memory.read_u16(Address::from(addr));
+        let addr = read_zero_page_u16(&mut cpu.memory, addr)?;
         Address::wrapping_add(addr, Address::from(cpu.register.index_y))
       }
+    })
+  }
+
+  #[must_use]
+  pub fn mode(self) -> Mode {
+    use Location::*;
+    match self {
+      ZeroPage(_) => Mode::ZeroPage,
+      Absolute(_) => Mode::Absolute,
+      XIndexedZeroPage(_) => Mode::XIndexedZeroPage,
+      YIndexedZeroPage(_) => Mode::YIndexedZeroPage,
+      XIndexedAbsolute(_) => Mode::XIndexedAbsolute,
+      YIndexedAbsolute(_) => Mode::YIndexedAbsolute,
+      Relative(_) => Mode::Relative,
+      Indirect(_) => Mode::Indirect,
+      XIndexedIndirect(_) => Mode::XIndexedIndirect,
+      IndirectYIndexed(_) => Mode::IndirectYIndexed,
+    }
+  }
+
+  /// Whether indexing moves the resolved address onto a different page than the unindexed address
+  ///
+  /// Pointers are peeked, so this has no side effects on the bus.
+  ///
+  /// # Errors
+  /// Forwards any error from reading a pointer from memory
+  pub fn page_crossed<B: Bus>(self, cpu: &cpu::Nes<B>) -> Result<bool, Error> {
+    use memory::Address;
+    use Location::*;
+    let (base, index) = match self {
+      XIndexedAbsolute(addr) => (addr, cpu.register.index_x),
+      YIndexedAbsolute(addr) => (addr, cpu.register.index_y),
+      IndirectYIndexed(addr) => (peek_zero_page_u16(&cpu.memory, addr)?, cpu.register.index_y),
+      _ => return Ok(false),
+    };
+    Ok(!same_page(base, base.wrapping_add(Address::from(index))))
+  }
+}
+
+/// An addressing mode, without its operand
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+  /// No operand, or one implied by the operation
+  Implied,
+  /// Operates on the accumulator
+  Accumulator,
+  Immediate,
+  ZeroPage,
+  Absolute,
+  XIndexedZeroPage,
+  YIndexedZeroPage,
+  XIndexedAbsolute,
+  YIndexedAbsolute,
+  Relative,
+  Indirect,
+  XIndexedIndirect,
+  IndirectYIndexed,
+}
+
+impl Mode {
+  /// Number of bytes which follow the opcode
+  #[must_use]
+  pub fn operand_length(self) -> u16 {
+    use Mode::*;
+    match self {
+      Implied | Accumulator => 0,
+      Immediate | ZeroPage | XIndexedZeroPage | YIndexedZeroPage | Relative | XIndexedIndirect
+      | IndirectYIndexed => 1,
+      Absolute | XIndexedAbsolute | YIndexedAbsolute | Indirect => 2,
     }
   }
 }
+
+/// Whether both addresses are in the same 256-byte page
+#[must_use]
+pub fn same_page(first: memory::Address, second: memory::Address) -> bool {
+  first & 0xFF00 == second & 0xFF00
+}
+
+/// Reads a pointer from the zero page, wrapping within the zero page
+fn read_zero_page_u16(bus: &mut impl Bus, addr: cpu::Int) -> Result<memory::Address, Error> {
+  Ok(memory::Address::from_le_bytes([
+    bus.read(memory::Address::from(addr))?,
+    bus.read(memory::Address::from(addr.wrapping_add(1)))?,
+  ]))
+}
+
+/// Peeks a pointer from the zero page, wrapping within the zero page
+fn peek_zero_page_u16(bus: &impl Bus, addr: cpu::Int) -> Result<memory::Address, Error> {
+  Ok(memory::Address::from_le_bytes([
+    bus.peek(memory::Address::from(addr))?,
+    bus.peek(memory::Address::from(addr.wrapping_add(1)))?,
+  ]))
+}
@@ -0,0 +1,670 @@
+//! Assembling 6502 source into machine code
+//!
+//! Instructions are written in the syntax produced by [`super::disassemble`], one statement per line:
+//!
+//! - `label:` defines a label at the current address, and may be followed by a statement on the same line
+//! - `name = expression` defines a constant
+//! - `.org expression` sets the current address, padding any gap with zeros
+//! - `.byte` and `.word` output comma-separated expressions as bytes or little-endian words
+//! - Anything after a `;` is a comment
+//!
+//! Expressions are evaluated by evalexpr with labels and constants as variables, `$` marking hexadecimal numbers,
+//! `*` standing for the current address wherever a value is expected, and `<` or `>` in front of an expression to
+//! take its low or high byte.
+//! See <https://www.nesdev.org/wiki/Assembler>.
+
+use std::collections::HashMap;
+
+use evalexpr::{Context, ContextWithMutableVariables, IntType};
+use thiserror::Error;
+
+use super::{addressing_mode::Mode, opcode::OPCODES};
+use crate::{cpu, memory};
+
+#[derive(Error, Debug)]
+#[error("line {line}: {kind}")]
+pub struct Error {
+  pub line: usize,
+  pub kind: ErrorKind,
+}
+
+#[derive(Error, Debug)]
+pub enum ErrorKind {
+  #[error("cannot parse {0:?}")]
+  Syntax(String),
+  #[error("unknown mnemonic {0}")]
+  UnknownMnemonic(String),
+  #[error("unknown directive {0}")]
+  UnknownDirective(String),
+  #[error("{mnemonic} has no form for this operand")]
+  UnsupportedOperand { mnemonic: String },
+  #[error("{0} is already defined")]
+  Redefined(String),
+  #[error("{value:#X} does not fit in {bytes} byte(s)")]
+  OutOfRange { value: IntType, bytes: u16 },
+  #[error("branch target is {0} bytes away, which is further than a signed byte can reach")]
+  BranchOutOfRange(IntType),
+  #[error("address {address:#X} is before the current address {current:#X}")]
+  OriginBackwards { address: IntType, current: IntType },
+  #[error("program runs past the end of the address space")]
+  AddressOverflow,
+  #[error(transparent)]
+  Expression(#[from] evalexpr::EvalexprError),
+}
+
+/// Machine code along with the address it starts at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+  pub origin: memory::Address,
+  pub bytes: Vec<cpu::Int>,
+}
+
+impl Program {
+  /// The program as a ROM image, padded from the start of ROM up to its origin, as taken by [`cpu::Nes::load`]
+  ///
+  /// Returns `None` if the program starts before ROM.
+  #[must_use]
+  pub fn rom(&self) -> Option<Vec<cpu::Int>> {
+    let padding = self
+      .origin
+      .checked_sub(memory::constant::PROGRAM_ROM_START)?;
+    let mut rom = vec![0; usize::from(padding)];
+    rom.extend_from_slice(&self.bytes);
+    Some(rom)
+  }
+}
+
+/// Assembles source text, starting at the start of ROM unless it begins with `.org`
+///
+/// # Errors
+/// Returns an [`Error`] giving the line of the first statement which cannot be assembled
+pub fn assemble(source: &str) -> Result<Program, Error> {
+  let lines = source
+    .lines()
+    .zip(1..)
+    .map(|(text, number)| {
+      let (label, statement) = parse_line(text).map_err(|kind| Error { line: number, kind })?;
+      Ok(SourceLine {
+        number,
+        label,
+        statement,
+      })
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let mut assembler = Assembler::new();
+  for pass in [Pass::Size, Pass::Output] {
+    assembler.start(pass);
+    for line in &lines {
+      assembler.line(line).map_err(|kind| Error {
+        line: line.number,
+        kind,
+      })?;
+    }
+  }
+  Ok(Program {
+    origin: assembler
+      .origin
+      .unwrap_or(memory::constant::PROGRAM_ROM_START),
+    bytes: assembler.bytes,
+  })
+}
+
+/// An operand as written, before the size of its value picks between zero page and absolute addressing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Shape {
+  None,
+  Accumulator,
+  Immediate,
+  Direct,
+  XIndexed,
+  YIndexed,
+  Indirect,
+  XIndexedIndirect,
+  IndirectYIndexed,
+}
+
+impl Shape {
+  /// Modes the operand could be written for, in order of preference
+  fn modes(self) -> &'static [Mode] {
+    use Mode::*;
+    match self {
+      Shape::None => &[Implied, Accumulator],
+      Shape::Accumulator => &[Accumulator],
+      Shape::Immediate => &[Immediate],
+      Shape::Direct => &[Relative, ZeroPage, Absolute],
+      Shape::XIndexed => &[XIndexedZeroPage, XIndexedAbsolute],
+      Shape::YIndexed => &[YIndexedZeroPage, YIndexedAbsolute],
+      Shape::Indirect => &[Indirect],
+      Shape::XIndexedIndirect => &[XIndexedIndirect],
+      Shape::IndirectYIndexed => &[IndirectYIndexed],
+    }
+  }
+}
+
+#[derive(Debug)]
+enum Statement {
+  Constant(String, String),
+  Org(String),
+  Data {
+    width: u16,
+    expressions: Vec<String>,
+  },
+  Instruction {
+    mnemonic: String,
+    shape: Shape,
+    expression: Option<String>,
+  },
+}
+
+#[derive(Debug)]
+struct SourceLine {
+  number: usize,
+  label: Option<String>,
+  statement: Option<Statement>,
+}
+
+fn parse_line(text: &str) -> Result<(Option<String>, Option<Statement>), ErrorKind> {
+  let text = text.split(';').next().unwrap_or_default().trim();
+  let (label, text) = match text.split_once(':') {
+    Some((label, rest)) if is_identifier(label.trim()) => {
+      (Some(label.trim().to_owned()), rest.trim())
+    }
+    _ => (None, text),
+  };
+  if text.is_empty() {
+    return Ok((label, None));
+  }
+
+  let statement = if let Some((name, expression)) = text
+    .split_once('=')
+    .filter(|(name, _)| is_identifier(name.trim()))
+  {
+    Statement::Constant(name.trim().to_owned(), without_whitespace(expression))
+  } else if let Some(directive) = text.strip_prefix('.') {
+    let (name, arguments) = directive
+      .split_once(char::is_whitespace)
+      .unwrap_or((directive, ""));
+    let expressions = arguments.split(',').map(without_whitespace).collect();
+    match name.to_ascii_lowercase().as_str() {
+      "org" => Statement::Org(without_whitespace(arguments)),
+      "byte" => Statement::Data {
+        width: 1,
+        expressions,
+      },
+      "word" => Statement::Data {
+        width: 2,
+        expressions,
+      },
+      _ => return Err(ErrorKind::UnknownDirective(name.to_owned())),
+    }
+  } else {
+    let (mnemonic, operand) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let operand = without_whitespace(operand);
+    let (shape, expression) = parse_operand(&operand);
+    Statement::Instruction {
+      mnemonic: mnemonic.to_ascii_uppercase(),
+      shape,
+      expression: expression.map(str::to_owned),
+    }
+  };
+  Ok((label, Some(statement)))
+}
+
+fn parse_operand(operand: &str) -> (Shape, Option<&str>) {
+  if operand.is_empty() {
+    return (Shape::None, None);
+  }
+  if operand.eq_ignore_ascii_case("A") {
+    return (Shape::Accumulator, None);
+  }
+  if let Some(value) = operand.strip_prefix('#') {
+    return (Shape::Immediate, Some(value));
+  }
+  if let Some(inner) = operand.strip_prefix('(') {
+    if let Some(pointer) = strip_suffix_ignore_case(inner, ",X)") {
+      return (Shape::XIndexedIndirect, Some(pointer));
+    }
+    if let Some(pointer) = strip_suffix_ignore_case(inner, "),Y") {
+      return (Shape::IndirectYIndexed, Some(pointer));
+    }
+    if let Some(pointer) = inner.strip_suffix(')') {
+      return (Shape::Indirect, Some(pointer));
+    }
+  }
+  if let Some(address) = strip_suffix_ignore_case(operand, ",X") {
+    return (Shape::XIndexed, Some(address));
+  }
+  if let Some(address) = strip_suffix_ignore_case(operand, ",Y") {
+    return (Shape::YIndexed, Some(address));
+  }
+  (Shape::Direct, Some(operand))
+}
+
+fn strip_suffix_ignore_case<'a>(text: &'a str, suffix: &str) -> Option<&'a str> {
+  let split = text.len().checked_sub(suffix.len())?;
+  (text.is_char_boundary(split) && text[split..].eq_ignore_ascii_case(suffix))
+    .then(|| &text[..split])
+}
+
+fn without_whitespace(text: &str) -> String {
+  text.split_whitespace().collect()
+}
+
+fn is_identifier(text: &str) -> bool {
+  text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+    && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Rewrites `$` hexadecimal numbers as decimal, which evalexpr understands
+fn decimal_literals(expression: &str) -> Result<String, ErrorKind> {
+  let mut result = String::new();
+  let mut rest = expression;
+  while let Some(start) = rest.find('$') {
+    result.push_str(&rest[..start]);
+    rest = &rest[start + 1..];
+    let end = rest
+      .find(|c: char| !c.is_ascii_hexdigit())
+      .unwrap_or(rest.len());
+    let value = IntType::from_str_radix(&rest[..end], 16)
+      .map_err(|_| ErrorKind::Syntax(expression.to_owned()))?;
+    result.push_str(&value.to_string());
+    rest = &rest[end..];
+  }
+  result.push_str(rest);
+  Ok(result)
+}
+
+/// Rewrites `*` as the current address where it stands for a value, rather than for multiplication
+fn current_address(expression: &str, address: IntType) -> String {
+  let mut result = String::new();
+  for c in expression.chars() {
+    let after_value = result.chars().next_back().is_some_and(|previous: char| {
+      previous.is_ascii_alphanumeric() || matches!(previous, '_' | ')')
+    });
+    if c == '*' && !after_value {
+      result.push_str(&address.to_string());
+    } else {
+      result.push(c);
+    }
+  }
+  result
+}
+
+/// The first pass only works out where everything goes, as labels may be used before they are defined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pass {
+  Size,
+  Output,
+}
+
+struct Assembler {
+  opcodes: HashMap<&'static str, HashMap<Mode, cpu::Int>>,
+  symbols: evalexpr::HashMapContext,
+  /// Addressing modes chosen for each instruction's line in the first pass, so both passes agree on sizes
+  modes: HashMap<usize, Mode>,
+  pass: Pass,
+  address: IntType,
+  origin: Option<memory::Address>,
+  bytes: Vec<cpu::Int>,
+}
+
+impl Assembler {
+  fn new() -> Self {
+    Self {
+      opcodes: opcodes(),
+      symbols: evalexpr::HashMapContext::new(),
+      modes: HashMap::new(),
+      pass: Pass::Size,
+      address: memory::constant::PROGRAM_ROM_START.into(),
+      origin: None,
+      bytes: Vec::new(),
+    }
+  }
+
+  fn start(&mut self, pass: Pass) {
+    self.pass = pass;
+    self.address = memory::constant::PROGRAM_ROM_START.into();
+    self.origin = None;
+    self.bytes.clear();
+  }
+
+  fn line(&mut self, line: &SourceLine) -> Result<(), ErrorKind> {
+    if let Some(label) = &line.label {
+      self.define(label, self.address)?;
+    }
+    match &line.statement {
+      None => {}
+      Some(Statement::Constant(name, expression)) => {
+        let value = self.evaluate(expression)?;
+        self.define(name, value)?;
+      }
+      Some(Statement::Org(expression)) => {
+        let address = self.evaluate(expression)?;
+        memory::Address::try_from(address).map_err(|_| ErrorKind::OutOfRange {
+          value: address,
+          bytes: 2,
+        })?;
+        if self.origin.is_some() {
+          let padding =
+            usize::try_from(address - self.address).map_err(|_| ErrorKind::OriginBackwards {
+              address,
+              current: self.address,
+            })?;
+          self.output(&vec![0; padding])?;
+        }
+        self.address = address;
+      }
+      Some(Statement::Data { width, expressions }) => {
+        for expression in expressions {
+          let value = self.evaluate_in_output(expression)?;
+          self.output_value(value, *width)?;
+        }
+      }
+      Some(Statement::Instruction {
+        mnemonic,
+        shape,
+        expression,
+      }) => self.instruction(line.number, mnemonic, *shape, expression.as_deref())?,
+    }
+    Ok(())
+  }
+
+  fn instruction(
+    &mut self,
+    number: usize,
+    mnemonic: &str,
+    shape: Shape,
+    expression: Option<&str>,
+  ) -> Result<(), ErrorKind> {
+    let opcodes = self
+      .opcodes
+      .get(mnemonic)
+      .ok_or_else(|| ErrorKind::UnknownMnemonic(mnemonic.to_owned()))?;
+    let value = expression
+      .map(|expression| self.evaluate_in_output(expression))
+      .transpose()?
+      .flatten();
+
+    let mode = match self.pass {
+      Pass::Size => {
+        let available: Vec<Mode> = shape
+          .modes()
+          .iter()
+          .copied()
+          .filter(|mode| opcodes.contains_key(mode))
+          .collect();
+        // Zero page forms can only be used once the value is known to fit
+        let fits_zero_page = value.is_some_and(|value| (0..=0xFF).contains(&value));
+        let mode = available
+          .iter()
+          .copied()
+          .find(|&mode| {
+            fits_zero_page
+              || !matches!(
+                mode,
+                Mode::ZeroPage | Mode::XIndexedZeroPage | Mode::YIndexedZeroPage
+              )
+          })
+          .or_else(|| available.first().copied())
+          .ok_or_else(|| ErrorKind::UnsupportedOperand {
+            mnemonic: mnemonic.to_owned(),
+          })?;
+        self.modes.insert(number, mode);
+        mode
+      }
+      Pass::Output => self.modes[&number],
+    };
+    let opcode = opcodes[&mode];
+
+    let value = value.unwrap_or_default();
+    self.output(&[opcode])?;
+    match mode {
+      Mode::Relative if self.pass == Pass::Output => {
+        // The offset is from the address after the operand
+        let offset = value - (self.address + 1);
+        let offset = i8::try_from(offset).map_err(|_| ErrorKind::BranchOutOfRange(offset))?;
+        self.output(&[offset.cast_unsigned()])
+      }
+      Mode::Relative => self.output(&[0]),
+      mode => self.output_value(Some(value), mode.operand_length()),
+    }
+  }
+
+  fn define(&mut self, name: &str, value: IntType) -> Result<(), ErrorKind> {
+    if self.pass == Pass::Output {
+      return Ok(());
+    }
+    if self.symbols.get_value(name).is_some() {
+      return Err(ErrorKind::Redefined(name.to_owned()));
+    }
+    self.symbols.set_value(name.to_owned(), value.into())?;
+    Ok(())
+  }
+
+  fn evaluate(&self, expression: &str) -> Result<IntType, ErrorKind> {
+    if let Some(expression) = expression.strip_prefix('<') {
+      return Ok(self.evaluate(expression)? & 0xFF);
+    }
+    if let Some(expression) = expression.strip_prefix('>') {
+      return Ok((self.evaluate(expression)? >> 8) & 0xFF);
+    }
+    Ok(evalexpr::eval_int_with_context(
+      &current_address(&decimal_literals(expression)?, self.address),
+      &self.symbols,
+    )?)
+  }
+
+  /// Evaluates an expression which may refer to labels defined later, so is only needed in the output pass
+  fn evaluate_in_output(&self, expression: &str) -> Result<Option<IntType>, ErrorKind> {
+    match self.pass {
+      Pass::Size => Ok(self.evaluate(expression).ok()),
+      Pass::Output => self.evaluate(expression).map(Some),
+    }
+  }
+
+  /// Outputs a little-endian value, or zeros for one which is not known yet
+  fn output_value(&mut self, value: Option<IntType>, width: u16) -> Result<(), ErrorKind> {
+    let value = value.unwrap_or_default();
+    let bytes = value.to_le_bytes();
+    let (low, high) = bytes.split_at(usize::from(width));
+    // Negative values are allowed as long as they fit in the width as signed
+    let fits = high.iter().all(|&byte| byte == 0)
+      || (high.iter().all(|&byte| byte == 0xFF) && low.last().is_some_and(|byte| byte & 0x80 != 0));
+    if !fits {
+      return Err(ErrorKind::OutOfRange {
+        value,
+        bytes: width,
+      });
+    }
+    self.output(low)
+  }
+
+  fn output(&mut self, bytes: &[cpu::Int]) -> Result<(), ErrorKind> {
+    if bytes.is_empty() {
+      return Ok(());
+    }
+    let start = memory::Address::try_from(self.address).map_err(|_| ErrorKind::AddressOverflow)?;
+    let end =
+      self.address + IntType::try_from(bytes.len()).map_err(|_| ErrorKind::AddressOverflow)?;
+    if end > IntType::from(memory::Address::MAX) + 1 {
+      return Err(ErrorKind::AddressOverflow);
+    }
+    self.origin.get_or_insert(start);
+    self.bytes.extend_from_slice(bytes);
+    self.address = end;
+    Ok(())
+  }
+}
+
+/// Opcodes by mnemonic and addressing mode
+///
+/// Where several opcodes have the same mnemonic and addressing mode, official opcodes are preferred, then the lowest.
+fn opcodes() -> HashMap<&'static str, HashMap<Mode, cpu::Int>> {
+  let mut opcodes: HashMap<_, HashMap<_, _>> = HashMap::new();
+  for official in [true, false] {
+    for opcode in OPCODES.iter().flatten() {
+      if opcode.official == official {
+        opcodes
+          .entry(opcode.mnemonic())
+          .or_default()
+          .entry(opcode.mode)
+          .or_insert(opcode.code);
+      }
+    }
+  }
+  opcodes
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+  use crate::cpu::operation::{disassemble::disassemble, Operation};
+
+  #[test_case("LDA #$01" => vec![0xA9, 0x01])]
+  #[test_case("lda ($20), y" => vec![0xB1, 0x20] ; "lower case")]
+  #[test_case("LDA ($20,X)" => vec![0xA1, 0x20])]
+  #[test_case("LDA $10,X" => vec![0xB5, 0x10])]
+  #[test_case("STA $0200,X" => vec![0x9D, 0x00, 0x02])]
+  #[test_case("STX $10,Y" => vec![0x96, 0x10])]
+  #[test_case("JMP ($FFFC)" => vec![0x6C, 0xFC, 0xFF])]
+  #[test_case("ASL" => vec![0x0A] ; "implied accumulator")]
+  #[test_case("ROR A" => vec![0x6A])]
+  #[test_case("LDA #>$1234" => vec![0xA9, 0x12] ; "high byte")]
+  #[test_case("LDA #<$1234" => vec![0xA9, 0x34] ; "low byte")]
+  #[test_case("LDX #-1" => vec![0xA2, 0xFF] ; "negative")]
+  #[test_case("SBC #2 * 8 + 1" => vec![0xE9, 0x11] ; "official duplicate")]
+  #[test_case("LAX $10" => vec![0xA7, 0x10] ; "unofficial")]
+  #[test_case("BEQ *-3" => vec![0xF0, 0xFB] ; "current address")]
+  #[test_case("JMP *" => vec![0x4C, 0x00, 0x80] ; "current address alone")]
+  #[test_case("LDA #(*-$7FFE)*2" => vec![0xA9, 0x04] ; "current address and multiplication")]
+  fn instruction(source: &str) -> Vec<cpu::Int> {
+    assemble(source).unwrap().bytes
+  }
+
+  #[test]
+  fn round_trips_disassembly() {
+    for opcode in 0..=cpu::Int::MAX {
+      let bytes = [opcode, 0x34, 0x12];
+      let line = &disassemble(&bytes, 0x8000)[0];
+      let Some(instruction) = line.instruction() else {
+        continue;
+      };
+
+      let program = assemble(&format!(".org $8000\n{instruction}")).unwrap();
+
+      let reassembled = disassemble(&program.bytes, 0x8000)[0]
+        .instruction()
+        .unwrap();
+      assert_eq!(
+        instruction.to_string(),
+        reassembled.to_string(),
+        "{opcode:#04X}"
+      );
+    }
+  }
+
+  #[test]
+  fn round_trips_operations() {
+    for opcode in 0..=cpu::Int::MAX {
+      let Ok((operation, _)) = Operation::decode(&[opcode, 0x34, 0x12], 0x8000) else {
+        continue;
+      };
+
+      let program = assemble(&format!(".org $8000\n{operation}")).unwrap();
+
+      let (reassembled, _) = Operation::decode(&program.bytes, 0x8000).unwrap();
+      assert_eq!(
+        operation.to_string(),
+        reassembled.to_string(),
+        "{opcode:#04X}"
+      );
+    }
+  }
+
+  #[test]
+  fn labels_and_directives() {
+    let program = assemble(
+      "
+      count = 3
+      .org $C000
+      start:  LDX #count
+      loop:   DEX         ; Count down
+              BNE loop
+              BEQ end
+              JMP (vector)
+      end:    BRK
+      vector: .word start, end + 1
+              .byte $FF, <vector
+      ",
+    )
+    .unwrap();
+
+    assert_eq!(0xC000, program.origin);
+    assert_eq!(
+      vec![
+        0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0xF0, 0x03, 0x6C, 0x0B, 0xC0, 0x00, 0x00, 0xC0, 0x0B, 0xC0,
+        0xFF, 0x0B
+      ],
+      program.bytes
+    );
+  }
+
+  #[test]
+  fn forward_reference_uses_absolute_addressing() {
+    let program = assemble("LDA data\ndata: .byte 1").unwrap();
+
+    assert_eq!(vec![0xAD, 0x03, 0x80, 0x01], program.bytes);
+  }
+
+  #[test]
+  fn org_pads_with_zeros() {
+    let program = assemble("NOP\n.org $8004\nNOP").unwrap();
+
+    assert_eq!(vec![0xEA, 0x00, 0x00, 0x00, 0xEA], program.bytes);
+    assert_eq!(
+      Some(vec![0x00, 0x00, 0xEA]),
+      assemble(".org $8002\nNOP").unwrap().rom()
+    );
+    assert_eq!(None, assemble(".org $0200\nNOP").unwrap().rom());
+  }
+
+  #[test]
+  fn errors() {
+    let error = |source| assemble(source).unwrap_err();
+
+    assert!(matches!(
+      error("NOP\nFOO #1"),
+      Error {
+        line: 2,
+        kind: ErrorKind::UnknownMnemonic(_)
+      }
+    ));
+    assert!(matches!(
+      error("LDA ($1234)").kind,
+      ErrorKind::UnsupportedOperand { .. }
+    ));
+    assert!(matches!(
+      error("a: NOP\na: NOP").kind,
+      ErrorKind::Redefined(_)
+    ));
+    assert!(matches!(
+      error("BNE far\n.org $8100\nfar: NOP").kind,
+      ErrorKind::BranchOutOfRange(_)
+    ));
+    assert!(matches!(
+      error("LDA #256").kind,
+      ErrorKind::OutOfRange { .. }
+    ));
+    assert!(matches!(
+      error("NOP\n.org $7000").kind,
+      ErrorKind::OriginBackwards { .. }
+    ));
+    assert!(matches!(
+      error("LDA missing").kind,
+      ErrorKind::Expression(_)
+    ));
+  }
+}
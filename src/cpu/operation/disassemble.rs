@@ -0,0 +1,190 @@
+//! Rendering operations as 6502 assembly
+//!
+//! The syntax is the one used by most 6502 assemblers, with `$` marking hexadecimal and `*` standing for the
+//! address of the current instruction. See <https://www.nesdev.org/obelisk-6502-guide/addressing.html>.
+
+use std::fmt;
+
+use super::{
+  addressing_mode::{Location, Mode, Value},
+  Operation,
+};
+use crate::{cpu, memory};
+
+/// Branch targets are written relative to the current instruction, as they are not known without its address
+impl fmt::Display for Operation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match (self.mode(), self.operand()) {
+      (Mode::Accumulator, _) => write!(f, "{} A", self.mnemonic()),
+      (_, Some(value)) => write!(f, "{} {value}", self.mnemonic()),
+      (_, None) => write!(f, "{}", self.mnemonic()),
+    }
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Value::Immediate(value) => write!(f, "#${value:02X}"),
+      Value::Location(location) => write!(f, "{location}"),
+    }
+  }
+}
+
+impl fmt::Display for Location {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    use Location::*;
+    match self {
+      ZeroPage(addr) => write!(f, "${addr:02X}"),
+      Absolute(addr) => write!(f, "${addr:04X}"),
+      XIndexedZeroPage(addr) => write!(f, "${addr:02X},X"),
+      YIndexedZeroPage(addr) => write!(f, "${addr:02X},Y"),
+      XIndexedAbsolute(addr) => write!(f, "${addr:04X},X"),
+      YIndexedAbsolute(addr) => write!(f, "${addr:04X},Y"),
+      // The offset is from the next instruction, which follows the two bytes of this one
+      Relative(offset) => write!(f, "*{:+}", i16::from(offset.cast_signed()) + 2),
+      Indirect(addr) => write!(f, "(${addr:04X})"),
+      XIndexedIndirect(addr) => write!(f, "(${addr:02X},X)"),
+      IndirectYIndexed(addr) => write!(f, "(${addr:02X}),Y"),
+    }
+  }
+}
+
+/// An operation along with the address it was decoded from, which is needed to resolve branch targets
+#[derive(Clone, Copy, Debug)]
+pub struct Instruction {
+  pub address: memory::Address,
+  pub operation: Operation,
+}
+
+impl Instruction {
+  /// The address a branch jumps to if taken, or `None` if the operation is not a branch
+  #[must_use]
+  pub fn branch_target(self) -> Option<memory::Address> {
+    match self.operation.operand() {
+      Some(Value::Location(Location::Relative(offset))) => Some(
+        self
+          .address
+          .wrapping_add(self.operation.length())
+          .wrapping_add_signed(i16::from(offset.cast_signed())),
+      ),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Instruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.branch_target() {
+      Some(target) => write!(f, "{} ${target:04X}", self.operation.mnemonic()),
+      None => write!(f, "{}", self.operation),
+    }
+  }
+}
+
+/// A line of a disassembly listing, covering one instruction or a byte which could not be decoded
+#[derive(Clone, Debug)]
+pub struct Line {
+  pub address: memory::Address,
+  pub bytes: Vec<cpu::Int>,
+  pub operation: Option<Operation>,
+}
+
+impl Line {
+  #[must_use]
+  pub fn instruction(&self) -> Option<Instruction> {
+    self.operation.map(|operation| Instruction {
+      address: self.address,
+      operation,
+    })
+  }
+}
+
+/// Formatted as the address, the bytes in hexadecimal and then the assembly, such as `8000  A9 01     LDA #$01`
+impl fmt::Display for Line {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let bytes: Vec<String> = self
+      .bytes
+      .iter()
+      .map(|byte| format!("{byte:02X}"))
+      .collect();
+    write!(f, "{:04X}  {:<8}  ", self.address, bytes.join(" "))?;
+    match self.instruction() {
+      Some(instruction) => write!(f, "{instruction}"),
+      None => write!(f, ".byte ${:02X}", self.bytes[0]),
+    }
+  }
+}
+
+/// Disassembles bytes as if they were loaded at the given address
+///
+/// Unofficial opcodes are decoded. Bytes which are not a valid opcode, or which start an instruction cut off by the
+/// end of the input, are listed as data with `.byte`.
+#[must_use]
+pub fn disassemble(bytes: &[cpu::Int], address: memory::Address) -> Vec<Line> {
+  let mut lines = Vec::new();
+  let mut offset = 0;
+  let mut line_address = address;
+  while offset < bytes.len() {
+    let decoded = Operation::decode(&bytes[offset..], line_address).ok();
+    let length = decoded.map_or(1, |(_, length)| length);
+    lines.push(Line {
+      address: line_address,
+      bytes: bytes[offset..offset + usize::from(length)].to_vec(),
+      operation: decoded.map(|(operation, _)| operation),
+    });
+    offset += usize::from(length);
+    line_address = line_address.wrapping_add(length);
+  }
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+
+  #[test_case(&[0xA9, 0x01] => "LDA #$01")]
+  #[test_case(&[0xB1, 0x20] => "LDA ($20),Y")]
+  #[test_case(&[0xA1, 0x20] => "LDA ($20,X)")]
+  #[test_case(&[0x9D, 0x00, 0x02] => "STA $0200,X")]
+  #[test_case(&[0xB6, 0x10] => "LDX $10,Y")]
+  #[test_case(&[0x6C, 0xFC, 0xFF] => "JMP ($FFFC)")]
+  #[test_case(&[0x0A] => "ASL A")]
+  #[test_case(&[0xF8] => "SED")]
+  #[test_case(&[0x1C, 0x34, 0x12] => "NOP $1234,X" ; "unofficial")]
+  #[test_case(&[0xD0, 0xFB] => "BNE $7FFD" ; "backward branch")]
+  #[test_case(&[0x10, 0x02] => "BPL $8004" ; "forward branch")]
+  fn syntax(bytes: &[cpu::Int]) -> String {
+    let lines = disassemble(bytes, 0x8000);
+    assert_eq!(1, lines.len());
+    lines[0].instruction().unwrap().to_string()
+  }
+
+  #[test]
+  fn relative_without_address() {
+    let operation = Operation::Beq(Value::Location(Location::Relative(0xFB)));
+
+    assert_eq!("BEQ *-3", operation.to_string());
+  }
+
+  #[test]
+  fn listing() {
+    // LDA #$01; STA $0200; an illegal opcode; then a JMP cut off by the end of the input
+    let lines = disassemble(&[0xA9, 0x01, 0x8D, 0x00, 0x02, 0x02, 0x4C, 0x00], 0xC000);
+
+    let listing: Vec<String> = lines.iter().map(ToString::to_string).collect();
+
+    assert_eq!(
+      vec![
+        "C000  A9 01     LDA #$01",
+        "C002  8D 00 02  STA $0200",
+        "C005  02        .byte $02",
+        "C006  4C        .byte $4C",
+        "C007  00        BRK",
+      ],
+      listing
+    );
+  }
+}
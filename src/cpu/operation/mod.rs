@@ -1,17 +1,23 @@
 pub mod addressing_mode;
+pub mod assemble;
+pub mod disassemble;
+pub mod opcode;
 pub mod parse;
+pub mod timing;
 
-use strum::Display;
+use strum::IntoStaticStr;
 
-use self::addressing_mode::{Value, Location};
+use self::addressing_mode::{Location, Mode, Value};
 
-#[derive(Clone, Copy, Debug, Display)]
+#[derive(Clone, Copy, Debug, IntoStaticStr)]
+#[strum(serialize_all = "UPPERCASE")]
 pub enum Operation {
   /// Add with carry
   Adc(Value),
   /// Bitwise AND with accumulator
   And(Value),
   /// Arithmetic shift accumulator left
+  #[strum(serialize = "ASL")]
   ASLAcc,
   /// Arithmetic shift left
   Asl(Location),
@@ -43,7 +49,8 @@ pub enum Operation {
   Beq(Value),
   /// Break
   ///
-  /// Triggers non-maskable interrupt (NMI).
+  /// Pushes the address after its padding byte and the status with the break flag set, then jumps through the
+  /// IRQ/BRK vector, or halts the CPU if configured to halt on break.
   Brk,
   /// Compare to accumulator
   Cmp(Value),
@@ -59,13 +66,14 @@ pub enum Operation {
   Sec,
   /// Clear carry processor flag
   Clc,
-  /// Set non-mutable interrupt processor flag
+  /// Set maskable interrupt (IRQ) disable processor flag
   Sei,
-  /// Clear non-mutable interrupt processor flag
+  /// Clear maskable interrupt (IRQ) disable processor flag
   Cli,
   /// Clear overflow processor flag
   Clv,
   /// Set decimal mode processor flag (not implemented on NES)
+  #[strum(serialize = "SED")]
   Set,
   /// Clear decimal mode processor flag (not implemented on NES)
   Cld,
@@ -73,7 +81,7 @@ pub enum Operation {
   Inc(Location),
   /// Jump
   Jmp(Location),
-  /// Jump to SubRoutine
+  /// Jump to subroutine
   Jsr(Location),
   /// Load to accumulator
   Lda(Value),
@@ -81,12 +89,23 @@ pub enum Operation {
   Ldx(Value),
   /// Load to Y register
   Ldy(Value),
+  /// Logical shift accumulator right
+  #[strum(serialize = "LSR")]
+  LsrAcc,
   /// Logical shift right
-  Lsr(Value),
+  Lsr(Location),
   /// No-op
   Nop,
   /// Bitwise OR with accumulator
   Ora(Value),
+  /// Push accumulator to stack
+  Pha,
+  /// Pull accumulator from stack
+  Pla,
+  /// Push processor status to stack
+  Php,
+  /// Pull processor status from stack
+  Plp,
   /// Transfer A to X
   Tax,
   /// Transfer X to A
@@ -103,11 +122,17 @@ pub enum Operation {
   Dey,
   /// Increment Y
   Iny,
+  /// Transfer stack pointer to X
+  Tsx,
+  /// Transfer X to stack pointer
+  Txs,
   /// Rotate accumulator left
+  #[strum(serialize = "ROL")]
   RolAcc,
   /// Rotate left
   Rol(Location),
   /// Rotate accumulator left
+  #[strum(serialize = "ROR")]
   RorAcc,
   /// Rotate accumulator right
   Ror(Location),
@@ -117,10 +142,80 @@ pub enum Operation {
   Rts,
   /// Subtract with carry
   Sbc(Value),
-  // Store accumulator
+  /// Store accumulator
   Sta(Location),
-  // Store accumulator
+  /// Store X register
   Stx(Location),
-  // Store accumulator
+  /// Store Y register
   Sty(Location),
+  /// Load to accumulator and X register (unofficial)
+  Lax(Value),
+  /// Store bitwise AND of accumulator and X register (unofficial)
+  Sax(Location),
+  /// Decrement memory then compare to accumulator (unofficial)
+  Dcp(Location),
+  /// Increment memory then subtract from accumulator with carry (unofficial)
+  Isc(Location),
+  /// Arithmetic shift memory left then bitwise OR with accumulator (unofficial)
+  Slo(Location),
+  /// Rotate memory left then bitwise AND with accumulator (unofficial)
+  Rla(Location),
+  /// Logical shift memory right then bitwise exclusive OR with accumulator (unofficial)
+  Sre(Location),
+  /// Rotate memory right then add to accumulator with carry (unofficial)
+  Rra(Location),
+  /// Bitwise AND with accumulator, copying the negative flag to carry (unofficial)
+  Anc(Value),
+  /// Bitwise AND with accumulator then logical shift accumulator right (unofficial)
+  Alr(Value),
+  /// Bitwise AND with accumulator then rotate accumulator right (unofficial)
+  ///
+  /// Carry and overflow are set from bits 6 and 5 of the result rather than by the rotation.
+  Arr(Value),
+  /// Subtract from bitwise AND of accumulator and X register, storing in X register (unofficial)
+  Axs(Value),
+  /// No-op which reads and ignores its operand (unofficial)
+  #[strum(serialize = "NOP")]
+  Ign(Value),
+}
+
+impl Operation {
+  /// The assembly mnemonic for the operation, such as `LDA`
+  #[must_use]
+  pub fn mnemonic(self) -> &'static str {
+    self.into()
+  }
+
+  /// The value or location the operation acts on, or `None` if it acts on the accumulator or takes no operand
+  #[must_use]
+  pub fn operand(self) -> Option<Value> {
+    use Operation::*;
+    match self {
+      Adc(value) | And(value) | Bit(value) | Bpl(value) | Bmi(value) | Bvc(value) | Bvs(value)
+      | Bcc(value) | Bcs(value) | Bne(value) | Beq(value) | Cmp(value) | Cpx(value)
+      | Cpy(value) | Eor(value) | Lda(value) | Ldx(value) | Ldy(value) | Ora(value)
+      | Sbc(value) | Lax(value) | Anc(value) | Alr(value) | Arr(value) | Axs(value)
+      | Ign(value) => Some(value),
+      Asl(location) | Dec(location) | Inc(location) | Jmp(location) | Jsr(location)
+      | Lsr(location) | Rol(location) | Ror(location) | Sta(location) | Stx(location)
+      | Sty(location) | Sax(location) | Dcp(location) | Isc(location) | Slo(location)
+      | Rla(location) | Sre(location) | Rra(location) => Some(location.into()),
+      _ => None,
+    }
+  }
+
+  #[must_use]
+  pub fn mode(self) -> Mode {
+    use Operation::*;
+    match self {
+      ASLAcc | LsrAcc | RolAcc | RorAcc => Mode::Accumulator,
+      _ => self.operand().map_or(Mode::Implied, Value::mode),
+    }
+  }
+
+  /// Number of bytes the operation is encoded in, including the opcode
+  #[must_use]
+  pub fn length(self) -> u16 {
+    1 + self.mode().operand_length()
+  }
 }
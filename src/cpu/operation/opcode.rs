@@ -0,0 +1,497 @@
+//! Metadata for every opcode the CPU decodes, which drives decoding, encoding, disassembly and timing
+//!
+//! See <https://www.nesdev.org/wiki/CPU_unofficial_opcodes> for the full opcode matrix.
+
+use std::{collections::HashMap, mem, sync::OnceLock};
+
+use super::{
+  addressing_mode::{Location, Mode, Value},
+  Operation,
+};
+use crate::cpu;
+
+/// How an operation is built from its operand
+#[derive(Clone, Copy, Debug)]
+pub enum Form {
+  /// Takes no operand from memory, so the operation is fixed
+  Fixed(Operation),
+  /// Reads a value, which may be immediate
+  Value(fn(Value) -> Operation),
+  /// Acts on a location in memory
+  Location(fn(Location) -> Operation),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Opcode {
+  pub code: cpu::Int,
+  pub form: Form,
+  pub mode: Mode,
+  /// Number of cycles taken to execute, not including the extra cycles taken when an indexed read crosses a page
+  /// boundary or a branch is taken
+  pub cycles: u8,
+  /// Whether the opcode is documented, rather than an unofficial opcode which is rejected in strict mode
+  pub official: bool,
+}
+
+impl Opcode {
+  #[must_use]
+  pub fn mnemonic(&self) -> &'static str {
+    self.operation(0).mnemonic()
+  }
+
+  /// Number of bytes the instruction is encoded in, including the opcode
+  #[must_use]
+  pub fn length(&self) -> u16 {
+    1 + self.mode.operand_length()
+  }
+
+  /// Builds the operation from the operand which follows the opcode
+  ///
+  /// Single byte operands are taken from the low byte.
+  #[must_use]
+  pub fn operation(&self, operand: u16) -> Operation {
+    let location = Location::from_operand(self.mode, operand);
+    match (self.form, location) {
+      (Form::Fixed(operation), _) => operation,
+      (Form::Value(build), None) => build(Value::Immediate(operand.to_le_bytes()[0])),
+      (Form::Value(build), Some(location)) => build(location.into()),
+      (Form::Location(build), Some(location)) => build(location),
+      (Form::Location(_), None) => unreachable!("table entries are checked to have a location"),
+    }
+  }
+}
+
+/// Looks up the metadata for an opcode byte, returning `None` if it is not decoded at all
+#[must_use]
+pub fn lookup(code: cpu::Int) -> Option<&'static Opcode> {
+  OPCODES[usize::from(code)].as_ref()
+}
+
+impl Operation {
+  /// The opcode which encodes the operation, preferring official opcodes where there is a choice
+  ///
+  /// Returns `None` if no opcode has the operation's addressing mode, such as JMP with zero page addressing.
+  #[must_use]
+  pub fn opcode(self) -> Option<&'static Opcode> {
+    static ENCODINGS: OnceLock<HashMap<(mem::Discriminant<Operation>, Mode), &'static Opcode>> =
+      OnceLock::new();
+    let encodings = ENCODINGS.get_or_init(|| {
+      let mut encodings = HashMap::new();
+      for official in [true, false] {
+        for opcode in OPCODES.iter().flatten() {
+          if opcode.official == official {
+            let key = (mem::discriminant(&opcode.operation(0)), opcode.mode);
+            encodings.entry(key).or_insert(opcode);
+          }
+        }
+      }
+      encodings
+    });
+    encodings
+      .get(&(mem::discriminant(&self), self.mode()))
+      .copied()
+  }
+
+  /// Encodes the operation as machine code, or returns `None` if it has no opcode
+  #[must_use]
+  pub fn encode(self) -> Option<Vec<cpu::Int>> {
+    let opcode = self.opcode()?;
+    let operand = match self.operand() {
+      Some(value) => value.operand().to_le_bytes(),
+      None => [0, 0],
+    };
+    let mut bytes = vec![opcode.code];
+    bytes.extend_from_slice(&operand[..usize::from(opcode.mode.operand_length())]);
+    Some(bytes)
+  }
+}
+
+const fn official(code: cpu::Int, form: Form, mode: Mode, cycles: u8) -> Opcode {
+  entry(code, form, mode, cycles, true)
+}
+
+const fn unofficial(code: cpu::Int, form: Form, mode: Mode, cycles: u8) -> Opcode {
+  entry(code, form, mode, cycles, false)
+}
+
+/// Checks the form can be built from the mode, so a mistake in the table fails to compile
+const fn entry(code: cpu::Int, form: Form, mode: Mode, cycles: u8, official: bool) -> Opcode {
+  let has_operand = !matches!(mode, Mode::Implied | Mode::Accumulator);
+  let has_location = has_operand && !matches!(mode, Mode::Immediate);
+  assert!(
+    match form {
+      Form::Fixed(_) => !has_operand,
+      Form::Value(_) => has_operand,
+      Form::Location(_) => has_location,
+    },
+    "form does not match addressing mode"
+  );
+  Opcode {
+    code,
+    form,
+    mode,
+    cycles,
+    official,
+  }
+}
+
+const fn index(opcodes: &[Opcode]) -> [Option<Opcode>; 256] {
+  let mut table = [None; 256];
+  let mut i = 0;
+  while i < opcodes.len() {
+    let code = opcodes[i].code as usize;
+    assert!(table[code].is_none(), "opcode is listed twice");
+    table[code] = Some(opcodes[i]);
+    i += 1;
+  }
+  table
+}
+
+/// Every opcode the CPU decodes, indexed by its byte
+pub static OPCODES: [Option<Opcode>; 256] = {
+  use Form::*;
+  use Mode::*;
+  use Operation::*;
+  index(&[
+    // ADC
+    official(0x69, Value(Adc), Immediate, 2),
+    official(0x65, Value(Adc), ZeroPage, 3),
+    official(0x75, Value(Adc), XIndexedZeroPage, 4),
+    official(0x6D, Value(Adc), Absolute, 4),
+    official(0x7D, Value(Adc), XIndexedAbsolute, 4),
+    official(0x79, Value(Adc), YIndexedAbsolute, 4),
+    official(0x61, Value(Adc), XIndexedIndirect, 6),
+    official(0x71, Value(Adc), IndirectYIndexed, 5),
+    // AND
+    official(0x29, Value(And), Immediate, 2),
+    official(0x25, Value(And), ZeroPage, 3),
+    official(0x35, Value(And), XIndexedZeroPage, 4),
+    official(0x2D, Value(And), Absolute, 4),
+    official(0x3D, Value(And), XIndexedAbsolute, 4),
+    official(0x39, Value(And), YIndexedAbsolute, 4),
+    official(0x21, Value(And), XIndexedIndirect, 6),
+    official(0x31, Value(And), IndirectYIndexed, 5),
+    // ASL
+    official(0x0A, Fixed(ASLAcc), Accumulator, 2),
+    official(0x06, Location(Asl), ZeroPage, 5),
+    official(0x16, Location(Asl), XIndexedZeroPage, 6),
+    official(0x0E, Location(Asl), Absolute, 6),
+    official(0x1E, Location(Asl), XIndexedAbsolute, 7),
+    // BIT
+    official(0x24, Value(Bit), ZeroPage, 3),
+    official(0x2C, Value(Bit), Absolute, 4),
+    // Branch
+    official(0x10, Value(Bpl), Relative, 2),
+    official(0x30, Value(Bmi), Relative, 2),
+    official(0x50, Value(Bvc), Relative, 2),
+    official(0x70, Value(Bvs), Relative, 2),
+    official(0x90, Value(Bcc), Relative, 2),
+    official(0xB0, Value(Bcs), Relative, 2),
+    official(0xD0, Value(Bne), Relative, 2),
+    official(0xF0, Value(Beq), Relative, 2),
+    // BRK
+    official(0x00, Fixed(Brk), Implied, 7),
+    // CMP
+    official(0xC9, Value(Cmp), Immediate, 2),
+    official(0xC5, Value(Cmp), ZeroPage, 3),
+    official(0xD5, Value(Cmp), XIndexedZeroPage, 4),
+    official(0xCD, Value(Cmp), Absolute, 4),
+    official(0xDD, Value(Cmp), XIndexedAbsolute, 4),
+    official(0xD9, Value(Cmp), YIndexedAbsolute, 4),
+    official(0xC1, Value(Cmp), XIndexedIndirect, 6),
+    official(0xD1, Value(Cmp), IndirectYIndexed, 5),
+    // CPX
+    official(0xE0, Value(Cpx), Immediate, 2),
+    official(0xE4, Value(Cpx), ZeroPage, 3),
+    official(0xEC, Value(Cpx), Absolute, 4),
+    // CPY
+    official(0xC0, Value(Cpy), Immediate, 2),
+    official(0xC4, Value(Cpy), ZeroPage, 3),
+    official(0xCC, Value(Cpy), Absolute, 4),
+    // DEC
+    official(0xC6, Location(Dec), ZeroPage, 5),
+    official(0xD6, Location(Dec), XIndexedZeroPage, 6),
+    official(0xCE, Location(Dec), Absolute, 6),
+    official(0xDE, Location(Dec), XIndexedAbsolute, 7),
+    // EOR (XOR)
+    official(0x49, Value(Eor), Immediate, 2),
+    official(0x45, Value(Eor), ZeroPage, 3),
+    official(0x55, Value(Eor), XIndexedZeroPage, 4),
+    official(0x4D, Value(Eor), Absolute, 4),
+    official(0x5D, Value(Eor), XIndexedAbsolute, 4),
+    official(0x59, Value(Eor), YIndexedAbsolute, 4),
+    official(0x41, Value(Eor), XIndexedIndirect, 6),
+    official(0x51, Value(Eor), IndirectYIndexed, 5),
+    // Processor status flags set
+    official(0x38, Fixed(Sec), Implied, 2),
+    official(0x78, Fixed(Sei), Implied, 2),
+    official(0xF8, Fixed(Set), Implied, 2),
+    // Processor status flags clear
+    official(0x18, Fixed(Clc), Implied, 2),
+    official(0x58, Fixed(Cli), Implied, 2),
+    official(0xB8, Fixed(Clv), Implied, 2),
+    official(0xD8, Fixed(Cld), Implied, 2),
+    // INC
+    official(0xE6, Location(Inc), ZeroPage, 5),
+    official(0xF6, Location(Inc), XIndexedZeroPage, 6),
+    official(0xEE, Location(Inc), Absolute, 6),
+    official(0xFE, Location(Inc), XIndexedAbsolute, 7),
+    // JMP
+    official(0x4C, Location(Jmp), Absolute, 3),
+    official(0x6C, Location(Jmp), Indirect, 5),
+    // JSR
+    official(0x20, Location(Jsr), Absolute, 6),
+    // LDA
+    official(0xA9, Value(Lda), Immediate, 2),
+    official(0xA5, Value(Lda), ZeroPage, 3),
+    official(0xB5, Value(Lda), XIndexedZeroPage, 4),
+    official(0xAD, Value(Lda), Absolute, 4),
+    official(0xBD, Value(Lda), XIndexedAbsolute, 4),
+    official(0xB9, Value(Lda), YIndexedAbsolute, 4),
+    official(0xA1, Value(Lda), XIndexedIndirect, 6),
+    official(0xB1, Value(Lda), IndirectYIndexed, 5),
+    // LDX
+    official(0xA2, Value(Ldx), Immediate, 2),
+    official(0xA6, Value(Ldx), ZeroPage, 3),
+    official(0xB6, Value(Ldx), YIndexedZeroPage, 4),
+    official(0xAE, Value(Ldx), Absolute, 4),
+    official(0xBE, Value(Ldx), YIndexedAbsolute, 4),
+    // LDY
+    official(0xA0, Value(Ldy), Immediate, 2),
+    official(0xA4, Value(Ldy), ZeroPage, 3),
+    official(0xB4, Value(Ldy), XIndexedZeroPage, 4),
+    official(0xAC, Value(Ldy), Absolute, 4),
+    official(0xBC, Value(Ldy), XIndexedAbsolute, 4),
+    // LSR
+    official(0x4A, Fixed(LsrAcc), Accumulator, 2),
+    official(0x46, Location(Lsr), ZeroPage, 5),
+    official(0x56, Location(Lsr), XIndexedZeroPage, 6),
+    official(0x4E, Location(Lsr), Absolute, 6),
+    official(0x5E, Location(Lsr), XIndexedAbsolute, 7),
+    // NOP
+    official(0xEA, Fixed(Nop), Implied, 2),
+    // ORA
+    official(0x09, Value(Ora), Immediate, 2),
+    official(0x05, Value(Ora), ZeroPage, 3),
+    official(0x15, Value(Ora), XIndexedZeroPage, 4),
+    official(0x0D, Value(Ora), Absolute, 4),
+    official(0x1D, Value(Ora), XIndexedAbsolute, 4),
+    official(0x19, Value(Ora), YIndexedAbsolute, 4),
+    official(0x01, Value(Ora), XIndexedIndirect, 6),
+    official(0x11, Value(Ora), IndirectYIndexed, 5),
+    // Stack
+    official(0x48, Fixed(Pha), Implied, 3),
+    official(0x68, Fixed(Pla), Implied, 4),
+    official(0x08, Fixed(Php), Implied, 3),
+    official(0x28, Fixed(Plp), Implied, 4),
+    // Register X
+    official(0xAA, Fixed(Tax), Implied, 2),
+    official(0x8A, Fixed(Txa), Implied, 2),
+    official(0xCA, Fixed(Dex), Implied, 2),
+    official(0xE8, Fixed(Inx), Implied, 2),
+    // Register Y
+    official(0xA8, Fixed(Tay), Implied, 2),
+    official(0x98, Fixed(Tya), Implied, 2),
+    official(0x88, Fixed(Dey), Implied, 2),
+    official(0xC8, Fixed(Iny), Implied, 2),
+    // Stack pointer
+    official(0xBA, Fixed(Tsx), Implied, 2),
+    official(0x9A, Fixed(Txs), Implied, 2),
+    // ROL
+    official(0x2A, Fixed(RolAcc), Accumulator, 2),
+    official(0x26, Location(Rol), ZeroPage, 5),
+    official(0x36, Location(Rol), XIndexedZeroPage, 6),
+    official(0x2E, Location(Rol), Absolute, 6),
+    official(0x3E, Location(Rol), XIndexedAbsolute, 7),
+    // ROR
+    official(0x6A, Fixed(RorAcc), Accumulator, 2),
+    official(0x66, Location(Ror), ZeroPage, 5),
+    official(0x76, Location(Ror), XIndexedZeroPage, 6),
+    official(0x6E, Location(Ror), Absolute, 6),
+    official(0x7E, Location(Ror), XIndexedAbsolute, 7),
+    // RTI
+    official(0x40, Fixed(Rti), Implied, 6),
+    // RTS
+    official(0x60, Fixed(Rts), Implied, 6),
+    // SBC
+    official(0xE9, Value(Sbc), Immediate, 2),
+    official(0xE5, Value(Sbc), ZeroPage, 3),
+    official(0xF5, Value(Sbc), XIndexedZeroPage, 4),
+    official(0xED, Value(Sbc), Absolute, 4),
+    official(0xFD, Value(Sbc), XIndexedAbsolute, 4),
+    official(0xF9, Value(Sbc), YIndexedAbsolute, 4),
+    official(0xE1, Value(Sbc), XIndexedIndirect, 6),
+    official(0xF1, Value(Sbc), IndirectYIndexed, 5),
+    // STA
+    official(0x85, Location(Sta), ZeroPage, 3),
+    official(0x95, Location(Sta), XIndexedZeroPage, 4),
+    official(0x8D, Location(Sta), Absolute, 4),
+    official(0x9D, Location(Sta), XIndexedAbsolute, 5),
+    official(0x99, Location(Sta), YIndexedAbsolute, 5),
+    official(0x81, Location(Sta), XIndexedIndirect, 6),
+    official(0x91, Location(Sta), IndirectYIndexed, 6),
+    // STX
+    official(0x86, Location(Stx), ZeroPage, 3),
+    official(0x96, Location(Stx), YIndexedZeroPage, 4),
+    official(0x8E, Location(Stx), Absolute, 4),
+    // STY
+    official(0x84, Location(Sty), ZeroPage, 3),
+    official(0x94, Location(Sty), XIndexedZeroPage, 4),
+    official(0x8C, Location(Sty), Absolute, 4),
+    // Unofficial opcodes, which are rejected in strict mode
+    // LAX
+    unofficial(0xA7, Value(Lax), ZeroPage, 3),
+    unofficial(0xB7, Value(Lax), YIndexedZeroPage, 4),
+    unofficial(0xAF, Value(Lax), Absolute, 4),
+    unofficial(0xBF, Value(Lax), YIndexedAbsolute, 4),
+    unofficial(0xA3, Value(Lax), XIndexedIndirect, 6),
+    unofficial(0xB3, Value(Lax), IndirectYIndexed, 5),
+    // SAX
+    unofficial(0x87, Location(Sax), ZeroPage, 3),
+    unofficial(0x97, Location(Sax), YIndexedZeroPage, 4),
+    unofficial(0x8F, Location(Sax), Absolute, 4),
+    unofficial(0x83, Location(Sax), XIndexedIndirect, 6),
+    // DCP
+    unofficial(0xC7, Location(Dcp), ZeroPage, 5),
+    unofficial(0xD7, Location(Dcp), XIndexedZeroPage, 6),
+    unofficial(0xCF, Location(Dcp), Absolute, 6),
+    unofficial(0xDF, Location(Dcp), XIndexedAbsolute, 7),
+    unofficial(0xDB, Location(Dcp), YIndexedAbsolute, 7),
+    unofficial(0xC3, Location(Dcp), XIndexedIndirect, 8),
+    unofficial(0xD3, Location(Dcp), IndirectYIndexed, 8),
+    // ISC
+    unofficial(0xE7, Location(Isc), ZeroPage, 5),
+    unofficial(0xF7, Location(Isc), XIndexedZeroPage, 6),
+    unofficial(0xEF, Location(Isc), Absolute, 6),
+    unofficial(0xFF, Location(Isc), XIndexedAbsolute, 7),
+    unofficial(0xFB, Location(Isc), YIndexedAbsolute, 7),
+    unofficial(0xE3, Location(Isc), XIndexedIndirect, 8),
+    unofficial(0xF3, Location(Isc), IndirectYIndexed, 8),
+    // SLO
+    unofficial(0x07, Location(Slo), ZeroPage, 5),
+    unofficial(0x17, Location(Slo), XIndexedZeroPage, 6),
+    unofficial(0x0F, Location(Slo), Absolute, 6),
+    unofficial(0x1F, Location(Slo), XIndexedAbsolute, 7),
+    unofficial(0x1B, Location(Slo), YIndexedAbsolute, 7),
+    unofficial(0x03, Location(Slo), XIndexedIndirect, 8),
+    unofficial(0x13, Location(Slo), IndirectYIndexed, 8),
+    // RLA
+    unofficial(0x27, Location(Rla), ZeroPage, 5),
+    unofficial(0x37, Location(Rla), XIndexedZeroPage, 6),
+    unofficial(0x2F, Location(Rla), Absolute, 6),
+    unofficial(0x3F, Location(Rla), XIndexedAbsolute, 7),
+    unofficial(0x3B, Location(Rla), YIndexedAbsolute, 7),
+    unofficial(0x23, Location(Rla), XIndexedIndirect, 8),
+    unofficial(0x33, Location(Rla), IndirectYIndexed, 8),
+    // SRE
+    unofficial(0x47, Location(Sre), ZeroPage, 5),
+    unofficial(0x57, Location(Sre), XIndexedZeroPage, 6),
+    unofficial(0x4F, Location(Sre), Absolute, 6),
+    unofficial(0x5F, Location(Sre), XIndexedAbsolute, 7),
+    unofficial(0x5B, Location(Sre), YIndexedAbsolute, 7),
+    unofficial(0x43, Location(Sre), XIndexedIndirect, 8),
+    unofficial(0x53, Location(Sre), IndirectYIndexed, 8),
+    // RRA
+    unofficial(0x67, Location(Rra), ZeroPage, 5),
+    unofficial(0x77, Location(Rra), XIndexedZeroPage, 6),
+    unofficial(0x6F, Location(Rra), Absolute, 6),
+    unofficial(0x7F, Location(Rra), XIndexedAbsolute, 7),
+    unofficial(0x7B, Location(Rra), YIndexedAbsolute, 7),
+    unofficial(0x63, Location(Rra), XIndexedIndirect, 8),
+    unofficial(0x73, Location(Rra), IndirectYIndexed, 8),
+    // Immediate
+    unofficial(0x0B, Value(Anc), Immediate, 2),
+    unofficial(0x2B, Value(Anc), Immediate, 2),
+    unofficial(0x4B, Value(Alr), Immediate, 2),
+    unofficial(0x6B, Value(Arr), Immediate, 2),
+    unofficial(0xCB, Value(Axs), Immediate, 2),
+    unofficial(0xEB, Value(Sbc), Immediate, 2),
+    // NOP
+    unofficial(0x1A, Fixed(Nop), Implied, 2),
+    unofficial(0x3A, Fixed(Nop), Implied, 2),
+    unofficial(0x5A, Fixed(Nop), Implied, 2),
+    unofficial(0x7A, Fixed(Nop), Implied, 2),
+    unofficial(0xDA, Fixed(Nop), Implied, 2),
+    unofficial(0xFA, Fixed(Nop), Implied, 2),
+    unofficial(0x80, Value(Ign), Immediate, 2),
+    unofficial(0x82, Value(Ign), Immediate, 2),
+    unofficial(0x89, Value(Ign), Immediate, 2),
+    unofficial(0xC2, Value(Ign), Immediate, 2),
+    unofficial(0xE2, Value(Ign), Immediate, 2),
+    unofficial(0x04, Value(Ign), ZeroPage, 3),
+    unofficial(0x44, Value(Ign), ZeroPage, 3),
+    unofficial(0x64, Value(Ign), ZeroPage, 3),
+    unofficial(0x14, Value(Ign), XIndexedZeroPage, 4),
+    unofficial(0x34, Value(Ign), XIndexedZeroPage, 4),
+    unofficial(0x54, Value(Ign), XIndexedZeroPage, 4),
+    unofficial(0x74, Value(Ign), XIndexedZeroPage, 4),
+    unofficial(0xD4, Value(Ign), XIndexedZeroPage, 4),
+    unofficial(0xF4, Value(Ign), XIndexedZeroPage, 4),
+    unofficial(0x0C, Value(Ign), Absolute, 4),
+    unofficial(0x1C, Value(Ign), XIndexedAbsolute, 4),
+    unofficial(0x3C, Value(Ign), XIndexedAbsolute, 4),
+    unofficial(0x5C, Value(Ign), XIndexedAbsolute, 4),
+    unofficial(0x7C, Value(Ign), XIndexedAbsolute, 4),
+    unofficial(0xDC, Value(Ign), XIndexedAbsolute, 4),
+    unofficial(0xFC, Value(Ign), XIndexedAbsolute, 4),
+  ])
+};
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+
+  #[test]
+  fn opcode_counts() {
+    let opcodes: Vec<&Opcode> = OPCODES.iter().flatten().collect();
+
+    assert_eq!(236, opcodes.len());
+    assert_eq!(151, opcodes.iter().filter(|opcode| opcode.official).count());
+    for (code, opcode) in OPCODES.iter().enumerate() {
+      if let Some(opcode) = opcode {
+        assert_eq!(code, usize::from(opcode.code));
+      }
+    }
+  }
+
+  #[test]
+  fn encodes_every_opcode() {
+    for opcode in OPCODES.iter().flatten() {
+      let operation = opcode.operation(0x1234);
+
+      let bytes = operation.encode().unwrap();
+
+      assert_eq!(usize::from(opcode.length()), bytes.len(), "{operation:?}");
+      assert_eq!(opcode.cycles, operation.base_cycles(), "{operation:?}");
+      let encoded = lookup(bytes[0]).unwrap();
+      assert_eq!(opcode.mnemonic(), encoded.mnemonic());
+      assert_eq!(opcode.mode, encoded.mode);
+      if opcode.official {
+        assert_eq!(opcode.code, encoded.code);
+      }
+    }
+  }
+
+  #[test_case(Operation::Lda(Value::Immediate(0x01)) => Some(vec![0xA9, 0x01]))]
+  #[test_case(Operation::Sta(Location::XIndexedAbsolute(0x0200)) => Some(vec![0x9D, 0x00, 0x02]))]
+  #[test_case(Operation::Sbc(Value::Immediate(0x01)) => Some(vec![0xE9, 0x01]) ; "official duplicate")]
+  #[test_case(Operation::RorAcc => Some(vec![0x6A]))]
+  #[test_case(Operation::Jmp(Location::ZeroPage(0x10)) => None ; "no opcode")]
+  fn encode(operation: Operation) -> Option<Vec<cpu::Int>> {
+    operation.encode()
+  }
+
+  #[test]
+  fn metadata() {
+    let opcode = lookup(0xB1).unwrap();
+
+    assert_eq!("LDA", opcode.mnemonic());
+    assert_eq!(Mode::IndirectYIndexed, opcode.mode);
+    assert_eq!(2, opcode.length());
+    assert_eq!(5, opcode.cycles);
+    assert!(opcode.official);
+    assert!(lookup(0x02).is_none());
+  }
+}
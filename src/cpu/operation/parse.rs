@@ -1,196 +1,205 @@
-use super::{addressing_mode, Operation};
-use crate::cpu::Cpu;
+use super::{opcode, Operation};
+use crate::{
+  cpu::{self, error::Error},
+  memory::{self, Bus},
+};
 
 impl Operation {
   /// Get the next operation to execute, moving the program counter forward
-  /// # Panics
-  /// This function will panic if it receives an opcode that is not defined
-  #[allow(clippy::too_many_lines)]
-  pub fn next(cpu: &mut Cpu) -> Operation {
-    use addressing_mode::Location::*;
-    use addressing_mode::{Value, Value::*};
-    use Operation::*;
-
-    let opcode = cpu.next_int();
-    match opcode {
-      // ADC
-      0x69 => Adc(Immediate(cpu.next_int())),
-      0x65 => Adc(Value::from(ZeroPage(cpu.next_int()))),
-      0x75 => Adc(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0x6D => Adc(Value::from(Absolute(cpu.next_address()))),
-      0x7D => Adc(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      0x79 => Adc(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      0x61 => Adc(Value::from(XIndexedIndirect(cpu.next_int()))),
-      0x71 => Adc(Value::from(IndirectYIndexed(cpu.next_int()))),
-      // AND
-      0x29 => And(Immediate(cpu.next_int())),
-      0x25 => And(Value::from(ZeroPage(cpu.next_int()))),
-      0x35 => And(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0x2D => And(Value::from(Absolute(cpu.next_address()))),
-      0x3D => And(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      0x39 => And(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      0x21 => And(Value::from(XIndexedIndirect(cpu.next_int()))),
-      0x31 => And(Value::from(IndirectYIndexed(cpu.next_int()))),
-      // ASL
-      0x0A => ASLAcc,
-      0x06 => Asl(ZeroPage(cpu.next_int())),
-      0x16 => Asl(XIndexedZeroPage(cpu.next_int())),
-      0x0E => Asl(Absolute(cpu.next_address())),
-      0x1E => Asl(XIndexedAbsolute(cpu.next_address())),
-      // BIT
-      0x24 => Bit(Value::from(ZeroPage(cpu.next_int()))),
-      0x2C => Bit(Value::from(Absolute(cpu.next_address()))),
-      // Branch
-      0x10 => Bpl(Value::from(Relative(cpu.next_int()))),
-      0x30 => Bmi(Value::from(Relative(cpu.next_int()))),
-      0x50 => Bvc(Value::from(Relative(cpu.next_int()))),
-      0x70 => Bvs(Value::from(Relative(cpu.next_int()))),
-      0x90 => Bcc(Value::from(Relative(cpu.next_int()))),
-      0xB0 => Bcs(Value::from(Relative(cpu.next_int()))),
-      0xD0 => Bne(Value::from(Relative(cpu.next_int()))),
-      0xF0 => Beq(Value::from(Relative(cpu.next_int()))),
-      // BRK
-      0x00 => Brk,
-      // CMP
-      0xC9 => Cmp(Immediate(cpu.next_int())),
-      0xC5 => Cmp(Value::from(ZeroPage(cpu.next_int()))),
-      0xD5 => Cmp(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0xCD => Cmp(Value::from(Absolute(cpu.next_address()))),
-      0xDD => Cmp(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      0xD9 => Cmp(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      0xC1 => Cmp(Value::from(XIndexedIndirect(cpu.next_int()))),
-      0xD1 => Cmp(Value::from(IndirectYIndexed(cpu.next_int()))),
-      // CPX
-      0xE0 => Cpx(Immediate(cpu.next_int())),
-      0xE4 => Cpx(Value::from(ZeroPage(cpu.next_int()))),
-      0xEC => Cpx(Value::from(Absolute(cpu.next_address()))),
-      // CPY
-      0xC0 => Cpy(Immediate(cpu.next_int())),
-      0xC4 => Cpy(Value::from(ZeroPage(cpu.next_int()))),
-      0xCC => Cpy(Value::from(Absolute(cpu.next_address()))),
-      // DEC
-      0xC6 => Dec(ZeroPage(cpu.next_int())),
-      0xD6 => Dec(XIndexedZeroPage(cpu.next_int())),
-      0xCE => Dec(Absolute(cpu.next_address())),
-      0xDE => Dec(XIndexedAbsolute(cpu.next_address())),
-      // EOR (XOR)
-      0x49 => Eor(Immediate(cpu.next_int())),
-      0x45 => Eor(Value::from(ZeroPage(cpu.next_int()))),
-      0x55 => Eor(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0x4D => Eor(Value::from(Absolute(cpu.next_address()))),
-      0x5D => Eor(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      0x59 => Eor(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      0x41 => Eor(Value::from(XIndexedIndirect(cpu.next_int()))),
-      0x51 => Eor(Value::from(IndirectYIndexed(cpu.next_int()))),
-      // Processor status flags set
-      0x38 => Sec,
-      0x78 => Sei,
-      0xF8 => Set,
-      // Processor status flags clear
-      0x18 => Clc,
-      0x58 => Cli,
-      0xB8 => Clv,
-      0xD8 => Cld,
-      // INC
-      0xE6 => Inc(ZeroPage(cpu.next_int())),
-      0xF6 => Inc(XIndexedZeroPage(cpu.next_int())),
-      0xEE => Inc(Absolute(cpu.next_address())),
-      0xFE => Inc(XIndexedAbsolute(cpu.next_address())),
-      // JMP
-      0x4C => Jmp(Absolute(cpu.next_address())),
-      0x6C => Jmp(Indirect(cpu.next_address())),
-      // JSR
-      0x20 => Jsr(Absolute(cpu.next_address())),
-      // LDA
-      0xA9 => Lda(Immediate(cpu.next_int())),
-      0xA5 => Lda(Value::from(ZeroPage(cpu.next_int()))),
-      0xB5 => Lda(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0xAD => Lda(Value::from(Absolute(cpu.next_address()))),
-      0xBD => Lda(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      0xB9 => Lda(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      0xA1 => Lda(Value::from(XIndexedIndirect(cpu.next_int()))),
-      0xB1 => Lda(Value::from(IndirectYIndexed(cpu.next_int()))),
-      // LDX
-      0xA2 => Ldx(Immediate(cpu.next_int())),
-      0xA6 => Ldx(Value::from(ZeroPage(cpu.next_int()))),
-      0xB6 => Ldx(Value::from(YIndexedZeroPage(cpu.next_int()))),
-      0xAE => Ldx(Value::from(Absolute(cpu.next_address()))),
-      0xBE => Ldx(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      // LDY
-      0xA0 => Ldy(Immediate(cpu.next_int())),
-      0xA4 => Ldy(Value::from(ZeroPage(cpu.next_int()))),
-      0xB4 => Ldy(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0xAC => Ldy(Value::from(Absolute(cpu.next_address()))),
-      0xBC => Ldy(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      // LSR
-      0x4A => Lsr(Immediate(cpu.register.accumulator)),
-      0x46 => Lsr(Value::from(ZeroPage(cpu.next_int()))),
-      0x56 => Lsr(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0x4E => Lsr(Value::from(Absolute(cpu.next_address()))),
-      0x5E => Lsr(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      // NOP
-      0xEA => Nop,
-      // ORA
-      0x09 => Ora(Immediate(cpu.next_int())),
-      0x05 => Ora(Value::from(ZeroPage(cpu.next_int()))),
-      0x15 => Ora(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0x0D => Ora(Value::from(Absolute(cpu.next_address()))),
-      0x1D => Ora(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      0x19 => Ora(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      0x01 => Ora(Value::from(XIndexedIndirect(cpu.next_int()))),
-      0x11 => Ora(Value::from(IndirectYIndexed(cpu.next_int()))),
-      // Register X
-      0xAA => Tax,
-      0x8A => Txa,
-      0xCA => Dex,
-      0xE8 => Inx,
-      // Register Y
-      0xA8 => Tay,
-      0x98 => Tya,
-      0x88 => Dey,
-      0xC8 => Iny,
-      // ROL
-      0x2A => RolAcc,
-      0x26 => Rol(ZeroPage(cpu.next_int())),
-      0x36 => Rol(XIndexedZeroPage(cpu.next_int())),
-      0x2E => Rol(Absolute(cpu.next_address())),
-      0x3E => Rol(XIndexedAbsolute(cpu.next_address())),
-      // ROR
-      0x6A => RorAcc,
-      0x66 => Ror(ZeroPage(cpu.next_int())),
-      0x76 => Ror(XIndexedZeroPage(cpu.next_int())),
-      0x6E => Ror(Absolute(cpu.next_address())),
-      0x7E => Ror(XIndexedAbsolute(cpu.next_address())),
-      // RTI
-      0x40 => Rti,
-      // RTS
-      0x60 => Rts,
-      // SBC
-      0xE9 => Sbc(Immediate(cpu.next_int())),
-      0xE5 => Sbc(Value::from(ZeroPage(cpu.next_int()))),
-      0xF5 => Sbc(Value::from(XIndexedZeroPage(cpu.next_int()))),
-      0xED => Sbc(Value::from(Absolute(cpu.next_address()))),
-      0xFD => Sbc(Value::from(XIndexedAbsolute(cpu.next_address()))),
-      0xF9 => Sbc(Value::from(YIndexedAbsolute(cpu.next_address()))),
-      0xE1 => Sbc(Value::from(XIndexedIndirect(cpu.next_int()))),
-      0xF1 => Sbc(Value::from(IndirectYIndexed(cpu.next_int()))),
-      // STA
-      0x85 => Sta(ZeroPage(cpu.next_int())),
-      0x95 => Sta(XIndexedZeroPage(cpu.next_int())),
-      0x8D => Sta(Absolute(cpu.next_address())),
-      0x9D => Sta(XIndexedAbsolute(cpu.next_address())),
-      0x99 => Sta(YIndexedAbsolute(cpu.next_address())),
-      0x81 => Sta(XIndexedIndirect(cpu.next_int())),
-      0x91 => Sta(IndirectYIndexed(cpu.next_int())),
-      // STX
-      0x86 => Stx(ZeroPage(cpu.next_int())),
-      0x96 => Stx(XIndexedZeroPage(cpu.next_int())),
-      0x8E => Stx(Absolute(cpu.next_address())),
-      // STY
-      0x84 => Sty(ZeroPage(cpu.next_int())),
-      0x94 => Sty(XIndexedZeroPage(cpu.next_int())),
-      0x8C => Sty(Absolute(cpu.next_address())),
-      _ => unimplemented!("opcode {:X?}", opcode),
+  ///
+  /// # Errors
+  /// Returns [`Error::IllegalOpcode`] if the opcode is not defined (or is unofficial and the CPU is in strict mode),
+  /// or forwards any error from reading memory
+  pub fn next<B: Bus>(cpu: &mut cpu::Nes<B>) -> Result<Operation, Error> {
+    let address = cpu.register.program_counter;
+    let code = cpu.next_int()?;
+    let opcode = opcode::lookup(code)
+      .filter(|opcode| opcode.official || !cpu.config.strict)
+      .ok_or(Error::IllegalOpcode {
+        address,
+        opcode: code,
+      })?;
+    let operand = match opcode.mode.operand_length() {
+      0 => 0,
+      1 => cpu.next_int()?.into(),
+      _ => cpu.next_address()?,
+    };
+    Ok(opcode.operation(operand))
+  }
+
+  /// Decodes the instruction at the start of a byte slice, returning it along with its length in bytes
+  ///
+  /// The address is where the bytes were read from, and is used in errors. Unofficial opcodes are decoded.
+  ///
+  /// # Errors
+  /// Returns [`Error::IllegalOpcode`] if the opcode is not defined, or [`Error::UnmappedAddress`] with the address
+  /// of the first missing byte if the instruction runs past the end of the slice
+  pub fn decode(bytes: &[cpu::Int], address: memory::Address) -> Result<(Operation, u16), Error> {
+    Self::decode_with(address, |at| {
+      bytes
+        .get(usize::from(at.wrapping_sub(address)))
+        .copied()
+        .ok_or(Error::UnmappedAddress(at))
+    })
+  }
+
+  /// Decodes the instruction at an address on a bus, returning it along with its length in bytes
+  ///
+  /// Memory is read with [`Bus::peek`], so decoding has no side effects. Unofficial opcodes are decoded.
+  ///
+  /// # Errors
+  /// Returns [`Error::IllegalOpcode`] if the opcode is not defined, or forwards any error from peeking memory
+  pub fn decode_from(bus: &impl Bus, address: memory::Address) -> Result<(Operation, u16), Error> {
+    Self::decode_with(address, |at| bus.peek(at))
+  }
+
+  fn decode_with(
+    address: memory::Address,
+    read: impl Fn(memory::Address) -> Result<cpu::Int, Error>,
+  ) -> Result<(Operation, u16), Error> {
+    let code = read(address)?;
+    let opcode = opcode::lookup(code).ok_or(Error::IllegalOpcode {
+      address,
+      opcode: code,
+    })?;
+    let [low, high] = [1, 2].map(|offset| {
+      if offset <= opcode.mode.operand_length() {
+        read(address.wrapping_add(offset))
+      } else {
+        Ok(0)
+      }
+    });
+    let operand = u16::from_le_bytes([low?, high?]);
+    Ok((opcode.operation(operand), opcode.length()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use crate::{
+    cpu::{error::Error, Cpu},
+    memory::{self, Bus},
+  };
+
+  use super::Operation;
+
+  /// Every opcode documented for the NMOS 6502
+  const OFFICIAL_OPCODES: [u8; 151] = [
+    0x00, 0x01, 0x05, 0x06, 0x08, 0x09, 0x0A, 0x0D, 0x0E, 0x10, 0x11, 0x15, 0x16, 0x18, 0x19, 0x1D,
+    0x1E, 0x20, 0x21, 0x24, 0x25, 0x26, 0x28, 0x29, 0x2A, 0x2C, 0x2D, 0x2E, 0x30, 0x31, 0x35, 0x36,
+    0x38, 0x39, 0x3D, 0x3E, 0x40, 0x41, 0x45, 0x46, 0x48, 0x49, 0x4A, 0x4C, 0x4D, 0x4E, 0x50, 0x51,
+    0x55, 0x56, 0x58, 0x59, 0x5D, 0x5E, 0x60, 0x61, 0x65, 0x66, 0x68, 0x69, 0x6A, 0x6C, 0x6D, 0x6E,
+    0x70, 0x71, 0x75, 0x76, 0x78, 0x79, 0x7D, 0x7E, 0x81, 0x84, 0x85, 0x86, 0x88, 0x8A, 0x8C, 0x8D,
+    0x8E, 0x90, 0x91, 0x94, 0x95, 0x96, 0x98, 0x99, 0x9A, 0x9D, 0xA0, 0xA1, 0xA2, 0xA4, 0xA5, 0xA6,
+    0xA8, 0xA9, 0xAA, 0xAC, 0xAD, 0xAE, 0xB0, 0xB1, 0xB4, 0xB5, 0xB6, 0xB8, 0xB9, 0xBA, 0xBC, 0xBD,
+    0xBE, 0xC0, 0xC1, 0xC4, 0xC5, 0xC6, 0xC8, 0xC9, 0xCA, 0xCC, 0xCD, 0xCE, 0xD0, 0xD1, 0xD5, 0xD6,
+    0xD8, 0xD9, 0xDD, 0xDE, 0xE0, 0xE1, 0xE4, 0xE5, 0xE6, 0xE8, 0xE9, 0xEA, 0xEC, 0xED, 0xEE, 0xF0,
+    0xF1, 0xF5, 0xF6, 0xF8, 0xF9, 0xFD, 0xFE,
+  ];
+
+  #[test]
+  fn decodes_official_opcodes() {
+    for opcode in OFFICIAL_OPCODES {
+      let mut cpu = Cpu::default();
+      cpu.load(&[opcode, 0x00, 0x00]);
+      cpu.register.program_counter = memory::constant::PROGRAM_ROM_START;
+
+      let operation = Operation::next(&mut cpu).unwrap();
+
+      assert!(
+        cpu.register.program_counter > memory::constant::PROGRAM_ROM_START,
+        "{opcode:#04X} decoded as {operation:?} without consuming input"
+      );
     }
   }
+
+  #[test]
+  fn illegal_opcode() {
+    let mut cpu = Cpu::default();
+    cpu.load(&[0x02]);
+    cpu.register.program_counter = memory::constant::PROGRAM_ROM_START;
+
+    let result = Operation::next(&mut cpu);
+
+    assert!(matches!(
+      result,
+      Err(Error::IllegalOpcode {
+        address: memory::constant::PROGRAM_ROM_START,
+        opcode: 0x02,
+      })
+    ));
+  }
+
+  #[test]
+  fn unofficial_opcode() {
+    let mut cpu = Cpu::default();
+    cpu.load(&[0xA7, 0x10]);
+    cpu.register.program_counter = memory::constant::PROGRAM_ROM_START;
+
+    let result = Operation::next(&mut cpu);
+
+    assert!(matches!(result, Ok(Operation::Lax(_))));
+  }
+
+  #[test_case(&[0xB1, 0x20] => (0xB1, 2) ; "zero page")]
+  #[test_case(&[0x9D, 0x00, 0x02, 0xFF] => (0x9D, 3) ; "absolute with trailing bytes")]
+  #[test_case(&[0xEA] => (0xEA, 1) ; "implied")]
+  #[test_case(&[0xA7, 0x10] => (0xA7, 2) ; "unofficial")]
+  fn decode(bytes: &[u8]) -> (u8, u16) {
+    let (operation, length) = Operation::decode(bytes, 0xC000).unwrap();
+
+    (operation.encode().unwrap()[0], length)
+  }
+
+  #[test]
+  fn decode_errors() {
+    assert!(matches!(
+      Operation::decode(&[0x02], 0xC000),
+      Err(Error::IllegalOpcode {
+        address: 0xC000,
+        opcode: 0x02,
+      })
+    ));
+    assert!(matches!(
+      Operation::decode(&[0x8D, 0x00], 0xC000),
+      Err(Error::UnmappedAddress(0xC002))
+    ));
+    assert!(matches!(
+      Operation::decode(&[], 0xC000),
+      Err(Error::UnmappedAddress(0xC000))
+    ));
+  }
+
+  #[test]
+  fn decode_from_bus() {
+    let mut bus = memory::bus::Flat::default();
+    // The operand wraps around the end of the address space
+    bus.write(0xFFFF, 0x6C).unwrap();
+    bus.write_u16(0x0000, 0x1234).unwrap();
+
+    let (operation, length) = Operation::decode_from(&bus, 0xFFFF).unwrap();
+
+    assert_eq!("JMP ($1234)", operation.to_string());
+    assert_eq!(3, length);
+  }
+
+  #[test]
+  fn unofficial_opcode_strict() {
+    let mut cpu = Cpu::default();
+    cpu.config.strict = true;
+    cpu.load(&[0xA7, 0x10]);
+    cpu.register.program_counter = memory::constant::PROGRAM_ROM_START;
+
+    let result = Operation::next(&mut cpu);
+
+    assert!(matches!(
+      result,
+      Err(Error::IllegalOpcode {
+        address: memory::constant::PROGRAM_ROM_START,
+        opcode: 0xA7,
+      })
+    ));
+  }
 }
@@ -0,0 +1,54 @@
+use super::{
+  addressing_mode::{Location, Value},
+  Operation,
+};
+
+impl Operation {
+  /// Number of cycles taken to execute the operation, from its opcode's entry in [`super::opcode::OPCODES`]
+  ///
+  /// This does not include the extra cycles taken when an indexed read crosses a page boundary or a branch is taken.
+  /// Operations which have no opcode, such as JMP with zero page addressing, take the minimum of 2 cycles.
+  #[must_use]
+  pub fn base_cycles(self) -> u8 {
+    self.opcode().map_or(2, |opcode| opcode.cycles)
+  }
+
+  /// The location read by the operation, if crossing a page boundary to reach it costs an extra cycle
+  #[must_use]
+  pub fn page_penalty_location(self) -> Option<Location> {
+    use Operation::*;
+    match self {
+      Adc(value) | And(value) | Cmp(value) | Eor(value) | Lda(value) | Ldx(value) | Ldy(value)
+      | Ora(value) | Sbc(value) | Lax(value) | Ign(value) => match value {
+        Value::Location(location) => Some(location),
+        Value::Immediate(_) => None,
+      },
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::{
+    super::addressing_mode::{Location::*, Value},
+    Operation::{self, *},
+  };
+
+  #[test_case(Lda(Value::Immediate(0)) => 2)]
+  #[test_case(Lda(Value::Location(XIndexedAbsolute(0))) => 4)]
+  #[test_case(Lda(Value::Location(IndirectYIndexed(0))) => 5)]
+  #[test_case(Sta(XIndexedAbsolute(0)) => 5)]
+  #[test_case(Sta(IndirectYIndexed(0)) => 6)]
+  #[test_case(Inc(XIndexedAbsolute(0)) => 7)]
+  #[test_case(Dcp(IndirectYIndexed(0)) => 8)]
+  #[test_case(Jmp(Absolute(0)) => 3)]
+  #[test_case(Jmp(Indirect(0)) => 5)]
+  #[test_case(Bne(Value::Location(Relative(0))) => 2)]
+  #[test_case(Brk => 7)]
+  fn base_cycles(operation: Operation) -> u8 {
+    operation.base_cycles()
+  }
+}
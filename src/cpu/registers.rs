@@ -10,7 +10,7 @@ pub struct Nes {
   pub status: StatusRegister,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 /// CPU status flags
 ///
 /// WARNING: some flags (especially N & Z) may be affected by commands in ways that do not follow their defined purpose.
@@ -24,18 +24,100 @@ pub struct StatusRegister {
   pub decimal_mode: NumberMode,
 }
 
-#[derive(Debug, Default)]
+impl StatusRegister {
+  pub const NEGATIVE: cpu::Int = 0b1000_0000;
+  pub const OVERFLOW: cpu::Int = 0b0100_0000;
+  /// Bit 5 is unused, and always reads as set
+  pub const UNUSED: cpu::Int = 0b0010_0000;
+  pub const BREAK: cpu::Int = 0b0001_0000;
+  pub const DECIMAL: cpu::Int = 0b0000_1000;
+  pub const INTERRUPT_DISABLE: cpu::Int = 0b0000_0100;
+  pub const ZERO: cpu::Int = 0b0000_0010;
+  pub const CARRY: cpu::Int = 0b0000_0001;
+
+  /// Packs the flags into the processor status layout `NV-BDIZC`
+  ///
+  /// The unused bit is always set, and the break bit is taken from [`InterruptStatus::break_command`].
+  #[must_use]
+  pub fn to_byte(self) -> cpu::Int {
+    let flags = [
+      (self.result_status.negative, Self::NEGATIVE),
+      (self.result_status.overflow, Self::OVERFLOW),
+      (true, Self::UNUSED),
+      (self.interrupt_status.break_command, Self::BREAK),
+      (
+        self.decimal_mode == NumberMode::BinaryCodedDecimal,
+        Self::DECIMAL,
+      ),
+      (self.interrupt_status.disabled, Self::INTERRUPT_DISABLE),
+      (self.result_status.zero, Self::ZERO),
+      (self.result_status.carry, Self::CARRY),
+    ];
+    flags
+      .into_iter()
+      .filter(|(set, _)| *set)
+      .fold(0, |byte, (_, bit)| byte | bit)
+  }
+
+  /// Unpacks flags from the processor status layout `NV-BDIZC`, including the break bit
+  #[must_use]
+  pub fn from_byte(byte: cpu::Int) -> Self {
+    Self {
+      result_status: ResultStatus {
+        zero: byte & Self::ZERO != 0,
+        negative: byte & Self::NEGATIVE != 0,
+        carry: byte & Self::CARRY != 0,
+        overflow: byte & Self::OVERFLOW != 0,
+      },
+      interrupt_status: InterruptStatus {
+        disabled: byte & Self::INTERRUPT_DISABLE != 0,
+        break_command: byte & Self::BREAK != 0,
+      },
+      decimal_mode: if byte & Self::DECIMAL == 0 {
+        NumberMode::Binary
+      } else {
+        NumberMode::BinaryCodedDecimal
+      },
+    }
+  }
+
+  /// Packs the flags as pushed to the stack
+  ///
+  /// The break bit is set when pushed by BRK or PHP, and clear when pushed by a hardware interrupt.
+  #[must_use]
+  pub fn to_stack_byte(self, break_command: bool) -> cpu::Int {
+    let byte = self.to_byte() & !Self::BREAK;
+    if break_command {
+      byte | Self::BREAK
+    } else {
+      byte
+    }
+  }
+
+  /// Restores the flags from a copy pulled from the stack by PLP or RTI
+  ///
+  /// The break and unused bits are ignored, as they do not exist in the CPU itself.
+  pub fn set_from_stack_byte(&mut self, byte: cpu::Int) {
+    let break_command = self.interrupt_status.break_command;
+    *self = Self::from_byte(byte);
+    self.interrupt_status.break_command = break_command;
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct InterruptStatus {
   /// Whether *maskable* interrupts should be disabled
   ///
   /// It can be explicitly set using the 'Set Interrupt Disable' (SEI) instruction and cleared with 'Clear Interrupt Disable' (CLI).
-  pub enabled: bool,
+  pub disabled: bool,
   /// Whether a BRK instruction has been executed and an interrupt has been generated to process it
+  ///
+  /// This flag does not exist in the CPU itself, only in copies of the status pushed to the stack.
   pub break_command: bool,
 }
 
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct ResultStatus {
   /// Whether result of last operation was zero
   pub zero: bool,
@@ -58,13 +140,48 @@ pub struct ResultStatus {
   pub overflow: bool,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum NumberMode {
+  #[default]
   Binary,
   BinaryCodedDecimal,
 }
-impl Default for NumberMode {
-  fn default() -> Self {
-    Self::Binary
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn byte_round_trip() {
+    for byte in 0..=u8::MAX {
+      let status = StatusRegister::from_byte(byte);
+
+      assert_eq!(byte | StatusRegister::UNUSED, status.to_byte());
+    }
+  }
+
+  #[test]
+  fn default_byte() {
+    assert_eq!(0b0010_0000, StatusRegister::default().to_byte());
+  }
+
+  #[test_case::test_case(true => 0b1011_0001)]
+  #[test_case::test_case(false => 0b1010_0001)]
+  fn stack_byte(break_command: bool) -> u8 {
+    let mut status = StatusRegister::default();
+    status.result_status.negative = true;
+    status.result_status.carry = true;
+
+    status.to_stack_byte(break_command)
+  }
+
+  #[test]
+  fn set_from_stack_byte_ignores_break() {
+    let mut status = StatusRegister::default();
+
+    status.set_from_stack_byte(0b1101_1111);
+
+    assert!(!status.interrupt_status.break_command);
+    assert_eq!(0b1110_1111, status.to_byte());
   }
 }
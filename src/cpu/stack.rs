@@ -0,0 +1,91 @@
+use super::{error::Error, Int};
+use crate::memory::{self, constant::STACK_START, Bus};
+
+/// Value of the stack pointer after a reset
+///
+/// The reset sequence performs three suppressed pushes from an initial stack pointer of zero.
+pub const STACK_POINTER_RESET: Int = 0xFD;
+
+impl<B: Bus> super::Nes<B> {
+  /// Address in page one of RAM that the stack pointer currently points to
+  #[must_use]
+  pub fn stack_address(&self) -> memory::Address {
+    STACK_START | memory::Address::from(self.register.stack_pointer)
+  }
+
+  /// Pushes a value onto the stack, wrapping within page one if the stack overflows
+  ///
+  /// # Errors
+  /// Forwards any error from writing to memory
+  pub fn push(&mut self, value: Int) -> Result<(), Error> {
+    self.memory.write(self.stack_address(), value)?;
+    self.register.stack_pointer = self.register.stack_pointer.wrapping_sub(1);
+    Ok(())
+  }
+
+  /// Pulls a value from the stack, wrapping within page one if the stack underflows
+  ///
+  /// # Errors
+  /// Forwards any error from reading memory
+  pub fn pull(&mut self) -> Result<Int, Error> {
+    self.register.stack_pointer = self.register.stack_pointer.wrapping_add(1);
+    self.memory.read(self.stack_address())
+  }
+
+  /// Pushes an address onto the stack, high byte first so it is stored little-endian
+  ///
+  /// # Errors
+  /// Forwards any error from writing to memory
+  pub fn push_u16(&mut self, value: memory::Address) -> Result<(), Error> {
+    let [low, high] = value.to_le_bytes();
+    self.push(high)?;
+    self.push(low)
+  }
+
+  /// Pulls an address from the stack
+  ///
+  /// # Errors
+  /// Forwards any error from reading memory
+  pub fn pull_u16(&mut self) -> Result<memory::Address, Error> {
+    let low = self.pull()?;
+    let high = self.pull()?;
+    Ok(memory::Address::from_le_bytes([low, high]))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{cpu::Cpu, memory::Bus};
+
+  #[test]
+  fn push_pull() {
+    let mut cpu = Cpu::default();
+    cpu.register.stack_pointer = super::STACK_POINTER_RESET;
+
+    cpu.push(0x12).unwrap();
+    cpu.push_u16(0x3456).unwrap();
+
+    assert_eq!(0xFA, cpu.register.stack_pointer);
+    assert_eq!(0x12, cpu.memory.read(0x01FD).unwrap());
+    assert_eq!(0x3456, cpu.memory.read_u16(0x01FB).unwrap());
+    assert_eq!(0x3456, cpu.pull_u16().unwrap());
+    assert_eq!(0x12, cpu.pull().unwrap());
+    assert_eq!(super::STACK_POINTER_RESET, cpu.register.stack_pointer);
+  }
+
+  #[test]
+  fn wraps_within_page_one() {
+    let mut cpu = Cpu::default();
+    cpu.register.stack_pointer = 0x00;
+
+    cpu.push(0x12).unwrap();
+    cpu.push(0x34).unwrap();
+
+    assert_eq!(0xFE, cpu.register.stack_pointer);
+    assert_eq!(0x12, cpu.memory.read(0x0100).unwrap());
+    assert_eq!(0x34, cpu.memory.read(0x01FF).unwrap());
+    assert_eq!(0x34, cpu.pull().unwrap());
+    assert_eq!(0x12, cpu.pull().unwrap());
+    assert_eq!(0x00, cpu.register.stack_pointer);
+  }
+}
@@ -0,0 +1,266 @@
+//! A whole console, which can be stepped by instruction, cycle or frame so the caller keeps control between steps
+
+use crate::{
+  apu::Apu,
+  cartridge::{self, Cartridge},
+  cpu::{self, error::Error},
+  input,
+  memory::{self, Bus},
+  ppu::Ppu,
+  trace::Tracer,
+};
+
+/// The CPU and everything on its bus, including the PPU, APU and cartridge
+///
+/// Nothing runs in the background: emulation only advances inside the stepping methods, which return once their
+/// condition is met, or early if the CPU halts.
+#[derive(Debug, Default)]
+pub struct Emulator {
+  pub cpu: cpu::Cpu,
+  cartridge: Option<Cartridge>,
+  tracer: Option<Tracer>,
+}
+
+impl Emulator {
+  /// Inserts a cartridge, mapping its memory into the address space
+  ///
+  /// # Errors
+  /// Returns [`cartridge::Error::UnsupportedMapper`] if the cartridge's board is not emulated
+  pub fn load_cartridge(&mut self, cartridge: Cartridge) -> Result<(), cartridge::Error> {
+    self.cpu.load_cartridge(&cartridge)?;
+    self.cartridge = Some(cartridge);
+    Ok(())
+  }
+
+  /// Loads a raw program image at the start of ROM, in place of any cartridge
+  pub fn load(&mut self, program: &[cpu::Int]) {
+    self.cpu.load(program);
+    self.cartridge = None;
+  }
+
+  #[must_use]
+  pub fn cartridge(&self) -> Option<&Cartridge> {
+    self.cartridge.as_ref()
+  }
+
+  #[must_use]
+  pub fn bus(&self) -> &memory::Nes {
+    &self.cpu.memory
+  }
+
+  #[must_use]
+  pub fn ppu(&self) -> &Ppu {
+    &self.cpu.memory.ppu
+  }
+
+  #[must_use]
+  pub fn apu(&self) -> &Apu {
+    &self.cpu.memory.apu
+  }
+
+  /// Mutable access to the APU, for changing the sample rate and draining samples
+  pub fn apu_mut(&mut self) -> &mut Apu {
+    &mut self.cpu.memory.apu
+  }
+
+  /// See [`Ppu::framebuffer`]
+  #[must_use]
+  pub fn framebuffer(&self) -> &[u8] {
+    self.ppu().framebuffer()
+  }
+
+  /// See [`cpu::Nes::connect`]
+  pub fn connect(&mut self, port: usize, device: Option<Box<dyn input::Device>>) {
+    self.cpu.connect(port, device);
+  }
+
+  /// See [`cpu::Nes::set_input`]
+  pub fn set_input(&mut self, port: usize, input: input::Input) {
+    self.cpu.set_input(port, input);
+  }
+
+  /// See [`cpu::Nes::set_buttons`]
+  pub fn set_buttons(&mut self, player: usize, buttons: input::Buttons) {
+    self.cpu.set_buttons(player, buttons);
+  }
+
+  /// Starts logging each instruction before it runs, or stops if given `None`, returning any previous tracer
+  pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+    std::mem::replace(&mut self.tracer, tracer)
+  }
+
+  /// Reads memory as the CPU sees it, without any side effects
+  ///
+  /// # Errors
+  /// Returns [`Error::UnmappedAddress`] if no device is attached at the address
+  pub fn peek(&self, address: memory::Address) -> Result<cpu::Int, Error> {
+    self.cpu.memory.peek(address)
+  }
+
+  /// Presses the reset button, jumping to the address in the reset vector
+  ///
+  /// # Errors
+  /// Returns an [`Error`] if the reset vector cannot be read
+  pub fn reset(&mut self) -> Result<(), Error> {
+    self.cpu.reset()
+  }
+
+  /// Runs a single instruction, or services an interrupt, returning the number of CPU cycles taken
+  ///
+  /// # Errors
+  /// Returns any [`Error`] that occurs during decoding or execution, or [`Error::Io`] if the trace cannot be written
+  pub fn step_instruction(&mut self) -> Result<u16, Error> {
+    match &mut self.tracer {
+      Some(tracer) => self.cpu.step_with(|cpu| Ok(tracer.trace(cpu)?)),
+      None => self.cpu.step(),
+    }
+  }
+
+  /// Runs whole instructions until at least the given number of CPU cycles have passed, returning the number
+  /// of cycles actually run
+  ///
+  /// # Errors
+  /// Returns any [`Error`] that occurs during decoding or execution
+  pub fn step_cycles(&mut self, cycles: u64) -> Result<u64, Error> {
+    let end = self.cpu.cycles + cycles;
+    self.run_until(|emulator| emulator.cpu.cycles >= end)
+  }
+
+  /// Runs until the PPU has finished drawing the current frame, returning the number of CPU cycles run
+  ///
+  /// The framebuffer then holds the whole frame.
+  ///
+  /// # Errors
+  /// Returns any [`Error`] that occurs during decoding or execution
+  pub fn run_frame(&mut self) -> Result<u64, Error> {
+    let frame = self.ppu().frame();
+    self.run_until(|emulator| emulator.ppu().frame() != frame)
+  }
+
+  /// Runs instructions until the predicate holds, checking it before each one, or until the CPU halts,
+  /// returning the number of CPU cycles run
+  ///
+  /// # Errors
+  /// Returns any [`Error`] that occurs during decoding or execution
+  pub fn run_until(&mut self, mut predicate: impl FnMut(&Self) -> bool) -> Result<u64, Error> {
+    let start = self.cpu.cycles;
+    while !self.cpu.is_halted() && !predicate(self) {
+      self.step_instruction()?;
+    }
+    Ok(self.cpu.cycles - start)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, io, rc::Rc};
+
+  use super::*;
+  use crate::{ppu, trace};
+
+  /// An emulator running a program from the start of ROM
+  fn emulator(program: &[cpu::Int]) -> Emulator {
+    let mut emulator = Emulator::default();
+    emulator.cpu.config.halt_on_break = true;
+    emulator.load(program);
+    emulator.reset().unwrap();
+    emulator
+  }
+
+  #[test]
+  fn step_instruction() {
+    // LDA #$01; STA $0200
+    let mut emulator = emulator(&[0xA9, 0x01, 0x8D, 0x00, 0x02]);
+
+    assert_eq!(2, emulator.step_instruction().unwrap());
+    assert_eq!(4, emulator.step_instruction().unwrap());
+    assert_eq!(0x01, emulator.peek(0x0200).unwrap());
+  }
+
+  #[test]
+  fn step_cycles_runs_whole_instructions() {
+    // JMP $8000
+    let mut emulator = emulator(&[0x4C, 0x00, 0x80]);
+
+    assert_eq!(9, emulator.step_cycles(7).unwrap());
+  }
+
+  #[test]
+  fn run_frame() {
+    let mut emulator = emulator(&[0x4C, 0x00, 0x80]);
+    emulator.run_frame().unwrap();
+
+    let cycles = emulator.run_frame().unwrap();
+
+    assert_eq!(2, emulator.ppu().frame());
+    let frame_cycles = u64::from(ppu::DOTS) * u64::from(ppu::SCANLINES) / 3;
+    assert!(cycles.abs_diff(frame_cycles) <= 3);
+  }
+
+  /// A trace log which can still be read after the tracer is handed to the emulator
+  #[derive(Clone, Default)]
+  struct Log(Rc<RefCell<Vec<u8>>>);
+
+  impl io::Write for Log {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn traces_each_instruction() {
+    let log = Log::default();
+    // LDA #$01; STA $0200
+    let mut emulator = emulator(&[0xA9, 0x01, 0x8D, 0x00, 0x02]);
+    emulator.set_tracer(Some(Tracer::new(log.clone(), trace::Format::Compact)));
+
+    emulator.step_instruction().unwrap();
+    emulator.step_instruction().unwrap();
+    emulator.set_tracer(None);
+    emulator.step_instruction().unwrap();
+
+    assert_eq!(
+      "8000 A901 00 00 00 24 FD 0 21 7\n8002 8D0002 01 00 00 24 FD 0 27 9\n",
+      String::from_utf8(log.0.take()).unwrap()
+    );
+  }
+
+  #[test]
+  fn does_not_trace_instructions_preempted_by_interrupts() {
+    let log = Log::default();
+    // LDA #$01; STA $0200, with an NMI handler at $0000 which starts with INX
+    let mut emulator = emulator(&[0xA9, 0x01, 0x8D, 0x00, 0x02]);
+    emulator.cpu.memory.ram[0] = 0xE8;
+    emulator.set_tracer(Some(Tracer::new(log.clone(), trace::Format::Compact)));
+
+    emulator.step_instruction().unwrap();
+    emulator.cpu.set_nmi(true);
+    emulator.step_instruction().unwrap();
+    emulator.step_instruction().unwrap();
+
+    let log = String::from_utf8(log.0.take()).unwrap();
+    let addresses: Vec<&str> = log
+      .lines()
+      .map(|line| line.split(' ').next().unwrap())
+      .collect();
+    assert_eq!(vec!["8000", "0000"], addresses);
+  }
+
+  #[test]
+  fn run_until_stops_on_halt() {
+    // INX; CPX #$05; BNE -5; BRK
+    let mut emulator = emulator(&[0xE8, 0xE0, 0x05, 0xD0, 0xFB, 0x00]);
+
+    emulator
+      .run_until(|emulator| emulator.cpu.register.index_x == 3)
+      .unwrap();
+    assert_eq!(3, emulator.cpu.register.index_x);
+
+    emulator.run_until(|_| false).unwrap();
+    assert!(emulator.cpu.is_halted());
+    assert_eq!(5, emulator.cpu.register.index_x);
+  }
+}
@@ -0,0 +1,106 @@
+//! The standard controller
+//!
+//! See <https://www.nesdev.org/wiki/Standard_controller>.
+
+use super::{Buttons, Device, Input, SERIAL_DATA, STROBE};
+use crate::ppu::Ppu;
+
+/// The standard controller, which latches its buttons into a shift register while strobed
+#[derive(Debug, Default)]
+pub struct Controller {
+  pub buttons: Buttons,
+  strobe: bool,
+  shift: u8,
+}
+
+impl Controller {
+  /// Writes the strobe bit, which reloads the shift register with the current buttons for as long as it is set
+  pub fn write(&mut self, data: u8) {
+    self.strobe = data & STROBE != 0;
+    if self.strobe {
+      self.shift = self.buttons.bits();
+    }
+  }
+
+  /// Reads the next button, after which an official controller reports every button as pressed
+  pub fn read(&mut self) -> u8 {
+    let data = self.peek();
+    if !self.strobe {
+      self.shift = (self.shift >> 1) | 0x80;
+    }
+    data
+  }
+
+  /// Reads the next button without shifting it out
+  #[must_use]
+  pub fn peek(&self) -> u8 {
+    let shift = if self.strobe {
+      self.buttons.bits()
+    } else {
+      self.shift
+    };
+    shift & SERIAL_DATA
+  }
+}
+
+impl Device for Controller {
+  fn write(&mut self, data: u8) {
+    Controller::write(self, data);
+  }
+
+  fn read(&mut self, _ppu: &Ppu) -> u8 {
+    Controller::read(self)
+  }
+
+  fn peek(&self, _ppu: &Ppu) -> u8 {
+    Controller::peek(self)
+  }
+
+  fn set_input(&mut self, input: Input) {
+    if let Input::Buttons { socket: 0, buttons } = input {
+      self.buttons = buttons;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::input::Button;
+
+  fn read_all(controller: &mut Controller) -> Vec<u8> {
+    (0..10).map(|_| controller.read()).collect()
+  }
+
+  #[test]
+  fn serial_read() {
+    let mut controller = Controller {
+      buttons: [Button::A, Button::Start, Button::Right]
+        .into_iter()
+        .collect(),
+      ..Controller::default()
+    };
+
+    controller.write(1);
+    controller.write(0);
+
+    assert_eq!(
+      vec![1, 0, 0, 1, 0, 0, 0, 1, 1, 1],
+      read_all(&mut controller)
+    );
+  }
+
+  #[test]
+  fn strobe_reports_a() {
+    let mut controller = Controller {
+      buttons: Buttons::from(Button::A),
+      ..Controller::default()
+    };
+
+    controller.write(1);
+
+    assert_eq!(vec![1; 10], read_all(&mut controller));
+    controller.buttons.set(Button::A, false);
+    assert_eq!(0, controller.read());
+  }
+}
@@ -0,0 +1,102 @@
+//! The Four Score adapter, which connects two controllers to each port for four-player games
+//!
+//! See <https://www.nesdev.org/wiki/Four_player_adapters>.
+
+use super::{Buttons, Device, Input, SERIAL_DATA, STROBE};
+use crate::ppu::Ppu;
+
+/// Reported after both controllers' buttons, so games can tell an adapter is connected
+const SIGNATURES: [u8; 2] = [0b0000_1000, 0b0000_0100];
+/// After the signature, every read reports 1
+const SHIFT_FILL: u32 = 0x80_0000;
+
+/// Half of a Four Score, handling the two sockets on one port
+///
+/// Players 1 and 3 are plugged into the half on the first port, and players 2 and 4 into the second.
+#[derive(Debug)]
+pub struct FourScore {
+  pub sockets: [Buttons; 2],
+  signature: u8,
+  strobe: bool,
+  shift: u32,
+}
+
+impl FourScore {
+  /// The half of the adapter for a port, 0 or 1
+  #[must_use]
+  pub fn new(port: usize) -> Self {
+    Self {
+      sockets: [Buttons::default(); 2],
+      signature: SIGNATURES[port % 2],
+      strobe: false,
+      shift: 0,
+    }
+  }
+
+  /// Both sockets' buttons followed by the signature, in the order they are reported
+  fn report(&self) -> u32 {
+    u32::from(self.sockets[0].bits())
+      | u32::from(self.sockets[1].bits()) << 8
+      | u32::from(self.signature) << 16
+  }
+}
+
+impl Device for FourScore {
+  fn write(&mut self, data: u8) {
+    self.strobe = data & STROBE != 0;
+    if self.strobe {
+      self.shift = self.report();
+    }
+  }
+
+  fn read(&mut self, ppu: &Ppu) -> u8 {
+    let data = self.peek(ppu);
+    if !self.strobe {
+      self.shift = (self.shift >> 1) | SHIFT_FILL;
+    }
+    data
+  }
+
+  fn peek(&self, _ppu: &Ppu) -> u8 {
+    let shift = if self.strobe {
+      self.report()
+    } else {
+      self.shift
+    };
+    u8::from(shift & u32::from(SERIAL_DATA) != 0)
+  }
+
+  fn set_input(&mut self, input: Input) {
+    if let Input::Buttons { socket, buttons } = input {
+      if let Some(socket) = self.sockets.get_mut(socket) {
+        *socket = buttons;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::input::Button;
+
+  #[test]
+  fn reports_both_sockets_and_signature() {
+    let ppu = Ppu::default();
+    let mut four_score = FourScore::new(1);
+    four_score.set_input(Input::Buttons {
+      socket: 1,
+      buttons: Buttons::from(Button::B),
+    });
+
+    four_score.write(1);
+    four_score.write(0);
+    let reads: Vec<u8> = (0..25).map(|_| four_score.read(&ppu)).collect();
+
+    let mut expected = vec![0; 24];
+    expected[9] = 1;
+    expected[18] = 1;
+    expected.push(1);
+    assert_eq!(expected, reads);
+  }
+}
@@ -0,0 +1,197 @@
+//! Input devices read by the CPU through the serial ports at $4016 and $4017
+//!
+//! See <https://www.nesdev.org/wiki/Input_devices>.
+
+pub mod controller;
+pub mod four_score;
+pub mod paddle;
+pub mod power_pad;
+pub mod zapper;
+
+use std::{fmt, ops::BitOr};
+
+use strum::{EnumIter, EnumString, IntoEnumIterator};
+
+pub use self::controller::Controller;
+use crate::{memory, ppu::Ppu};
+
+// Register numbers, as offsets from $4000
+/// Writing bit 0 strobes the devices in both ports, and reading returns the next bits from the first port
+pub const PORT_1: memory::Address = 0x16;
+/// Reading returns the next bits from the second port, while writes go to the APU's frame counter
+pub const PORT_2: memory::Address = 0x17;
+
+/// Bits of a port which are not driven by the devices, and so read back the high byte of the address
+pub const OPEN_BUS: u8 = 0x40;
+
+const STROBE: u8 = 0b0000_0001;
+/// Bit 0 of a port, which the standard controller shifts its buttons out on
+const SERIAL_DATA: u8 = 0b0000_0001;
+
+/// Something plugged into a controller port
+pub trait Device: fmt::Debug {
+  /// Receives the value written to $4016, which strobes the device in bit 0
+  fn write(&mut self, data: u8);
+
+  /// Reads the port's data lines D0-D4, moving on to the next bits of any serial data
+  ///
+  /// Light-sensing devices watch the picture being drawn by the PPU.
+  fn read(&mut self, ppu: &Ppu) -> u8;
+
+  /// Reads the port's data lines D0-D4 without any side effects
+  fn peek(&self, ppu: &Ppu) -> u8;
+
+  /// Updates the device from the host, which ignores input meant for other kinds of device
+  fn set_input(&mut self, input: Input);
+}
+
+/// The state of a device, as set by the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+  /// Buttons held down on a standard controller, or on the controller in one of a Four Score's sockets
+  Buttons { socket: usize, buttons: Buttons },
+  /// Where a Zapper is pointed on the screen, if anywhere, and whether its trigger is pulled
+  Zapper {
+    position: Option<(usize, usize)>,
+    trigger: bool,
+  },
+  /// Buttons held down on a Power Pad, with button 1 in bit 0 up to button 12 in bit 11
+  PowerPad(u16),
+  /// Position of an Arkanoid paddle's knob, and whether its button is pressed
+  Paddle { position: u8, button: bool },
+}
+
+/// The kinds of device which can be plugged into a port
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Kind {
+  None,
+  #[default]
+  Controller,
+  Zapper,
+  /// Half of a Four Score, which needs to be plugged into both ports
+  FourScore,
+  PowerPad,
+  Paddle,
+}
+
+impl Kind {
+  /// Creates a device of this kind for a port, 0 or 1, or `None` for an empty port
+  #[must_use]
+  pub fn device(self, port: usize) -> Option<Box<dyn Device>> {
+    match self {
+      Self::None => None,
+      Self::Controller => Some(Box::new(Controller::default())),
+      Self::Zapper => Some(Box::new(zapper::Zapper::default())),
+      Self::FourScore => Some(Box::new(four_score::FourScore::new(port))),
+      Self::PowerPad => Some(Box::new(power_pad::PowerPad::default())),
+      Self::Paddle => Some(Box::new(paddle::Paddle::default())),
+    }
+  }
+}
+
+/// A button on the standard controller, in the order they are reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Button {
+  A,
+  B,
+  Select,
+  Start,
+  Up,
+  Down,
+  Left,
+  Right,
+}
+
+impl Button {
+  fn mask(self) -> u8 {
+    1 << self as u8
+  }
+}
+
+/// The set of buttons held down on a controller
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Buttons(u8);
+
+impl fmt::Debug for Buttons {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_set()
+      .entries(Button::iter().filter(|&button| self.is_pressed(button)))
+      .finish()
+  }
+}
+
+impl Buttons {
+  #[must_use]
+  pub fn is_pressed(self, button: Button) -> bool {
+    self.0 & button.mask() != 0
+  }
+
+  pub fn set(&mut self, button: Button, pressed: bool) {
+    if pressed {
+      self.0 |= button.mask();
+    } else {
+      self.0 &= !button.mask();
+    }
+  }
+
+  /// One bit per button, in the order they are reported, with A in bit 0
+  #[must_use]
+  pub fn bits(self) -> u8 {
+    self.0
+  }
+}
+
+impl From<Button> for Buttons {
+  fn from(button: Button) -> Self {
+    Self(button.mask())
+  }
+}
+
+impl FromIterator<Button> for Buttons {
+  fn from_iter<I: IntoIterator<Item = Button>>(buttons: I) -> Self {
+    buttons
+      .into_iter()
+      .map(Self::from)
+      .fold(Self::default(), BitOr::bitor)
+  }
+}
+
+impl BitOr for Buttons {
+  type Output = Self;
+
+  fn bitor(self, other: Self) -> Self {
+    Self(self.0 | other.0)
+  }
+}
+
+impl BitOr<Button> for Buttons {
+  type Output = Self;
+
+  fn bitor(self, button: Button) -> Self {
+    self | Self::from(button)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn buttons_from_iterator() {
+    let buttons: Buttons = [Button::A, Button::Start, Button::Right]
+      .into_iter()
+      .collect();
+
+    assert_eq!(0b1000_1001, buttons.bits());
+    assert!(buttons.is_pressed(Button::Start));
+    assert!(!buttons.is_pressed(Button::B));
+  }
+
+  #[test]
+  fn kind_from_str() {
+    assert_eq!(Ok(Kind::FourScore), "four-score".parse());
+    assert!(Kind::None.device(0).is_none());
+  }
+}
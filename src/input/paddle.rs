@@ -0,0 +1,81 @@
+//! The Arkanoid paddle, a knob whose position is reported as a serial 8-bit value
+//!
+//! See <https://www.nesdev.org/wiki/Arkanoid_controller>.
+
+use super::{Device, Input, STROBE};
+use crate::ppu::Ppu;
+
+/// Serial position, most significant bit first and inverted
+const POSITION: u8 = 0b0001_0000;
+const BUTTON: u8 = 0b0000_1000;
+
+#[derive(Debug, Default)]
+pub struct Paddle {
+  /// Position of the knob, which ranges from about 98 to 242 on hardware
+  pub position: u8,
+  pub button: bool,
+  strobe: bool,
+  shift: u8,
+}
+
+impl Device for Paddle {
+  fn write(&mut self, data: u8) {
+    self.strobe = data & STROBE != 0;
+    if self.strobe {
+      self.shift = !self.position;
+    }
+  }
+
+  fn read(&mut self, ppu: &Ppu) -> u8 {
+    let data = self.peek(ppu);
+    if !self.strobe {
+      self.shift <<= 1;
+    }
+    data
+  }
+
+  fn peek(&self, _ppu: &Ppu) -> u8 {
+    let shift = if self.strobe {
+      !self.position
+    } else {
+      self.shift
+    };
+    let mut data = 0;
+    if shift & 0x80 != 0 {
+      data |= POSITION;
+    }
+    if self.button {
+      data |= BUTTON;
+    }
+    data
+  }
+
+  fn set_input(&mut self, input: Input) {
+    if let Input::Paddle { position, button } = input {
+      self.position = position;
+      self.button = button;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reports_inverted_position() {
+    let ppu = Ppu::default();
+    let mut paddle = Paddle::default();
+    paddle.set_input(Input::Paddle {
+      position: 0b1010_0000,
+      button: true,
+    });
+
+    paddle.write(1);
+    paddle.write(0);
+    let reads: Vec<u8> = (0..8).map(|_| paddle.read(&ppu)).collect();
+
+    // The position is on D4 and the button on D3
+    assert_eq!(vec![0x08, 0x18, 0x08, 0x18, 0x18, 0x18, 0x18, 0x18], reads);
+  }
+}
@@ -0,0 +1,95 @@
+//! The Power Pad floor mat, with twelve buttons reported on two serial lines
+//!
+//! See <https://www.nesdev.org/wiki/Power_Pad>.
+
+use super::{Device, Input, STROBE};
+use crate::ppu::Ppu;
+
+/// Buttons reported on D3, numbered from 1
+const D3_BUTTONS: [u16; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+/// Buttons reported on D4, numbered from 1, after which the line reads 1
+const D4_BUTTONS: [u16; 4] = [4, 3, 12, 8];
+
+const D3: u8 = 0b0000_1000;
+const D4: u8 = 0b0001_0000;
+
+#[derive(Debug, Default)]
+pub struct PowerPad {
+  /// Buttons held down, with button 1 in bit 0 up to button 12 in bit 11
+  pub buttons: u16,
+  strobe: bool,
+  /// Shift registers for D3 and D4
+  shift: (u8, u8),
+}
+
+impl PowerPad {
+  /// The buttons latched into each shift register, in the order they are reported
+  fn report(&self) -> (u8, u8) {
+    let latch = |buttons: &[u16]| {
+      buttons
+        .iter()
+        .enumerate()
+        .filter(|&(_, &button)| self.buttons & (1 << (button - 1)) != 0)
+        .fold(0, |shift, (bit, _)| shift | 1 << bit)
+    };
+    (latch(&D3_BUTTONS), latch(&D4_BUTTONS) | 0xF0)
+  }
+}
+
+impl Device for PowerPad {
+  fn write(&mut self, data: u8) {
+    self.strobe = data & STROBE != 0;
+    if self.strobe {
+      self.shift = self.report();
+    }
+  }
+
+  fn read(&mut self, ppu: &Ppu) -> u8 {
+    let data = self.peek(ppu);
+    if !self.strobe {
+      self.shift = ((self.shift.0 >> 1) | 0x80, (self.shift.1 >> 1) | 0x80);
+    }
+    data
+  }
+
+  fn peek(&self, _ppu: &Ppu) -> u8 {
+    let (d3, d4) = if self.strobe {
+      self.report()
+    } else {
+      self.shift
+    };
+    let mut data = 0;
+    if d3 & 1 != 0 {
+      data |= D3;
+    }
+    if d4 & 1 != 0 {
+      data |= D4;
+    }
+    data
+  }
+
+  fn set_input(&mut self, input: Input) {
+    if let Input::PowerPad(buttons) = input {
+      self.buttons = buttons;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reports_buttons_on_both_lines() {
+    let ppu = Ppu::default();
+    let mut power_pad = PowerPad::default();
+    // Buttons 1 and 12
+    power_pad.set_input(Input::PowerPad(0b1000_0000_0001));
+
+    power_pad.write(1);
+    power_pad.write(0);
+    let reads: Vec<u8> = (0..9).map(|_| power_pad.read(&ppu)).collect();
+
+    assert_eq!(vec![0, D3, D4, 0, D4, D4, D4, D4, D3 | D4], reads);
+  }
+}
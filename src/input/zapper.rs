@@ -0,0 +1,95 @@
+//! The Zapper light gun, which senses light from the part of the screen it is pointed at
+//!
+//! See <https://www.nesdev.org/wiki/Zapper>.
+
+use super::{Device, Input};
+use crate::ppu::{self, Ppu};
+
+/// Cleared while light is sensed
+const LIGHT_NOT_SENSED: u8 = 0b0000_1000;
+const TRIGGER: u8 = 0b0001_0000;
+
+/// Scanlines for which the screen stays bright enough to sense after the beam draws a pixel
+const PERSISTENCE_SCANLINES: u16 = 20;
+/// Minimum average of a pixel's RGB components for it to be sensed
+const BRIGHTNESS_THRESHOLD: u16 = 0x55;
+
+#[derive(Debug, Default)]
+pub struct Zapper {
+  /// Pixel the Zapper is pointed at, or `None` if pointed away from the screen
+  pub position: Option<(usize, usize)>,
+  pub trigger: bool,
+}
+
+impl Device for Zapper {
+  fn write(&mut self, _data: u8) {}
+
+  fn read(&mut self, ppu: &Ppu) -> u8 {
+    self.peek(ppu)
+  }
+
+  fn peek(&self, ppu: &Ppu) -> u8 {
+    let lit = self.position.is_some_and(|(x, y)| {
+      x < ppu::WIDTH
+        && y < ppu::HEIGHT
+        && senses_light((x, y), ppu.scanline(), ppu.dot(), ppu.pixel(x, y))
+    });
+
+    let mut data = 0;
+    if !lit {
+      data |= LIGHT_NOT_SENSED;
+    }
+    if self.trigger {
+      data |= TRIGGER;
+    }
+    data
+  }
+
+  fn set_input(&mut self, input: Input) {
+    if let Input::Zapper { position, trigger } = input {
+      self.position = position;
+      self.trigger = trigger;
+    }
+  }
+}
+
+/// Whether a pixel is bright, and was drawn recently enough for the screen to still be glowing there
+fn senses_light((x, y): (usize, usize), scanline: u16, dot: u16, colour: [u8; 3]) -> bool {
+  let scanline = usize::from(scanline);
+  // The pixel at x is drawn on dot x + 1
+  let drawn = scanline > y || (scanline == y && usize::from(dot) > x + 1);
+  let recent = scanline <= y + usize::from(PERSISTENCE_SCANLINES);
+  let brightness = colour.iter().copied().map(u16::from).sum::<u16>() / 3;
+
+  drawn && recent && brightness >= BRIGHTNESS_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+
+  const WHITE: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+  #[test_case(10, 100, WHITE => true ; "just drawn")]
+  #[test_case(10, 5, WHITE => false ; "not yet drawn")]
+  #[test_case(40, 100, WHITE => false ; "faded")]
+  #[test_case(10, 100, [0x20, 0x20, 0x20] => false ; "dark")]
+  fn light_sensing(scanline: u16, dot: u16, colour: [u8; 3]) -> bool {
+    senses_light((50, 10), scanline, dot, colour)
+  }
+
+  #[test]
+  fn trigger_without_light() {
+    let ppu = Ppu::default();
+    let mut zapper = Zapper::default();
+
+    zapper.set_input(Input::Zapper {
+      position: Some((0, 0)),
+      trigger: true,
+    });
+
+    assert_eq!(LIGHT_NOT_SENSED | TRIGGER, zapper.read(&ppu));
+  }
+}
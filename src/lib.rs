@@ -7,10 +7,16 @@
 // #![warn(clippy::missing_docs_in_private_items)]
 #![warn(clippy::shadow_unrelated)]
 #![warn(clippy::str_to_string)]
-#![warn(clippy::string_to_string)]
 #![warn(clippy::unneeded_field_pattern)]
 #![warn(clippy::unwrap_in_result)]
 // #![warn(clippy::unwrap_used)]
 
+pub mod apu;
+pub mod cartridge;
 pub mod cpu;
+pub mod emulator;
+pub mod input;
 pub mod memory;
+pub mod ppu;
+pub mod screenshot;
+pub mod trace;
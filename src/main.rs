@@ -7,20 +7,29 @@
 // #![warn(clippy::missing_docs_in_private_items)]
 #![warn(clippy::shadow_unrelated)]
 #![warn(clippy::str_to_string)]
-#![warn(clippy::string_to_string)]
 #![warn(clippy::unneeded_field_pattern)]
 #![warn(clippy::unwrap_in_result)]
 // #![warn(clippy::unwrap_used)]
 
-use std::fs::File;
+use std::{
+  fs::File,
+  io::{self, BufWriter, Read, Write},
+};
 
 use clap::Parser;
 use env_logger::Builder;
 use log::LevelFilter;
 
+pub mod apu;
+pub mod cartridge;
 mod cli;
 pub mod cpu;
+pub mod emulator;
+pub mod input;
 pub mod memory;
+pub mod ppu;
+pub mod screenshot;
+pub mod trace;
 
 fn main() -> anyhow::Result<()> {
   let args = cli::Cli::parse();
@@ -34,17 +43,94 @@ fn main() -> anyhow::Result<()> {
 
   log_builder.init();
 
-  let mut cpu: cpu::Cpu = cpu::Cpu::default();
+  let mut emulator = emulator::Emulator::default();
+  emulator.cpu.config.strict = args.strict;
+  emulator.cpu.config.halt_on_break = args.halt_on_break;
+  emulator.cpu.config.variant = args.variant;
+  emulator.connect(0, args.port_1.device(0));
+  emulator.connect(1, args.port_2.device(1));
 
   if let Some(path) = &args.file {
-    let mut file = File::open(&path)?;
-    cpu.load_from(&mut file)?;
-  };
+    let mut file = File::open(path)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let is_source = path.extension().is_some_and(|extension| {
+      extension.eq_ignore_ascii_case("asm") || extension.eq_ignore_ascii_case("s")
+    });
+    if is_source {
+      let program = cpu::operation::assemble::assemble(&String::from_utf8(bytes)?)?;
+      let rom = program.rom().ok_or_else(|| {
+        anyhow::anyhow!(
+          "program starts at {:#06X}, but should be assembled into ROM",
+          program.origin
+        )
+      })?;
+      emulator.load(&rom);
+    } else if bytes.starts_with(&cartridge::MAGIC) {
+      emulator.load_cartridge(cartridge::Cartridge::from_bytes(&bytes)?)?;
+    } else {
+      emulator.cpu.load_from(&mut bytes.as_slice())?;
+    }
+  }
+
+  if let Some(range) = args.disassemble {
+    let start = *range.start();
+    let bytes = range
+      .map(|address| emulator.peek(address))
+      .collect::<Result<Vec<_>, _>>()?;
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for line in cpu::operation::disassemble::disassemble(&bytes, start) {
+      writeln!(stdout, "{line}")?;
+    }
+    stdout.flush()?;
+    return Ok(());
+  }
 
-  match args.start_address {
-    None => cpu.start()?,
-    Some(address) => cpu.start_from(address)?,
+  let screenshot = match &args.screenshot {
+    None => None,
+    Some(path) => Some((
+      path,
+      screenshot::Format::from_path(path).ok_or_else(|| {
+        anyhow::anyhow!("screenshot {path:?} should have a .png or .ppm extension")
+      })?,
+    )),
   };
 
+  emulator.reset()?;
+  if let Some(address) = args.start_address {
+    emulator.cpu.register.program_counter = address;
+  }
+
+  if let Some(path) = &args.trace {
+    let writer = BufWriter::new(File::create(path)?);
+    emulator.set_tracer(Some(trace::Tracer::new(writer, args.trace_format)));
+  }
+
+  let last_frame = args.frames.map(|frames| emulator.ppu().frame() + frames);
+  let mut condition_error = None;
+  emulator.run_until(|emulator| {
+    if last_frame.is_some_and(|last_frame| emulator.ppu().frame() >= last_frame) {
+      return true;
+    }
+    args.until.as_ref().is_some_and(|condition| {
+      cli::condition_holds(condition, emulator).unwrap_or_else(|err| {
+        condition_error = Some(err);
+        true
+      })
+    })
+  })?;
+  if let Some(mut tracer) = emulator.set_tracer(None) {
+    tracer.flush()?;
+  }
+  if let Some(err) = condition_error {
+    return Err(err.into());
+  }
+
+  if let Some((path, format)) = screenshot {
+    let mut file = BufWriter::new(File::create(path)?);
+    screenshot::write(&mut file, format, emulator.framebuffer())?;
+    file.flush()?;
+  }
+
   Ok(())
 }
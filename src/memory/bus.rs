@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::{
+  cpu::{self, error::Error},
+  memory::Address,
+};
+
+/// The CPU's view of the address space
+///
+/// Reads may have side effects on the device behind an address, such as acknowledging a status flag or
+/// shifting out a controller bit, so [`Bus::peek`] is provided for inspecting memory without disturbing it.
+pub trait Bus: fmt::Debug {
+  /// # Errors
+  /// Returns [`Error::UnmappedAddress`] if no device is attached at the address
+  fn read(&mut self, address: Address) -> Result<cpu::Int, Error>;
+
+  /// # Errors
+  /// Returns [`Error::UnmappedAddress`] if no device is attached at the address
+  fn write(&mut self, address: Address, data: cpu::Int) -> Result<(), Error>;
+
+  /// Reads without any side effects
+  ///
+  /// # Errors
+  /// Returns [`Error::UnmappedAddress`] if no device is attached at the address
+  fn peek(&self, address: Address) -> Result<cpu::Int, Error>;
+
+  /// Advances the devices on the bus by a number of CPU cycles
+  ///
+  /// Returns the number of extra cycles the CPU was stalled for while a device used the bus, such as for DMA.
+  fn tick(&mut self, _cycles: u8) -> u16 {
+    0
+  }
+
+  /// Whether a device on the bus is asserting the CPU's NMI line
+  fn nmi(&self) -> bool {
+    false
+  }
+
+  /// Whether a device on the bus is asserting the CPU's IRQ line
+  fn irq(&self) -> bool {
+    false
+  }
+
+  /// Reads a little-endian word, wrapping at the end of the address space
+  ///
+  /// # Errors
+  /// See [`Bus::read`]
+  fn read_u16(&mut self, address: Address) -> Result<u16, Error> {
+    Ok(u16::from_le_bytes([
+      self.read(address)?,
+      self.read(address.wrapping_add(1))?,
+    ]))
+  }
+
+  /// Writes a little-endian word, wrapping at the end of the address space
+  ///
+  /// # Errors
+  /// See [`Bus::write`]
+  fn write_u16(&mut self, address: Address, data: u16) -> Result<(), Error> {
+    let [fst, snd] = u16::to_le_bytes(data);
+    self.write(address, fst)?;
+    self.write(address.wrapping_add(1), snd)
+  }
+
+  /// Reads a little-endian word without any side effects
+  ///
+  /// # Errors
+  /// See [`Bus::peek`]
+  fn peek_u16(&self, address: Address) -> Result<u16, Error> {
+    Ok(u16::from_le_bytes([
+      self.peek(address)?,
+      self.peek(address.wrapping_add(1))?,
+    ]))
+  }
+}
+
+/// 64 KiB of plain RAM covering the whole address space, with no devices or mirroring
+///
+/// This is useful for testing the CPU in isolation and for running generic 6502 programs.
+pub struct Flat {
+  pub memory: Box<[cpu::Int]>,
+}
+
+impl fmt::Debug for Flat {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Flat")
+      .field("memory", &format_args!("{:X?}", &self.memory))
+      .finish()
+  }
+}
+
+impl Default for Flat {
+  fn default() -> Self {
+    Flat {
+      memory: vec![0; usize::from(Address::MAX) + 1].into_boxed_slice(),
+    }
+  }
+}
+
+impl Bus for Flat {
+  fn read(&mut self, address: Address) -> Result<cpu::Int, Error> {
+    self.peek(address)
+  }
+
+  fn write(&mut self, address: Address, data: cpu::Int) -> Result<(), Error> {
+    self.memory[usize::from(address)] = data;
+    Ok(())
+  }
+
+  fn peek(&self, address: Address) -> Result<cpu::Int, Error> {
+    Ok(self.memory[usize::from(address)])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn flat_covers_address_space() {
+    let mut bus = Flat::default();
+
+    bus.write(0xFFFF, 0x12).unwrap();
+    bus.write(0x2000, 0x34).unwrap();
+
+    assert_eq!(0x12, bus.read(0xFFFF).unwrap());
+    assert_eq!(0x34, bus.peek(0x2000).unwrap());
+    assert_eq!(0x00, bus.read(0x2008).unwrap());
+  }
+
+  #[test]
+  fn read_u16_wraps() {
+    let mut bus = Flat::default();
+
+    bus.write_u16(0xFFFF, 0x1234).unwrap();
+
+    assert_eq!(0x34, bus.peek(0xFFFF).unwrap());
+    assert_eq!(0x12, bus.peek(0x0000).unwrap());
+    assert_eq!(0x1234, bus.read_u16(0xFFFF).unwrap());
+  }
+}
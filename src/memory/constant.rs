@@ -5,17 +5,39 @@ use crate::memory::Address;
 pub const RAM_START: Address = 0x0000;
 pub const RAM_SIZE: Address = 0x0800;
 pub const RAM_END: Address = RAM_START + RAM_SIZE;
+/// RAM is mirrored every [`RAM_SIZE`] bytes up to this address
+pub const RAM_MIRRORS_END: Address = 0x2000;
+/// The stack occupies page one of RAM, with the stack pointer as the offset into it
+pub const STACK_START: Address = 0x0100;
+pub const PPU_REGISTERS_START: Address = 0x2000;
+pub const PPU_REGISTERS_SIZE: Address = 0x0008;
+/// PPU registers are mirrored every [`PPU_REGISTERS_SIZE`] bytes up to this address
+pub const PPU_REGISTERS_MIRRORS_END: Address = 0x4000;
+/// APU and I/O registers, including the disabled APU test registers at $4018-$401F
+pub const APU_IO_REGISTERS_START: Address = 0x4000;
+pub const APU_IO_REGISTERS_END: Address = 0x4020;
+/// Writing a page number here copies that page of memory into the PPU's object attribute memory
+pub const OAM_DMA: Address = 0x4014;
+/// Cartridge expansion area, unused by most boards
+pub const EXPANSION_START: Address = 0x4020;
+pub const EXPANSION_END: Address = 0x6000;
+/// Cartridge program RAM (also known as work RAM or save RAM)
+pub const PROGRAM_RAM_START: Address = 0x6000;
+pub const PROGRAM_RAM_SIZE: Address = 0x2000;
+pub const PROGRAM_RAM_END: Address = PROGRAM_RAM_START + PROGRAM_RAM_SIZE;
+/// Trainers are loaded into program RAM at this address
+pub const TRAINER_START: Address = 0x7000;
 pub const PROGRAM_ROM_START: Address = 0x8000;
 pub const PROGRAM_ROM_SIZE: Address = 0x8000; // ROM runs to end of memory (0xFFFF inclusive)
 
 /// Contains address of non-maskable interrupt handler
-/// Value is u16, so should be read with [`crate::memory::Nes::read_u16`]
+/// Value is u16, so should be read with [`crate::memory::Bus::read_u16`]
 pub const NON_MASKABLE_INTERRUPT: Address = 0xFFFA;
 
 /// Contains address to set program counter to on reset interrupt or load
-/// This is a u16 value, so should be read with [`crate::memory::Nes::read_u16`]
+/// This is a u16 value, so should be read with [`crate::memory::Bus::read_u16`]
 pub const PROGRAM_COUNTER_RESET: Address = 0xFFFC;
 
 /// Contains address of (maskable) interrupt handler, also triggered by BRK instruction
-/// Value is u16, so should be read with [`crate::memory::Nes::read_u16`]
+/// Value is u16, so should be read with [`crate::memory::Bus::read_u16`]
 pub const INTERRUPT: Address = 0xFFFE;
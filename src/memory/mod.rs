@@ -1,105 +1,354 @@
-use std::{
-  fmt,
-  ops::{Index, IndexMut},
-};
+use std::fmt;
 
-use crate::cpu;
+use crate::{
+  apu::{self, Apu},
+  cartridge::mapper::{self, Mapper},
+  cpu::{self, error::Error},
+  input::{self, Device},
+  ppu::{self, Ppu},
+};
 
+pub mod bus;
 pub mod constant;
 
-#[derive(Debug)]
+pub use bus::Bus;
+
+/// A region of the CPU address space, with the offset of an address within it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Location {
-  ProgramRom(Address),
+  /// Internal RAM, with mirrors folded onto the same offset
   Ram(Address),
+  /// PPU register number 0-7, with mirrors folded onto the same register
+  PpuRegister(Address),
+  /// APU and I/O registers
+  ApuIoRegister(Address),
+  /// Cartridge expansion area
+  Expansion(Address),
+  ProgramRam(Address),
+  ProgramRom(Address),
 }
 
 pub type Address = u16;
 
 pub struct Nes {
-  pub program_rom: [cpu::Int; constant::PROGRAM_ROM_SIZE as usize],
   pub ram: [cpu::Int; constant::RAM_SIZE as usize],
+  /// The cartridge board, which handles the expansion area, program RAM and program ROM
+  pub mapper: Box<dyn Mapper>,
+  pub ppu: Ppu,
+  pub apu: Apu,
+  /// Devices plugged into the first and second controller ports
+  pub ports: [Option<Box<dyn Device>>; 2],
+  /// Number of CPU cycles the bus has been run for
+  cycles: u64,
+  /// CPU cycles taken by DMA since the bus was last run
+  stall: u16,
 }
 
 impl fmt::Debug for Nes {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     f.debug_struct("Cpu6502")
-      .field("program_rom", &format_args!("{:X?}", &self.program_rom))
       .field("ram", &format_args!("{:X?}", &self.ram))
+      .field("mapper", &self.mapper)
+      .field("ppu", &self.ppu)
+      .field("apu", &self.apu)
+      .field("ports", &self.ports)
+      .field("cycles", &self.cycles)
+      .field("stall", &self.stall)
       .finish()
   }
 }
 
 impl Nes {
+  /// Finds the region of the address space which an address belongs to
   #[must_use]
-  /// # Panics
-  /// This function will panic if it receives an address that is outside of the known memory region ranges
   pub fn resolve_address(address: Address) -> Location {
     use crate::memory::{
-      constant::{PROGRAM_ROM_START, RAM_END, RAM_START},
+      constant::{
+        APU_IO_REGISTERS_END, APU_IO_REGISTERS_START, EXPANSION_END, EXPANSION_START,
+        PPU_REGISTERS_MIRRORS_END, PPU_REGISTERS_SIZE, PPU_REGISTERS_START, PROGRAM_RAM_END,
+        PROGRAM_RAM_START, PROGRAM_ROM_START, RAM_MIRRORS_END, RAM_SIZE, RAM_START,
+      },
       Location::*,
     };
 
     match address {
-      RAM_START.. if address < RAM_END => Ram(address - RAM_START),
-      constant::PROGRAM_ROM_START.. => ProgramRom(address - PROGRAM_ROM_START),
-      _ => todo!("Memory location {:#4X}", address),
+      RAM_START..RAM_MIRRORS_END => Ram((address - RAM_START) % RAM_SIZE),
+      PPU_REGISTERS_START..PPU_REGISTERS_MIRRORS_END => {
+        PpuRegister((address - PPU_REGISTERS_START) % PPU_REGISTERS_SIZE)
+      }
+      APU_IO_REGISTERS_START..APU_IO_REGISTERS_END => {
+        ApuIoRegister(address - APU_IO_REGISTERS_START)
+      }
+      EXPANSION_START..EXPANSION_END => Expansion(address - EXPANSION_START),
+      PROGRAM_RAM_START..PROGRAM_RAM_END => ProgramRam(address - PROGRAM_RAM_START),
+      PROGRAM_ROM_START..=Address::MAX => ProgramRom(address - PROGRAM_ROM_START),
     }
   }
 
-  #[must_use]
-  pub fn read(&self, addr: u16) -> cpu::Int {
-    self[addr]
+  fn read_port(&mut self, port: usize) -> cpu::Int {
+    let data = self.ports[port]
+      .as_mut()
+      .map_or(0, |device| device.read(&self.ppu));
+    input::OPEN_BUS | data
   }
 
-  pub fn write(&mut self, addr: u16, data: cpu::Int) {
-    self[addr] = data;
+  fn peek_port(&self, port: usize) -> cpu::Int {
+    let data = self.ports[port]
+      .as_ref()
+      .map_or(0, |device| device.peek(&self.ppu));
+    input::OPEN_BUS | data
   }
 
-  #[must_use]
-  pub fn read_u16(&self, addr: u16) -> u16 {
-    u16::from_le_bytes([self[addr], self[addr + 1]])
+  /// Copies a page of memory into OAM, stalling the CPU while it does
+  ///
+  /// # Errors
+  /// Forwards any error from reading the page
+  fn oam_dma(&mut self, page: cpu::Int) -> Result<(), Error> {
+    let start = Address::from_le_bytes([0, page]);
+    for offset in 0..=0xFF {
+      let data = self.read(start | offset)?;
+      self
+        .ppu
+        .write_register(ppu::OAM_DATA, data, self.mapper.as_mut());
+    }
+    // The transfer takes 512 cycles, after waiting for a read cycle to align with
+    self.stall += 513 + u16::from(self.cycles % 2 == 1);
+    Ok(())
+  }
+}
+
+impl Bus for Nes {
+  fn read(&mut self, address: Address) -> Result<cpu::Int, Error> {
+    use crate::memory::Location::*;
+
+    match Self::resolve_address(address) {
+      PpuRegister(register) => Ok(self.ppu.read_register(register, self.mapper.as_mut())),
+      ApuIoRegister(apu::STATUS) => Ok(self.apu.read_status()),
+      ApuIoRegister(input::PORT_1) => Ok(self.read_port(0)),
+      ApuIoRegister(input::PORT_2) => Ok(self.read_port(1)),
+      _ => self.peek(address),
+    }
   }
 
-  pub fn write_u16(&mut self, addr: u16, data: u16) {
-    let [fst, snd] = u16::to_le_bytes(data);
-    self[addr] = fst;
-    self[addr] = snd;
+  fn write(&mut self, address: Address, data: cpu::Int) -> Result<(), Error> {
+    use crate::memory::Location::*;
+
+    match Self::resolve_address(address) {
+      Ram(offset) => self.ram[offset as usize] = data,
+      PpuRegister(register) => self
+        .ppu
+        .write_register(register, data, self.mapper.as_mut()),
+      ApuIoRegister(_) if address == constant::OAM_DMA => self.oam_dma(data)?,
+      ApuIoRegister(input::PORT_1) => {
+        for device in self.ports.iter_mut().flatten() {
+          device.write(data);
+        }
+      }
+      ApuIoRegister(register) => self.apu.write_register(register, data),
+      Expansion(_) | ProgramRam(_) | ProgramRom(_) => self.mapper.write_program(address, data),
+    }
+    Ok(())
+  }
+
+  fn peek(&self, address: Address) -> Result<cpu::Int, Error> {
+    use crate::memory::Location::*;
+
+    match Self::resolve_address(address) {
+      Ram(offset) => Ok(self.ram[offset as usize]),
+      PpuRegister(register) => Ok(self.ppu.peek_register(register, self.mapper.as_ref())),
+      // Nothing on the cartridge drives the data bus, so it keeps the high byte of the address
+      Expansion(_) | ProgramRam(_) | ProgramRom(_) => Ok(
+        self
+          .mapper
+          .read_program(address)
+          .unwrap_or(address.to_le_bytes()[1]),
+      ),
+      ApuIoRegister(apu::STATUS) => Ok(self.apu.peek_status()),
+      ApuIoRegister(input::PORT_1) => Ok(self.peek_port(0)),
+      ApuIoRegister(input::PORT_2) => Ok(self.peek_port(1)),
+      // Write-only and unused registers leave the high byte of the address on the data bus
+      ApuIoRegister(_) => Ok(address.to_le_bytes()[1]),
+    }
+  }
+
+  fn tick(&mut self, cycles: u8) -> u16 {
+    let mut stall = std::mem::take(&mut self.stall);
+    let mut remaining = u16::from(cycles) + stall;
+    while remaining > 0 {
+      for _ in 0..ppu::DOTS_PER_CYCLE {
+        self.ppu.tick(self.mapper.as_mut());
+      }
+      // Cycles stolen by the DMC also need to be run
+      let dma = self.apu.tick(self.mapper.as_ref());
+      stall += dma;
+      remaining += dma;
+
+      remaining -= 1;
+      self.cycles += 1;
+    }
+    stall
+  }
+
+  fn nmi(&self) -> bool {
+    self.ppu.nmi()
+  }
+
+  fn irq(&self) -> bool {
+    self.mapper.irq() || self.apu.irq()
   }
 }
 
 impl Default for Nes {
   #[inline]
   fn default() -> Self {
-    #[allow(clippy::uninit_assumed_init)] // No guarantees that emulator memory is initialised
     Nes {
-      program_rom: [0; constant::PROGRAM_ROM_SIZE as usize],
       ram: [0; constant::RAM_SIZE as usize],
+      mapper: Box::new(mapper::nrom::Nrom::new(mapper::Board::with_program_rom(
+        vec![0; constant::PROGRAM_ROM_SIZE as usize],
+      ))),
+      ppu: Ppu::default(),
+      apu: Apu::default(),
+      ports: [0, 1].map(|port| input::Kind::Controller.device(port)),
+      cycles: 0,
+      stall: 0,
     }
   }
 }
 
-impl Index<Address> for Nes {
-  type Output = cpu::Int;
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-  fn index(&self, address: Address) -> &Self::Output {
-    use crate::memory::Location::*;
+  #[test_case::test_case(0x0000 => Location::Ram(0x0000))]
+  #[test_case::test_case(0x1FFF => Location::Ram(0x07FF))]
+  #[test_case::test_case(0x2000 => Location::PpuRegister(0))]
+  #[test_case::test_case(0x3FFE => Location::PpuRegister(6))]
+  #[test_case::test_case(0x4014 => Location::ApuIoRegister(0x14))]
+  #[test_case::test_case(0x401F => Location::ApuIoRegister(0x1F))]
+  #[test_case::test_case(0x4020 => Location::Expansion(0x0000))]
+  #[test_case::test_case(0x6000 => Location::ProgramRam(0x0000))]
+  #[test_case::test_case(0x7FFF => Location::ProgramRam(0x1FFF))]
+  #[test_case::test_case(0xFFFF => Location::ProgramRom(0x7FFF))]
+  fn resolve_address(address: Address) -> Location {
+    Nes::resolve_address(address)
+  }
 
-    let location = Self::resolve_address(address);
-    match location {
-      Ram(ram_address) => &self.ram[ram_address as usize],
-      ProgramRom(rom_address) => &self.program_rom[rom_address as usize],
-    }
+  #[test]
+  fn ram_mirrors() {
+    let mut memory = Nes::default();
+
+    memory.write(0x0801, 0x12).unwrap();
+
+    assert_eq!(0x12, memory.read(0x0001).unwrap());
+    assert_eq!(0x12, memory.read(0x1801).unwrap());
   }
-}
 
-impl IndexMut<Address> for Nes {
-  fn index_mut(&mut self, address: Address) -> &mut Self::Output {
-    use crate::memory::Location::*;
+  #[test]
+  fn program_ram() {
+    let mut memory = Nes::default();
+
+    memory.write(0x6123, 0x12).unwrap();
+
+    assert_eq!(0x12, memory.read(0x6123).unwrap());
+    assert_eq!(0x00, memory.read(0x6124).unwrap());
+  }
+
+  #[test]
+  fn program_rom_is_read_only() {
+    let mut memory = Nes::default();
+
+    memory.write(0x8000, 0x12).unwrap();
+
+    assert_eq!(0x00, memory.read(0x8000).unwrap());
+  }
 
-    let location = Self::resolve_address(address);
-    match location {
-      Ram(ram_address) => &mut self.ram[ram_address as usize],
-      ProgramRom(rom_address) => &mut self.program_rom[rom_address as usize],
+  #[test_case::test_case(0x4000)]
+  #[test_case::test_case(0x4014)]
+  #[test_case::test_case(0x4018)]
+  #[test_case::test_case(0x401F)]
+  fn open_bus(address: Address) {
+    let mut memory = Nes::default();
+
+    assert_eq!(0x40, memory.read(address).unwrap());
+    assert_eq!(0x40, memory.peek(address).unwrap());
+  }
+
+  #[test]
+  fn unmapped_cartridge_reads_open_bus() {
+    let mut memory = Nes {
+      mapper: Box::new(mapper::mmc1::Mmc1::new(mapper::Board::with_program_rom(
+        vec![0; 0x8000],
+      ))),
+      ..Nes::default()
+    };
+    // Disables program RAM by shifting 0b1_0000 into the program bank register
+    for bit in [0, 0, 0, 0, 1] {
+      memory.write(0xE000, bit).unwrap();
     }
+
+    assert_eq!(0x50, memory.read(0x5000).unwrap());
+    assert_eq!(0x60, memory.read(0x6000).unwrap());
+  }
+
+  #[test]
+  fn ppu_registers_mirror() {
+    let mut memory = Nes::default();
+
+    memory.write(0x3FF6, 0x3F).unwrap();
+    memory.write(0x2006, 0x01).unwrap();
+    memory.write(0x2007, 0x21).unwrap();
+
+    assert_eq!(0x21, memory.ppu.peek(0x3F01, memory.mapper.as_ref()));
+  }
+
+  #[test]
+  fn apu_status() {
+    let mut memory = Nes::default();
+
+    memory.write(0x4015, 0x01).unwrap();
+    memory.write(0x4003, 0x08).unwrap();
+
+    assert_eq!(0x01, memory.read(0x4015).unwrap());
+  }
+
+  #[test]
+  fn controller_ports() {
+    let mut memory = Nes::default();
+    memory.ports[1]
+      .as_mut()
+      .unwrap()
+      .set_input(input::Input::Buttons {
+        socket: 0,
+        buttons: input::Buttons::from(input::Button::B),
+      });
+
+    memory.write(0x4016, 0x01).unwrap();
+    memory.write(0x4016, 0x00).unwrap();
+
+    assert_eq!(0x40, memory.read(0x4016).unwrap());
+    assert_eq!(0x40, memory.read(0x4017).unwrap());
+    assert_eq!(0x41, memory.read(0x4017).unwrap());
+  }
+
+  #[test]
+  fn empty_port() {
+    let mut memory = Nes::default();
+    memory.ports[0] = None;
+
+    memory.write(0x4016, 0x01).unwrap();
+
+    assert_eq!(0x40, memory.read(0x4016).unwrap());
+  }
+
+  #[test]
+  fn oam_dma() {
+    let mut memory = Nes::default();
+    memory.ram[0x0200..0x0300].fill(0x12);
+    memory.write(0x2003, 0x80).unwrap();
+
+    memory.write(constant::OAM_DMA, 0x02).unwrap();
+
+    assert!(memory.ppu.oam.iter().all(|&byte| byte == 0x12));
+    assert_eq!(513, memory.tick(0));
   }
 }
@@ -0,0 +1,491 @@
+//! Emulation of the 2C02 picture processing unit, rendering into a software framebuffer
+//!
+//! See <https://www.nesdev.org/wiki/PPU>.
+
+pub mod palette;
+mod render;
+
+use std::fmt;
+
+use crate::{
+  cartridge::{Mapper, Mirroring},
+  memory,
+};
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 240;
+/// Dots (PPU cycles) in each scanline
+pub const DOTS: u16 = 341;
+/// Scanlines in each frame, including those in VBlank and the pre-render scanline
+pub const SCANLINES: u16 = 262;
+/// Scanline at the start of which the VBlank flag is set
+pub const VBLANK_SCANLINE: u16 = 241;
+/// Scanline before the first visible scanline, which fetches the first tiles but outputs nothing
+pub const PRE_RENDER_SCANLINE: u16 = 261;
+/// The PPU runs three dots for every CPU cycle
+pub const DOTS_PER_CYCLE: u16 = 3;
+pub const OAM_SIZE: usize = 0x100;
+
+// Register numbers, as offsets from $2000
+pub const CONTROL: memory::Address = 0;
+pub const MASK: memory::Address = 1;
+pub const STATUS: memory::Address = 2;
+pub const OAM_ADDRESS: memory::Address = 3;
+pub const OAM_DATA: memory::Address = 4;
+pub const SCROLL: memory::Address = 5;
+pub const ADDRESS: memory::Address = 6;
+pub const DATA: memory::Address = 7;
+
+const CONTROL_NAMETABLE: u8 = 0b0000_0011;
+const CONTROL_INCREMENT: u8 = 0b0000_0100;
+const CONTROL_SPRITE_TABLE: u8 = 0b0000_1000;
+const CONTROL_BACKGROUND_TABLE: u8 = 0b0001_0000;
+const CONTROL_SPRITE_SIZE: u8 = 0b0010_0000;
+const CONTROL_NMI: u8 = 0b1000_0000;
+
+const MASK_GREYSCALE: u8 = 0b0000_0001;
+const MASK_BACKGROUND_LEFT: u8 = 0b0000_0010;
+const MASK_SPRITES_LEFT: u8 = 0b0000_0100;
+const MASK_BACKGROUND: u8 = 0b0000_1000;
+const MASK_SPRITES: u8 = 0b0001_0000;
+
+const STATUS_OVERFLOW: u8 = 0b0010_0000;
+const STATUS_SPRITE_ZERO_HIT: u8 = 0b0100_0000;
+const STATUS_VBLANK: u8 = 0b1000_0000;
+/// Bits of the status register which are not driven, and so read back the last value written to any register
+const STATUS_OPEN_BUS: u8 = 0b0001_1111;
+
+/// The PPU address space is 14 bits wide
+const ADDRESS_MASK: memory::Address = 0x3FFF;
+/// The current and temporary VRAM addresses are 15 bits wide
+const VRAM_ADDRESS_MASK: memory::Address = 0x7FFF;
+const NAMETABLES_START: memory::Address = 0x2000;
+const NAMETABLE_SIZE: usize = 0x400;
+const PALETTE_START: memory::Address = 0x3F00;
+const PALETTE_SIZE: usize = 0x20;
+
+/// The 2C02 and its video memory, which fetches nametables and patterns from the cartridge through the mapper
+#[allow(clippy::struct_excessive_bools)]
+pub struct Ppu {
+  control: u8,
+  mask: u8,
+  vblank: bool,
+  sprite_zero_hit: bool,
+  sprite_overflow: bool,
+  /// Current VRAM address, which doubles as the scroll position while rendering
+  address: memory::Address,
+  /// Temporary VRAM address, holding the scroll position for the top left of the screen
+  temporary_address: memory::Address,
+  fine_x: u8,
+  /// Whether the next write to the scroll or address register is the second of the pair
+  write_toggle: bool,
+  /// Reads of PPU memory other than palettes are delayed through this buffer
+  read_buffer: u8,
+  /// Last value written to any register
+  latch: u8,
+  /// Object attribute memory, holding four bytes for each of the 64 sprites
+  pub oam: [u8; OAM_SIZE],
+  oam_address: u8,
+  /// Nametable memory, with room for four nametables on boards which provide the extra memory
+  vram: [u8; 4 * NAMETABLE_SIZE],
+  palette: [u8; PALETTE_SIZE],
+  dot: u16,
+  scanline: u16,
+  frame: u64,
+  background: render::Background,
+  sprites: [render::Sprite; 8],
+  sprite_count: usize,
+  /// RGB triples for each pixel, row by row
+  framebuffer: Box<[u8]>,
+}
+
+impl fmt::Debug for Ppu {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Ppu")
+      .field("control", &format_args!("{:#010b}", self.control))
+      .field("mask", &format_args!("{:#010b}", self.mask))
+      .field("status", &format_args!("{:#010b}", self.status()))
+      .field("address", &format_args!("{:#06X}", self.address))
+      .field(
+        "temporary_address",
+        &format_args!("{:#06X}", self.temporary_address),
+      )
+      .field("fine_x", &self.fine_x)
+      .field("oam_address", &format_args!("{:#04X}", self.oam_address))
+      .field("dot", &self.dot)
+      .field("scanline", &self.scanline)
+      .field("frame", &self.frame)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Default for Ppu {
+  fn default() -> Self {
+    Self {
+      control: 0,
+      mask: 0,
+      vblank: false,
+      sprite_zero_hit: false,
+      sprite_overflow: false,
+      address: 0,
+      temporary_address: 0,
+      fine_x: 0,
+      write_toggle: false,
+      read_buffer: 0,
+      latch: 0,
+      oam: [0; OAM_SIZE],
+      oam_address: 0,
+      vram: [0; 4 * NAMETABLE_SIZE],
+      palette: [0; PALETTE_SIZE],
+      dot: 0,
+      scanline: 0,
+      frame: 0,
+      background: render::Background::default(),
+      sprites: [render::Sprite::default(); 8],
+      sprite_count: 0,
+      framebuffer: vec![0; WIDTH * HEIGHT * 3].into_boxed_slice(),
+    }
+  }
+}
+
+impl Ppu {
+  /// Dot (PPU cycle) within the current scanline, 0-340
+  #[must_use]
+  pub fn dot(&self) -> u16 {
+    self.dot
+  }
+
+  /// Current scanline, 0-261, where 0-239 are visible
+  #[must_use]
+  pub fn scanline(&self) -> u16 {
+    self.scanline
+  }
+
+  /// Number of frames completed
+  #[must_use]
+  pub fn frame(&self) -> u64 {
+    self.frame
+  }
+
+  /// RGB triples for each pixel of the last rendered image, row by row from the top left
+  #[must_use]
+  pub fn framebuffer(&self) -> &[u8] {
+    &self.framebuffer
+  }
+
+  /// RGB value of a pixel of the last rendered image
+  #[must_use]
+  pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+    let offset = (y * WIDTH + x) * 3;
+    [
+      self.framebuffer[offset],
+      self.framebuffer[offset + 1],
+      self.framebuffer[offset + 2],
+    ]
+  }
+
+  /// Whether the PPU is asserting the CPU's NMI line, which it does during VBlank if enabled
+  #[must_use]
+  pub fn nmi(&self) -> bool {
+    self.vblank && self.control & CONTROL_NMI != 0
+  }
+
+  fn status(&self) -> u8 {
+    let mut status = self.latch & STATUS_OPEN_BUS;
+    if self.sprite_overflow {
+      status |= STATUS_OVERFLOW;
+    }
+    if self.sprite_zero_hit {
+      status |= STATUS_SPRITE_ZERO_HIT;
+    }
+    if self.vblank {
+      status |= STATUS_VBLANK;
+    }
+    status
+  }
+
+  /// Reads a register, with any side effects that has on the PPU
+  pub fn read_register(&mut self, register: memory::Address, mapper: &mut dyn Mapper) -> u8 {
+    match register {
+      STATUS => {
+        let status = self.status();
+        self.vblank = false;
+        self.write_toggle = false;
+        status
+      }
+      DATA => {
+        let address = self.address & ADDRESS_MASK;
+        let data = self.read(address, mapper);
+        self.increment_address();
+        if address < PALETTE_START {
+          std::mem::replace(&mut self.read_buffer, data)
+        } else {
+          // Palette reads are immediate, but still fill the buffer with the nametable byte underneath
+          self.read_buffer = self.read(address - 0x1000, mapper);
+          data
+        }
+      }
+      _ => self.peek_register(register, mapper),
+    }
+  }
+
+  /// Reads a register without any side effects
+  #[must_use]
+  pub fn peek_register(&self, register: memory::Address, mapper: &dyn Mapper) -> u8 {
+    match register {
+      STATUS => self.status(),
+      OAM_DATA => self.oam[usize::from(self.oam_address)],
+      DATA => {
+        let address = self.address & ADDRESS_MASK;
+        if address < PALETTE_START {
+          self.read_buffer
+        } else {
+          self.peek(address, mapper)
+        }
+      }
+      // Write-only registers
+      _ => self.latch,
+    }
+  }
+
+  pub fn write_register(&mut self, register: memory::Address, data: u8, mapper: &mut dyn Mapper) {
+    self.latch = data;
+    match register {
+      CONTROL => {
+        self.control = data;
+        self.temporary_address =
+          self.temporary_address & 0x73FF | memory::Address::from(data & CONTROL_NAMETABLE) << 10;
+      }
+      MASK => self.mask = data,
+      OAM_ADDRESS => self.oam_address = data,
+      OAM_DATA => {
+        self.oam[usize::from(self.oam_address)] = data;
+        self.oam_address = self.oam_address.wrapping_add(1);
+      }
+      SCROLL => {
+        let data = memory::Address::from(data);
+        if self.write_toggle {
+          // Fine Y into bits 12-14, coarse Y into bits 5-9
+          self.temporary_address =
+            self.temporary_address & 0x0C1F | (data & 0x07) << 12 | (data & 0xF8) << 2;
+        } else {
+          self.temporary_address = self.temporary_address & 0x7FE0 | data >> 3;
+          let [fine_x, _] = (data & 0x07).to_le_bytes();
+          self.fine_x = fine_x;
+        }
+        self.write_toggle = !self.write_toggle;
+      }
+      ADDRESS => {
+        let data = memory::Address::from(data);
+        if self.write_toggle {
+          self.temporary_address = self.temporary_address & 0x7F00 | data;
+          self.address = self.temporary_address;
+          mapper.observe_ppu_address(self.address & ADDRESS_MASK);
+        } else {
+          self.temporary_address = self.temporary_address & 0x00FF | (data & 0x3F) << 8;
+        }
+        self.write_toggle = !self.write_toggle;
+      }
+      DATA => {
+        self.write(self.address & ADDRESS_MASK, data, mapper);
+        self.increment_address();
+      }
+      // The status register is read-only
+      _ => {}
+    }
+  }
+
+  /// Moves the VRAM address on after an access through the data register
+  fn increment_address(&mut self) {
+    let step = if self.control & CONTROL_INCREMENT == 0 {
+      1
+    } else {
+      32
+    };
+    self.address = self.address.wrapping_add(step) & VRAM_ADDRESS_MASK;
+  }
+
+  /// Reads from the PPU address space without any side effects
+  #[must_use]
+  pub fn peek(&self, address: memory::Address, mapper: &dyn Mapper) -> u8 {
+    let address = address & ADDRESS_MASK;
+    match address {
+      0..NAMETABLES_START => mapper.read_character(address),
+      NAMETABLES_START..PALETTE_START => self.vram[nametable_index(address, mapper.mirroring())],
+      _ => self.palette[palette_index(address)],
+    }
+  }
+
+  /// Reads from the PPU address space, letting the mapper observe the address
+  fn read(&mut self, address: memory::Address, mapper: &mut dyn Mapper) -> u8 {
+    mapper.observe_ppu_address(address);
+    self.peek(address, mapper)
+  }
+
+  fn write(&mut self, address: memory::Address, data: u8, mapper: &mut dyn Mapper) {
+    mapper.observe_ppu_address(address);
+    match address {
+      0..NAMETABLES_START => mapper.write_character(address, data),
+      NAMETABLES_START..PALETTE_START => {
+        self.vram[nametable_index(address, mapper.mirroring())] = data;
+      }
+      // Palette entries are only six bits wide
+      _ => self.palette[palette_index(address)] = data & 0x3F,
+    }
+  }
+}
+
+/// Index into nametable memory for an address in $2000-$3EFF
+fn nametable_index(address: memory::Address, mirroring: Mirroring) -> usize {
+  // $3000-$3EFF mirrors $2000-$2EFF
+  let offset = usize::from(address - NAMETABLES_START) % (4 * NAMETABLE_SIZE);
+  let table = offset / NAMETABLE_SIZE;
+  let table = match mirroring {
+    Mirroring::Horizontal => table / 2,
+    Mirroring::Vertical => table % 2,
+    Mirroring::FourScreen => table,
+    Mirroring::SingleScreenLower => 0,
+    Mirroring::SingleScreenUpper => 1,
+  };
+  table * NAMETABLE_SIZE + offset % NAMETABLE_SIZE
+}
+
+/// Index into palette memory for an address in $3F00-$3FFF
+///
+/// The backdrop colour of each sprite palette mirrors the one for the background palette.
+fn palette_index(address: memory::Address) -> usize {
+  let index = usize::from(address) % PALETTE_SIZE;
+  if index >= 0x10 && index.is_multiple_of(4) {
+    index - 0x10
+  } else {
+    index
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+  use crate::cartridge::mapper::{nrom::Nrom, Board};
+
+  pub(super) fn mapper(mirroring: Mirroring) -> Nrom {
+    let mut board = Board::with_program_rom(vec![0; 0x8000]);
+    board.mirroring = mirroring;
+    Nrom::new(board)
+  }
+
+  pub(super) fn write(ppu: &mut Ppu, mapper: &mut Nrom, address: memory::Address, data: &[u8]) {
+    let [low, high] = address.to_le_bytes();
+    ppu.write_register(ADDRESS, high, mapper);
+    ppu.write_register(ADDRESS, low, mapper);
+    for &byte in data {
+      ppu.write_register(DATA, byte, mapper);
+    }
+  }
+
+  #[test]
+  fn data_reads_are_buffered() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    write(&mut ppu, &mut mapper, 0x2100, &[0x12, 0x34]);
+
+    write(&mut ppu, &mut mapper, 0x2100, &[]);
+
+    assert_eq!(0x00, ppu.read_register(DATA, &mut mapper));
+    assert_eq!(0x12, ppu.read_register(DATA, &mut mapper));
+    assert_eq!(0x34, ppu.read_register(DATA, &mut mapper));
+  }
+
+  #[test]
+  fn palette_reads_are_immediate() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    write(&mut ppu, &mut mapper, 0x3F01, &[0x2C]);
+
+    write(&mut ppu, &mut mapper, 0x3F01, &[]);
+
+    assert_eq!(0x2C, ppu.read_register(DATA, &mut mapper));
+  }
+
+  #[test]
+  fn increment_across() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    ppu.write_register(CONTROL, CONTROL_INCREMENT, &mut mapper);
+
+    write(&mut ppu, &mut mapper, 0x2000, &[1, 2]);
+
+    assert_eq!(2, ppu.peek(0x2020, &mapper));
+    assert_eq!(0x2040, ppu.address);
+  }
+
+  #[test_case(Mirroring::Horizontal, 0x2400 => 0x12)]
+  #[test_case(Mirroring::Horizontal, 0x2800 => 0x00)]
+  #[test_case(Mirroring::Vertical, 0x2400 => 0x00)]
+  #[test_case(Mirroring::Vertical, 0x2800 => 0x12)]
+  #[test_case(Mirroring::SingleScreenLower, 0x2C00 => 0x12)]
+  #[test_case(Mirroring::Vertical, 0x3000 => 0x12)]
+  fn nametable_mirroring(mirroring: Mirroring, address: memory::Address) -> u8 {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(mirroring);
+    write(&mut ppu, &mut mapper, 0x2000, &[0x12]);
+
+    ppu.peek(address, &mapper)
+  }
+
+  #[test]
+  fn palette_mirroring() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+
+    write(&mut ppu, &mut mapper, 0x3F10, &[0x21]);
+
+    assert_eq!(0x21, ppu.peek(0x3F00, &mapper));
+    assert_eq!(0x21, ppu.peek(0x3F20, &mapper));
+  }
+
+  #[test]
+  fn scroll_sets_temporary_address() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+
+    ppu.write_register(CONTROL, 0b01, &mut mapper);
+    ppu.write_register(SCROLL, 0x7D, &mut mapper);
+    ppu.write_register(SCROLL, 0x5E, &mut mapper);
+
+    // Fine Y, nametable, coarse Y, coarse X: 110 01 01011 01111
+    assert_eq!(0x656F, ppu.temporary_address);
+    assert_eq!(0b101, ppu.fine_x);
+  }
+
+  #[test]
+  fn status_read_clears_vblank() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    ppu.vblank = true;
+    ppu.write_toggle = true;
+
+    assert_eq!(
+      STATUS_VBLANK,
+      ppu.read_register(STATUS, &mut mapper) & STATUS_VBLANK
+    );
+    assert_eq!(0, ppu.read_register(STATUS, &mut mapper) & STATUS_VBLANK);
+    assert!(!ppu.write_toggle);
+  }
+
+  #[test]
+  fn oam_data() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+
+    ppu.write_register(OAM_ADDRESS, 0xFF, &mut mapper);
+    ppu.write_register(OAM_DATA, 0x12, &mut mapper);
+    ppu.write_register(OAM_DATA, 0x34, &mut mapper);
+
+    ppu.write_register(OAM_ADDRESS, 0x00, &mut mapper);
+
+    assert_eq!(0x12, ppu.oam[0xFF]);
+    assert_eq!(0x34, ppu.peek_register(OAM_DATA, &mapper));
+  }
+}
@@ -0,0 +1,481 @@
+use super::{
+  palette, palette_index, Ppu, CONTROL_BACKGROUND_TABLE, CONTROL_SPRITE_SIZE, CONTROL_SPRITE_TABLE,
+  DOTS, HEIGHT, MASK_BACKGROUND, MASK_BACKGROUND_LEFT, MASK_GREYSCALE, MASK_SPRITES,
+  MASK_SPRITES_LEFT, NAMETABLES_START, PALETTE_START, PRE_RENDER_SCANLINE, SCANLINES,
+  VBLANK_SCANLINE, WIDTH,
+};
+use crate::{cartridge::Mapper, memory};
+
+/// Bytes fetched for the upcoming background tiles
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct Background {
+  nametable: u8,
+  /// Palette number of the tile, shifted into bits 2-3
+  attribute: u8,
+  low_tile: u8,
+  high_tile: u8,
+  /// Four-bit palette indices for the pixels of the current and next tiles, leftmost pixel first
+  tiles: u64,
+}
+
+/// A sprite selected for the current scanline
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct Sprite {
+  /// Four-bit palette indices for each pixel, leftmost pixel first
+  pattern: u32,
+  x: u8,
+  behind_background: bool,
+  /// Whether this is sprite 0 in OAM, which sets the sprite zero hit flag
+  zero: bool,
+}
+
+const SPRITE_PALETTE: u8 = 0b0000_0011;
+const SPRITE_BEHIND_BACKGROUND: u8 = 0b0010_0000;
+const SPRITE_FLIP_HORIZONTAL: u8 = 0b0100_0000;
+const SPRITE_FLIP_VERTICAL: u8 = 0b1000_0000;
+/// Sprite palettes follow the four background palettes
+const SPRITE_PALETTES: u8 = 0x10;
+/// Tile fetched for unused sprite slots
+const UNUSED_SPRITE_TILE: u8 = 0xFF;
+const PATTERN_TABLE_SIZE: memory::Address = 0x1000;
+
+impl Ppu {
+  /// Runs the PPU for a single dot
+  pub fn tick(&mut self, mapper: &mut dyn Mapper) {
+    self.advance();
+
+    let pre_render_line = self.scanline == PRE_RENDER_SCANLINE;
+    let visible_line = usize::from(self.scanline) < HEIGHT;
+    let render_line = pre_render_line || visible_line;
+    let visible_dot = (1..=256).contains(&self.dot);
+    let fetch_dot = visible_dot || (321..=336).contains(&self.dot);
+
+    if self.rendering_enabled() {
+      if visible_line && visible_dot {
+        self.render_pixel();
+      }
+      if render_line && fetch_dot {
+        self.background.tiles <<= 4;
+        match self.dot % 8 {
+          1 => self.fetch_nametable_byte(mapper),
+          3 => self.fetch_attribute_byte(mapper),
+          5 => self.fetch_tile_byte(mapper, false),
+          7 => self.fetch_tile_byte(mapper, true),
+          0 => self.store_tile(),
+          _ => {}
+        }
+      }
+      if pre_render_line && (280..=304).contains(&self.dot) {
+        self.copy_y();
+      }
+      if render_line {
+        if fetch_dot && self.dot.is_multiple_of(8) {
+          self.increment_x();
+        }
+        if self.dot == 256 {
+          self.increment_y();
+        }
+        if self.dot == 257 {
+          self.copy_x();
+          self.evaluate_sprites(mapper, visible_line);
+        }
+      }
+    }
+
+    if self.scanline == VBLANK_SCANLINE && self.dot == 1 {
+      self.vblank = true;
+    }
+    if pre_render_line && self.dot == 1 {
+      self.vblank = false;
+      self.sprite_zero_hit = false;
+      self.sprite_overflow = false;
+    }
+  }
+
+  fn rendering_enabled(&self) -> bool {
+    self.mask & (MASK_BACKGROUND | MASK_SPRITES) != 0
+  }
+
+  /// Moves on to the next dot, scanline and frame
+  fn advance(&mut self) {
+    // The last dot of the pre-render scanline is skipped on odd frames while rendering
+    if self.rendering_enabled()
+      && self.frame % 2 == 1
+      && self.scanline == PRE_RENDER_SCANLINE
+      && self.dot == DOTS - 2
+    {
+      self.dot = DOTS - 1;
+    }
+
+    self.dot += 1;
+    if self.dot == DOTS {
+      self.dot = 0;
+      self.scanline += 1;
+      if self.scanline == SCANLINES {
+        self.scanline = 0;
+        self.frame += 1;
+      }
+    }
+  }
+
+  fn render_pixel(&mut self) {
+    let x = usize::from(self.dot - 1);
+    let y = usize::from(self.scanline);
+    let left_edge = x < 8;
+
+    let background = if self.mask & MASK_BACKGROUND != 0
+      && !(left_edge && self.mask & MASK_BACKGROUND_LEFT == 0)
+    {
+      self.background_pixel()
+    } else {
+      0
+    };
+    let sprite =
+      if self.mask & MASK_SPRITES != 0 && !(left_edge && self.mask & MASK_SPRITES_LEFT == 0) {
+        self.sprite_pixel(x)
+      } else {
+        None
+      };
+
+    let colour = match (background % 4 != 0, sprite) {
+      (false, None) => 0,
+      (false, Some((_, colour))) => SPRITE_PALETTES | colour,
+      (true, None) => background,
+      (true, Some((sprite, colour))) => {
+        // Sprite zero hits are never detected at the rightmost pixel
+        if sprite.zero && x < WIDTH - 1 {
+          self.sprite_zero_hit = true;
+        }
+        if sprite.behind_background {
+          background
+        } else {
+          SPRITE_PALETTES | colour
+        }
+      }
+    };
+
+    let mut index = self.palette[palette_index(PALETTE_START | memory::Address::from(colour))];
+    if self.mask & MASK_GREYSCALE != 0 {
+      index &= 0x30;
+    }
+    let offset = (y * WIDTH + x) * 3;
+    self.framebuffer[offset..offset + 3].copy_from_slice(&palette::COLOURS[usize::from(index)]);
+  }
+
+  /// Palette index of the background at the current dot, taking fine X scroll into account
+  fn background_pixel(&self) -> u8 {
+    let [pixel, ..] =
+      (self.background.tiles >> (32 + (7 - u32::from(self.fine_x)) * 4)).to_le_bytes();
+    pixel & 0x0F
+  }
+
+  /// Frontmost opaque sprite pixel at a column, with its palette index
+  fn sprite_pixel(&self, x: usize) -> Option<(Sprite, u8)> {
+    self.sprites[..self.sprite_count].iter().find_map(|sprite| {
+      let offset = x
+        .checked_sub(usize::from(sprite.x))
+        .filter(|&offset| offset < 8)?;
+      let [pixel, ..] = (sprite.pattern >> ((7 - offset) * 4)).to_le_bytes();
+      let colour = pixel & 0x0F;
+      (colour % 4 != 0).then_some((*sprite, colour))
+    })
+  }
+
+  fn fetch_nametable_byte(&mut self, mapper: &mut dyn Mapper) {
+    let address = NAMETABLES_START | self.address & 0x0FFF;
+    self.background.nametable = self.read(address, mapper);
+  }
+
+  fn fetch_attribute_byte(&mut self, mapper: &mut dyn Mapper) {
+    let v = self.address;
+    // Each attribute byte covers 4x4 tiles, in four quadrants of 2x2 tiles
+    let address = 0x23C0 | v & 0x0C00 | (v >> 4) & 0x38 | (v >> 2) & 0x07;
+    let shift = (v >> 4) & 0x04 | v & 0x02;
+    self.background.attribute = (self.read(address, mapper) >> shift & 0b11) << 2;
+  }
+
+  fn fetch_tile_byte(&mut self, mapper: &mut dyn Mapper, high: bool) {
+    let fine_y = (self.address >> 12) & 0x07;
+    let table = if self.control & CONTROL_BACKGROUND_TABLE == 0 {
+      0
+    } else {
+      PATTERN_TABLE_SIZE
+    };
+    let address = table + 16 * memory::Address::from(self.background.nametable) + fine_y;
+    if high {
+      self.background.high_tile = self.read(address + 8, mapper);
+    } else {
+      self.background.low_tile = self.read(address, mapper);
+    }
+  }
+
+  /// Moves the fetched tile into the low half of the shift register
+  fn store_tile(&mut self) {
+    let Background {
+      attribute,
+      mut low_tile,
+      mut high_tile,
+      ..
+    } = self.background;
+    let mut tile = 0;
+    for _ in 0..8 {
+      let pixel = attribute | (low_tile & 0x80) >> 7 | (high_tile & 0x80) >> 6;
+      low_tile <<= 1;
+      high_tile <<= 1;
+      tile = tile << 4 | u64::from(pixel);
+    }
+    self.background.tiles |= tile;
+  }
+
+  /// Moves the coarse X scroll on to the next tile, wrapping into the horizontally adjacent nametable
+  fn increment_x(&mut self) {
+    if self.address & 0x001F == 31 {
+      self.address &= !0x001F;
+      self.address ^= 0x0400;
+    } else {
+      self.address += 1;
+    }
+  }
+
+  /// Moves the Y scroll on to the next row of pixels, wrapping into the vertically adjacent nametable
+  fn increment_y(&mut self) {
+    if self.address & 0x7000 != 0x7000 {
+      self.address += 0x1000;
+      return;
+    }
+    self.address &= !0x7000;
+    let coarse_y = match (self.address & 0x03E0) >> 5 {
+      29 => {
+        self.address ^= 0x0800;
+        0
+      }
+      // Rows 30 and 31 are attribute data, and wrap without changing nametable
+      31 => 0,
+      coarse_y => coarse_y + 1,
+    };
+    self.address = self.address & !0x03E0 | coarse_y << 5;
+  }
+
+  fn copy_x(&mut self) {
+    self.address = self.address & 0x7BE0 | self.temporary_address & 0x041F;
+  }
+
+  fn copy_y(&mut self) {
+    self.address = self.address & 0x041F | self.temporary_address & 0x7BE0;
+  }
+
+  fn sprite_height(&self) -> u16 {
+    if self.control & CONTROL_SPRITE_SIZE == 0 {
+      8
+    } else {
+      16
+    }
+  }
+
+  /// Selects the first eight sprites on the scanline and fetches their patterns for the next scanline
+  ///
+  /// Unused slots still fetch a pattern, as some mappers count scanlines by watching the fetches.
+  /// Overflow is detected without the hardware's buggy search, which only matters for a few games.
+  fn evaluate_sprites(&mut self, mapper: &mut dyn Mapper, visible_line: bool) {
+    let height = self.sprite_height();
+    let oam = self.oam;
+    let mut count = 0;
+    if visible_line {
+      for (index, entry) in oam.chunks_exact(4).enumerate() {
+        let Some(row) = self
+          .scanline
+          .checked_sub(u16::from(entry[0]))
+          .filter(|&row| row < height)
+        else {
+          continue;
+        };
+        if count == self.sprites.len() {
+          self.sprite_overflow = true;
+          break;
+        }
+        self.sprites[count] = Sprite {
+          pattern: self.fetch_sprite_pattern(mapper, entry[1], entry[2], row),
+          x: entry[3],
+          behind_background: entry[2] & SPRITE_BEHIND_BACKGROUND != 0,
+          zero: index == 0,
+        };
+        count += 1;
+      }
+    }
+    for _ in count..self.sprites.len() {
+      self.fetch_sprite_pattern(mapper, UNUSED_SPRITE_TILE, 0, 0);
+    }
+    self.sprite_count = count;
+  }
+
+  fn fetch_sprite_pattern(
+    &mut self,
+    mapper: &mut dyn Mapper,
+    tile: u8,
+    attributes: u8,
+    row: u16,
+  ) -> u32 {
+    let height = self.sprite_height();
+    let row = if attributes & SPRITE_FLIP_VERTICAL == 0 {
+      row
+    } else {
+      height - 1 - row
+    };
+    let tile = memory::Address::from(tile);
+    let address = if height == 8 {
+      let table = if self.control & CONTROL_SPRITE_TABLE == 0 {
+        0
+      } else {
+        PATTERN_TABLE_SIZE
+      };
+      table + 16 * tile + row
+    } else {
+      // Tall sprites take their table from the lowest bit of the tile number, and use a pair of tiles
+      let table = (tile & 1) * PATTERN_TABLE_SIZE;
+      table + 16 * (tile & !1) + if row < 8 { row } else { 16 + row - 8 }
+    };
+
+    // The PPU fetches two garbage nametable bytes before each sprite's pattern
+    let garbage = NAMETABLES_START | self.address & 0x0FFF;
+    self.read(garbage, mapper);
+    self.read(garbage, mapper);
+    let mut low = self.read(address, mapper);
+    let mut high = self.read(address + 8, mapper);
+
+    let palette = (attributes & SPRITE_PALETTE) << 2;
+    let mut pattern = 0;
+    for _ in 0..8 {
+      let pixel = if attributes & SPRITE_FLIP_HORIZONTAL == 0 {
+        let pixel = (low & 0x80) >> 7 | (high & 0x80) >> 6;
+        low <<= 1;
+        high <<= 1;
+        pixel
+      } else {
+        let pixel = low & 0x01 | (high & 0x01) << 1;
+        low >>= 1;
+        high >>= 1;
+        pixel
+      };
+      pattern = pattern << 4 | u32::from(palette | pixel);
+    }
+    pattern
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    cartridge::Mirroring,
+    ppu::{
+      tests::{mapper, write},
+      ADDRESS, CONTROL, CONTROL_NMI, DATA, MASK, SCROLL,
+    },
+  };
+
+  /// Runs until the start of the next VBlank, so a whole frame has been rendered
+  fn run_frame(ppu: &mut Ppu, mapper: &mut dyn Mapper) {
+    let frame = ppu.frame;
+    while ppu.frame == frame {
+      ppu.tick(mapper);
+    }
+    while !ppu.vblank {
+      ppu.tick(mapper);
+    }
+  }
+
+  /// Sets up tile 1 as a solid block of colour 3 and a palette in which it is white on black
+  fn setup(ppu: &mut Ppu, mapper: &mut dyn Mapper) {
+    for row in 0..8 {
+      mapper.write_character(0x10 + row, 0xFF);
+      mapper.write_character(0x18 + row, 0xFF);
+    }
+    let [mut low, mut high] = PALETTE_START.to_le_bytes();
+    ppu.write_register(ADDRESS, high, mapper);
+    ppu.write_register(ADDRESS, low, mapper);
+    for colour in [0x0F, 0x00, 0x10, 0x30] {
+      ppu.write_register(DATA, colour, mapper);
+    }
+    [low, high] = 0x3F10_u16.to_le_bytes();
+    ppu.write_register(ADDRESS, high, mapper);
+    ppu.write_register(ADDRESS, low, mapper);
+    for colour in [0x0F, 0x16, 0x16, 0x16] {
+      ppu.write_register(DATA, colour, mapper);
+    }
+    // Hide every sprite below the screen
+    for sprite in ppu.oam.chunks_exact_mut(4) {
+      sprite[0] = 0xFF;
+    }
+  }
+
+  /// Scrolls back to the top left, as writing the address register also moves the scroll position
+  fn reset_scroll(ppu: &mut Ppu, mapper: &mut dyn Mapper) {
+    ppu.write_register(CONTROL, 0, mapper);
+    ppu.write_register(SCROLL, 0, mapper);
+    ppu.write_register(SCROLL, 0, mapper);
+  }
+
+  #[test]
+  fn vblank_raises_nmi() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    ppu.write_register(CONTROL, CONTROL_NMI, &mut mapper);
+
+    run_frame(&mut ppu, &mut mapper);
+
+    assert!(ppu.nmi());
+    assert_eq!((VBLANK_SCANLINE, 1), (ppu.scanline, ppu.dot));
+  }
+
+  #[test]
+  fn renders_background() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    setup(&mut ppu, &mut mapper);
+    // Tile 1 at the second tile of the second row
+    write(&mut ppu, &mut mapper, 0x2021, &[0x01]);
+    reset_scroll(&mut ppu, &mut mapper);
+    ppu.write_register(MASK, MASK_BACKGROUND | MASK_BACKGROUND_LEFT, &mut mapper);
+
+    run_frame(&mut ppu, &mut mapper);
+
+    assert_eq!(palette::COLOURS[0x30], ppu.pixel(8, 8));
+    assert_eq!(palette::COLOURS[0x30], ppu.pixel(15, 15));
+    assert_eq!(palette::COLOURS[0x0F], ppu.pixel(7, 8));
+    assert_eq!(palette::COLOURS[0x0F], ppu.pixel(16, 16));
+  }
+
+  #[test]
+  fn renders_sprite_with_zero_hit() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    setup(&mut ppu, &mut mapper);
+    write(&mut ppu, &mut mapper, 0x2021, &[0x01]);
+    reset_scroll(&mut ppu, &mut mapper);
+    // Sprite 0 overlaps the background tile, sprite 1 is over the backdrop
+    ppu.oam[..8].copy_from_slice(&[11, 0x01, 0x00, 12, 99, 0x01, 0x00, 100]);
+    ppu.write_register(MASK, MASK_BACKGROUND | MASK_SPRITES, &mut mapper);
+
+    run_frame(&mut ppu, &mut mapper);
+
+    assert!(ppu.sprite_zero_hit);
+    assert_eq!(palette::COLOURS[0x16], ppu.pixel(100, 100));
+    assert_eq!(palette::COLOURS[0x16], ppu.pixel(12, 12));
+    assert_eq!(palette::COLOURS[0x0F], ppu.pixel(100, 99));
+  }
+
+  #[test]
+  fn sprite_overflow() {
+    let mut ppu = Ppu::default();
+    let mut mapper = mapper(Mirroring::Vertical);
+    setup(&mut ppu, &mut mapper);
+    for sprite in ppu.oam.chunks_exact_mut(4).take(9) {
+      sprite[0] = 50;
+    }
+    ppu.write_register(MASK, MASK_SPRITES, &mut mapper);
+
+    run_frame(&mut ppu, &mut mapper);
+
+    assert!(ppu.sprite_overflow);
+  }
+}
@@ -0,0 +1,164 @@
+//! Writing the PPU's framebuffer out as an image file
+//!
+//! PNG images are written uncompressed, using deflate's stored blocks, so no compression library is needed.
+//! See <https://www.w3.org/TR/png/> and <https://netpbm.sourceforge.net/doc/ppm.html>.
+
+use std::{
+  io::{self, Write},
+  path::Path,
+};
+
+use strum::EnumString;
+
+use crate::ppu;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// 8 bits per sample, RGB colour, default compression, filtering and no interlacing
+const PNG_HEADER_FORMAT: [u8; 5] = [8, 2, 0, 0, 0];
+/// Deflate with a 32K window, at the fastest compression level
+const ZLIB_HEADER: [u8; 2] = [0x78, 0x01];
+const STORED_BLOCK_SIZE: usize = 0xFFFF;
+/// Each row of a PNG starts with the filter used for it
+const FILTER_NONE: u8 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Format {
+  Png,
+  Ppm,
+}
+
+impl Format {
+  /// Chooses the format from a file's extension
+  #[must_use]
+  pub fn from_path(path: &Path) -> Option<Self> {
+    path.extension()?.to_str()?.parse().ok()
+  }
+}
+
+/// Writes a framebuffer of RGB triples, such as [`ppu::Ppu::framebuffer`], as an image
+///
+/// # Errors
+/// Forwards any error from the writer
+pub fn write(writer: &mut impl Write, format: Format, framebuffer: &[u8]) -> io::Result<()> {
+  match format {
+    Format::Png => write_png(writer, framebuffer),
+    Format::Ppm => write_ppm(writer, framebuffer),
+  }
+}
+
+fn write_ppm(writer: &mut impl Write, framebuffer: &[u8]) -> io::Result<()> {
+  write!(writer, "P6\n{} {}\n255\n", ppu::WIDTH, ppu::HEIGHT)?;
+  writer.write_all(framebuffer)
+}
+
+fn write_png(writer: &mut impl Write, framebuffer: &[u8]) -> io::Result<()> {
+  writer.write_all(&PNG_SIGNATURE)?;
+
+  let mut header = Vec::new();
+  for size in [ppu::WIDTH, ppu::HEIGHT] {
+    let size =
+      u32::try_from(size).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    header.extend_from_slice(&size.to_be_bytes());
+  }
+  header.extend_from_slice(&PNG_HEADER_FORMAT);
+  write_chunk(writer, *b"IHDR", &header)?;
+
+  let mut rows = Vec::with_capacity(framebuffer.len() + ppu::HEIGHT);
+  for row in framebuffer.chunks(ppu::WIDTH * 3) {
+    rows.push(FILTER_NONE);
+    rows.extend_from_slice(row);
+  }
+  let mut data = ZLIB_HEADER.to_vec();
+  let blocks = rows.chunks(STORED_BLOCK_SIZE).count();
+  for (index, block) in rows.chunks(STORED_BLOCK_SIZE).enumerate() {
+    data.push(u8::from(index + 1 == blocks));
+    let [low, high] = u16::try_from(block.len()).unwrap_or(u16::MAX).to_le_bytes();
+    data.extend_from_slice(&[low, high, !low, !high]);
+    data.extend_from_slice(block);
+  }
+  data.extend_from_slice(&adler32(&rows).to_be_bytes());
+  write_chunk(writer, *b"IDAT", &data)?;
+
+  write_chunk(writer, *b"IEND", &[])
+}
+
+fn write_chunk(writer: &mut impl Write, kind: [u8; 4], data: &[u8]) -> io::Result<()> {
+  let length =
+    u32::try_from(data.len()).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+  writer.write_all(&length.to_be_bytes())?;
+  writer.write_all(&kind)?;
+  writer.write_all(data)?;
+  writer.write_all(&crc32(kind.iter().chain(data)).to_be_bytes())
+}
+
+/// The CRC-32 used by PNG chunks, computed a bit at a time since images are only written occasionally
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+  !bytes.into_iter().fold(u32::MAX, |crc, &byte| {
+    (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+      if crc & 1 == 1 {
+        (crc >> 1) ^ 0xEDB8_8320
+      } else {
+        crc >> 1
+      }
+    })
+  })
+}
+
+/// The checksum at the end of a zlib stream
+fn adler32(bytes: &[u8]) -> u32 {
+  const MODULUS: u32 = 65521;
+  let (a, b) = bytes.iter().fold((1, 0), |(a, b), &byte| {
+    let a = (a + u32::from(byte)) % MODULUS;
+    (a, (b + a) % MODULUS)
+  });
+  b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn checksums() {
+    assert_eq!(0xAE42_6082, crc32(b"IEND"));
+    assert_eq!(0x11E6_0398, adler32(b"Wikipedia"));
+  }
+
+  #[test]
+  fn format_from_path() {
+    assert_eq!(Some(Format::Png), Format::from_path(Path::new("frame.PNG")));
+    assert_eq!(
+      Some(Format::Ppm),
+      Format::from_path(Path::new("out/frame.ppm"))
+    );
+    assert_eq!(None, Format::from_path(Path::new("frame.bmp")));
+  }
+
+  #[test]
+  fn ppm() {
+    let framebuffer = vec![0x12; ppu::WIDTH * ppu::HEIGHT * 3];
+    let mut image = Vec::new();
+
+    write(&mut image, Format::Ppm, &framebuffer).unwrap();
+
+    assert!(image.starts_with(b"P6\n256 240\n255\n\x12"));
+    assert_eq!(15 + framebuffer.len(), image.len());
+  }
+
+  #[test]
+  fn png() {
+    let framebuffer = vec![0x12; ppu::WIDTH * ppu::HEIGHT * 3];
+    let mut image = Vec::new();
+
+    write(&mut image, Format::Png, &framebuffer).unwrap();
+
+    assert!(image.starts_with(&PNG_SIGNATURE));
+    assert_eq!(&image[12..16], b"IHDR");
+    assert!(image.ends_with(&[b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    // Signature, three chunks' overhead, the header, and the zlib stream of stored blocks
+    let rows = framebuffer.len() + ppu::HEIGHT;
+    let blocks = rows.div_ceil(STORED_BLOCK_SIZE);
+    assert_eq!(8 + 3 * 12 + 13 + 2 + blocks * 5 + rows + 4, image.len());
+  }
+}
@@ -0,0 +1,275 @@
+//! Logging each instruction before it runs, for diffing against other emulators
+//!
+//! The default format matches `nestest.log` from Nintendulator, which most emulators can produce.
+//! See <https://www.nesdev.org/wiki/Emulator_tests>.
+
+use std::{
+  fmt,
+  io::{self, Write},
+};
+
+use strum::EnumString;
+
+use crate::{
+  cpu::{
+    self,
+    operation::{
+      addressing_mode::{Location, Mode, Value},
+      disassemble::Instruction,
+      opcode, Operation,
+    },
+  },
+  memory::{self, Bus},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Format {
+  /// Nintendulator's format, as used by `nestest.log`
+  #[default]
+  Nestest,
+  /// Space-separated fields: the program counter, instruction bytes, A, X, Y, P and SP in hexadecimal, then the
+  /// PPU scanline and dot and the CPU cycle count in decimal
+  Compact,
+}
+
+/// Writes a line to a log for each instruction the emulator runs
+pub struct Tracer {
+  writer: Box<dyn Write>,
+  format: Format,
+}
+
+impl fmt::Debug for Tracer {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Tracer")
+      .field("format", &self.format)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Tracer {
+  pub fn new(writer: impl Write + 'static, format: Format) -> Self {
+    Self {
+      writer: Box::new(writer),
+      format,
+    }
+  }
+
+  /// Writes a line for the instruction the CPU is about to run
+  ///
+  /// # Errors
+  /// Forwards any error from the writer
+  pub fn trace(&mut self, cpu: &cpu::Cpu) -> io::Result<()> {
+    writeln!(self.writer, "{}", line(cpu, self.format))
+  }
+
+  /// # Errors
+  /// Forwards any error from the writer
+  pub fn flush(&mut self) -> io::Result<()> {
+    self.writer.flush()
+  }
+}
+
+/// Describes the instruction the CPU is about to run, along with the state of the CPU and PPU
+#[must_use]
+pub fn line(cpu: &cpu::Cpu, format: Format) -> String {
+  let register = &cpu.register;
+  let ppu = &cpu.memory.ppu;
+  let address = register.program_counter;
+  let decoded = Operation::decode_from(&cpu.memory, address).ok();
+  let bytes: Vec<cpu::Int> = (0..decoded.map_or(1, |(_, length)| length))
+    .map(|offset| peek(cpu, address.wrapping_add(offset)))
+    .collect();
+  let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+
+  match format {
+    Format::Nestest => {
+      let official = opcode::lookup(bytes[0]).is_none_or(|opcode| opcode.official);
+      let assembly = match decoded {
+        Some((operation, _)) => annotated(cpu, Instruction { address, operation }),
+        None => format!(".byte ${:02X}", bytes[0]),
+      };
+      format!(
+        "{address:04X}  {:<8} {}{assembly:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        hex.join(" "),
+        if official { ' ' } else { '*' },
+        register.accumulator,
+        register.index_x,
+        register.index_y,
+        register.status.to_byte(),
+        register.stack_pointer,
+        ppu.scanline(),
+        ppu.dot(),
+        cpu.cycles,
+      )
+    }
+    Format::Compact => {
+      format!(
+        "{address:04X} {} {:02X} {:02X} {:02X} {:02X} {:02X} {} {} {}",
+        hex.concat(),
+        register.accumulator,
+        register.index_x,
+        register.index_y,
+        register.status.to_byte(),
+        register.stack_pointer,
+        ppu.scanline(),
+        ppu.dot(),
+        cpu.cycles,
+      )
+    }
+  }
+}
+
+/// Disassembles an instruction, followed by the addresses it resolves to and the value currently there
+fn annotated(cpu: &cpu::Cpu, instruction: Instruction) -> String {
+  use Location::*;
+  let operation = instruction.operation;
+  // Nintendulator calls ISC by its other name
+  let mnemonic = match operation {
+    Operation::Isc(_) => "ISB",
+    _ => operation.mnemonic(),
+  };
+  let (x, y) = (cpu.register.index_x, cpu.register.index_y);
+  let read = |address| peek(cpu, address);
+  let zero_page_pointer = |address: cpu::Int| {
+    memory::Address::from_le_bytes([read(address.into()), read(address.wrapping_add(1).into())])
+  };
+
+  let location = match (operation.mode(), operation.operand()) {
+    (Mode::Accumulator, _) => return format!("{mnemonic} A"),
+    (_, None) => return mnemonic.to_owned(),
+    (_, Some(Value::Immediate(value))) => return format!("{mnemonic} #${value:02X}"),
+    (_, Some(Value::Location(location))) => location,
+  };
+  let operand = match location {
+    Relative(_) => format!("${:04X}", instruction.branch_target().unwrap_or_default()),
+    Absolute(addr) if matches!(operation, Operation::Jmp(_) | Operation::Jsr(_)) => {
+      format!("${addr:04X}")
+    }
+    ZeroPage(addr) => format!("${addr:02X} = {:02X}", read(addr.into())),
+    Absolute(addr) => format!("${addr:04X} = {:02X}", read(addr)),
+    XIndexedZeroPage(addr) | YIndexedZeroPage(addr) => {
+      let (register, index) = if matches!(location, XIndexedZeroPage(_)) {
+        ('X', x)
+      } else {
+        ('Y', y)
+      };
+      let at = addr.wrapping_add(index);
+      format!(
+        "${addr:02X},{register} @ {at:02X} = {:02X}",
+        read(at.into())
+      )
+    }
+    XIndexedAbsolute(addr) | YIndexedAbsolute(addr) => {
+      let (register, index) = if matches!(location, XIndexedAbsolute(_)) {
+        ('X', x)
+      } else {
+        ('Y', y)
+      };
+      let at = addr.wrapping_add(index.into());
+      format!("${addr:04X},{register} @ {at:04X} = {:02X}", read(at))
+    }
+    Indirect(addr) => {
+      // The pointer's high byte is fetched without carrying into the page
+      let [low, high] = addr.to_le_bytes();
+      let high_addr = memory::Address::from_le_bytes([low.wrapping_add(1), high]);
+      let target = memory::Address::from_le_bytes([read(addr), read(high_addr)]);
+      format!("(${addr:04X}) = {target:04X}")
+    }
+    XIndexedIndirect(addr) => {
+      let pointer = addr.wrapping_add(x);
+      let at = zero_page_pointer(pointer);
+      format!(
+        "(${addr:02X},X) @ {pointer:02X} = {at:04X} = {:02X}",
+        read(at)
+      )
+    }
+    IndirectYIndexed(addr) => {
+      let base = zero_page_pointer(addr);
+      let at = base.wrapping_add(y.into());
+      format!("(${addr:02X}),Y = {base:04X} @ {at:04X} = {:02X}", read(at))
+    }
+  };
+  format!("{mnemonic} {operand}")
+}
+
+/// Peeks memory, reading unmapped addresses as zero
+fn peek(cpu: &cpu::Cpu, address: memory::Address) -> cpu::Int {
+  cpu.memory.peek(address).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use test_case::test_case;
+
+  use super::*;
+
+  /// A CPU which has just been reset into a program at the start of ROM
+  fn cpu(program: &[cpu::Int]) -> cpu::Cpu {
+    let mut cpu = cpu::Cpu::default();
+    cpu.load(program);
+    cpu.reset().unwrap();
+    cpu
+  }
+
+  #[test]
+  fn nestest_line() {
+    let cpu = cpu(&[0x4C, 0xF5, 0xC5]);
+
+    assert_eq!(
+      "8000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7",
+      line(&cpu, Format::Nestest)
+    );
+  }
+
+  #[test]
+  fn unofficial_opcode() {
+    let mut cpu = cpu(&[0xE7, 0x10]);
+    cpu.memory.ram[0x10] = 0x5A;
+
+    assert!(line(&cpu, Format::Nestest).starts_with("8000  E7 10    *ISB $10 = 5A    "));
+  }
+
+  #[test]
+  fn compact_line() {
+    let mut cpu = cpu(&[0xA9, 0x01]);
+    cpu.register.index_x = 0x12;
+
+    assert_eq!(
+      "8000 A901 00 12 00 24 FD 0 21 7",
+      line(&cpu, Format::Compact)
+    );
+  }
+
+  #[test_case(&[0xA5, 0x10] => "LDA $10 = 5A")]
+  #[test_case(&[0xB5, 0x0E] => "LDA $0E,X @ 10 = 5A")]
+  #[test_case(&[0xB6, 0x0F] => "LDX $0F,Y @ 10 = 5A")]
+  #[test_case(&[0x8D, 0x00, 0x02] => "STA $0200 = 34")]
+  #[test_case(&[0x9D, 0xFE, 0x01] => "STA $01FE,X @ 0200 = 34")]
+  #[test_case(&[0xA1, 0x1E] => "LDA ($1E,X) @ 20 = 0200 = 34")]
+  #[test_case(&[0xB1, 0x20] => "LDA ($20),Y = 0200 @ 0201 = 12")]
+  #[test_case(&[0x6C, 0xFF, 0x02] => "JMP ($02FF) = 3478")]
+  #[test_case(&[0x20, 0x00, 0x90] => "JSR $9000")]
+  #[test_case(&[0xD0, 0xFE] => "BNE $8000")]
+  #[test_case(&[0x4A] => "LSR A")]
+  #[test_case(&[0xA9, 0x01] => "LDA #$01")]
+  #[test_case(&[0xE8] => "INX")]
+  fn annotation(program: &[cpu::Int]) -> String {
+    let mut cpu = cpu(program);
+    cpu.register.index_x = 2;
+    cpu.register.index_y = 1;
+    cpu.memory.ram[0x10] = 0x5A;
+    cpu.memory.ram[0x20..0x22].copy_from_slice(&[0x00, 0x02]);
+    cpu.memory.ram[0x0200..0x0202].copy_from_slice(&[0x34, 0x12]);
+    cpu.memory.ram[0x02FF] = 0x78;
+
+    let (operation, _) = Operation::decode_from(&cpu.memory, 0x8000).unwrap();
+    annotated(
+      &cpu,
+      Instruction {
+        address: 0x8000,
+        operation,
+      },
+    )
+  }
+}